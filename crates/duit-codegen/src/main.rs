@@ -27,7 +27,10 @@ pub struct Args {
 fn main() -> anyhow::Result<()> {
     let args: Args = argh::from_env();
 
-    let spec = Spec::deserialize_from_str(&fs::read_to_string(&args.input)?)?;
+    let spec = Spec::deserialize_from_file(
+        &args.input.display().to_string(),
+        &fs::read_to_string(&args.input)?,
+    )?;
     let code = generate_code(&spec);
     let code = rustfmt(&code)?;
 
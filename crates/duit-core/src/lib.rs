@@ -1,8 +1,14 @@
 //! Code shared between `duit-codegen` and `duit`.
 
+pub mod source;
 pub mod spec;
 
-use serde::Deserialize;
+use std::fmt;
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
 pub enum Align {
@@ -41,3 +47,116 @@ impl Axis {
         }
     }
 }
+
+/// Distances from each edge of a box, used
+/// for padding, margins and border widths.
+///
+/// Deserializes, as in CSS, from a single number for all edges,
+/// `[vertical, horizontal]`, or `[top, right, bottom, left]`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Insets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Insets {
+    pub const ZERO: Insets = Insets::uniform(0.);
+
+    pub const fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    pub const fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+
+    pub const fn symmetric(vertical: f32, horizontal: f32) -> Self {
+        Self::new(vertical, horizontal, vertical, horizontal)
+    }
+
+    /// The sum of the left and right insets.
+    pub fn horizontal(self) -> f32 {
+        self.left + self.right
+    }
+
+    /// The sum of the top and bottom insets.
+    pub fn vertical(self) -> f32 {
+        self.top + self.bottom
+    }
+
+    pub fn is_uniform(self) -> bool {
+        self.top == self.right && self.top == self.bottom && self.top == self.left
+    }
+}
+
+impl From<f32> for Insets {
+    fn from(inset: f32) -> Self {
+        Insets::uniform(inset)
+    }
+}
+
+impl<'de> Deserialize<'de> for Insets {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct InsetsVisitor;
+
+        impl<'de> Visitor<'de> for InsetsVisitor {
+            type Value = Insets;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number, or a list of 2 or 4 numbers")
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Insets, E> {
+                Ok(Insets::uniform(v as f32))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Insets, E> {
+                Ok(Insets::uniform(v as f32))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Insets, E> {
+                Ok(Insets::uniform(v as f32))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Insets, A::Error> {
+                let mut values = Vec::new();
+                while let Some(value) = seq.next_element::<f32>()? {
+                    values.push(value);
+                }
+                match values[..] {
+                    [vertical, horizontal] => Ok(Insets::symmetric(vertical, horizontal)),
+                    [top, right, bottom, left] => Ok(Insets::new(top, right, bottom, left)),
+                    _ => Err(de::Error::invalid_length(values.len(), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(InsetsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_insets() {
+        let insets = |s: &str| serde_yaml::from_str::<Insets>(s);
+        assert_eq!(insets("5").unwrap(), Insets::uniform(5.));
+        assert_eq!(insets("2.5").unwrap(), Insets::uniform(2.5));
+        assert_eq!(insets("[1, 2]").unwrap(), Insets::new(1., 2., 1., 2.));
+        assert_eq!(insets("[1, 2, 3, 4]").unwrap(), Insets::new(1., 2., 3., 4.));
+        assert!(insets("[1, 2, 3]").is_err());
+        assert!(insets("wide").is_err());
+    }
+}
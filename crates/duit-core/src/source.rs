@@ -0,0 +1,515 @@
+//! Locations in YAML source files, used to point error
+//! messages at the text that caused them.
+
+use std::fmt;
+
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+/// A position in a source file. Lines and columns start at 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    fn from_marker(marker: Marker) -> Self {
+        Self {
+            line: marker.line(),
+            column: marker.col() + 1,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The text of a YAML file, along with the location
+/// of each mapping key and scalar in it.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    name: Option<String>,
+    text: String,
+    nodes: Vec<Node>,
+}
+
+/// A mapping key or scalar value.
+#[derive(Debug, Clone)]
+struct Node {
+    /// Keys and sequence indices leading to the node. For a key, this
+    /// ends with the key itself, so a key and its value share a path.
+    path: Vec<String>,
+    text: String,
+    location: Location,
+    is_key: bool,
+}
+
+impl SourceFile {
+    /// Indexes a YAML file that has no name.
+    ///
+    /// Syntax errors are left to the deserializer to report;
+    /// the nodes before the error are still indexed.
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let mut indexer = Indexer::default();
+        let _ = Parser::new(text.chars()).load(&mut indexer, false);
+        Self {
+            name: None,
+            text,
+            nodes: indexer.nodes,
+        }
+    }
+
+    /// Indexes a YAML file, naming it `name` in error messages.
+    pub fn with_name(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Self::new(text)
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Gets the location of the key at `path`, or of the
+    /// sequence element if the path ends with an index.
+    pub fn locate(&self, path: &[&str]) -> Option<Location> {
+        self.nodes
+            .iter()
+            .find(|node| node.path == path)
+            .map(|node| node.location)
+    }
+
+    /// Gets the location of the first key or scalar under `path` whose text
+    /// is `text`, or else the location of `path` itself.
+    pub fn locate_in(&self, path: &[&str], text: &str) -> Option<Location> {
+        self.nodes
+            .iter()
+            .find(|node| node.text == text && starts_with(&node.path, path))
+            .map(|node| node.location)
+            .or_else(|| self.locate(path))
+    }
+
+    /// Gets the keys of the mapping at `path`, in order.
+    pub fn keys<'a>(&'a self, path: &'a [&str]) -> impl Iterator<Item = &'a str> + 'a {
+        self.nodes
+            .iter()
+            .filter(move |node| {
+                node.is_key && node.path.len() == path.len() + 1 && starts_with(&node.path, path)
+            })
+            .map(|node| node.text.as_str())
+    }
+
+    /// Gets the location of the first scalar `value` set for `key`.
+    pub fn locate_value(&self, key: &str, value: &str) -> Option<Location> {
+        self.nodes
+            .iter()
+            .find(|node| {
+                !node.is_key
+                    && node.text == value
+                    && node.path.last().map(String::as_str) == Some(key)
+            })
+            .map(|node| node.location)
+    }
+
+    /// Gets the location the deserializer error `error` refers to.
+    ///
+    /// The location serde_yaml reports is wrong for errors inside
+    /// flattened or untagged structs, which are buffered before they
+    /// are deserialized. Instead, the key path at the start of the message
+    /// narrows down the nodes, and the field or value the message names
+    /// picks one of them.
+    pub fn locate_yaml_error(&self, error: &serde_yaml::Error) -> Option<Location> {
+        let message = error.to_string();
+        let (path, message) = split_path(strip_yaml_location(&message));
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let within: Vec<&Node> = self
+            .nodes
+            .iter()
+            .filter(|node| starts_with(&node.path, &path))
+            .collect();
+
+        if !message.starts_with("missing field") {
+            if let Some(name) = quoted_name(message) {
+                if let Some(node) = within.iter().find(|node| node.text == name) {
+                    return Some(node.location);
+                }
+            }
+        }
+
+        if path.is_empty() {
+            error.location().map(|location| Location {
+                line: location.line(),
+                column: location.column(),
+            })
+        } else {
+            within.first().map(|node| node.location)
+        }
+    }
+
+    /// Gets the line of text at `location`, for display under an error.
+    pub fn snippet(&self, location: Location) -> Snippet {
+        let line = self
+            .text
+            .lines()
+            .nth(location.line.saturating_sub(1))
+            .unwrap_or_default();
+        Snippet {
+            file: self.name.clone(),
+            location,
+            line: line.trim_end().to_owned(),
+        }
+    }
+}
+
+fn starts_with(path: &[String], prefix: &[&str]) -> bool {
+    path.len() >= prefix.len() && path.iter().zip(prefix).all(|(a, b)| a == b)
+}
+
+/// Removes the ` at line 1 column 2` that serde_yaml appends to
+/// error messages, for when the location is shown separately.
+pub fn strip_yaml_location(message: &str) -> &str {
+    match message.rsplit_once(" at line ") {
+        Some((message, location))
+            if location
+                .split(" column ")
+                .all(|n| n.parse::<usize>().is_ok()) =>
+        {
+            message
+        }
+        _ => message,
+    }
+}
+
+/// Splits the key path, like `child.Column.children[0]`, off the start
+/// of a serde_yaml error message.
+fn split_path(message: &str) -> (Vec<String>, &str) {
+    match message.split_once(": ") {
+        Some((path, rest)) if !path.contains(char::is_whitespace) && path != "." => {
+            let segments = path
+                .split('.')
+                .flat_map(|segment| segment.split(['[', ']'].as_ref()))
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_owned)
+                .collect();
+            (segments, rest)
+        }
+        _ => (Vec::new(), message),
+    }
+}
+
+/// Gets the first field name or value quoted in a message,
+/// like `foo` in ``unknown variant `foo`, expected ...``.
+fn quoted_name(message: &str) -> Option<&str> {
+    ['`', '"'].iter().find_map(|&quote| {
+        let start = message.find(quote)? + 1;
+        let len = message[start..].find(quote)?;
+        Some(&message[start..start + len])
+    })
+}
+
+/// Builds the nodes of a source file from parser events.
+#[derive(Default)]
+struct Indexer {
+    nodes: Vec<Node>,
+    path: Vec<String>,
+    stack: Vec<Collection>,
+    /// The index of a sequence element whose location is not yet known.
+    pending: Option<usize>,
+}
+
+enum Collection {
+    /// The key whose value is being parsed, if any.
+    Mapping(Option<String>),
+    /// The index of the element being parsed.
+    Sequence(usize),
+}
+
+impl Indexer {
+    /// Gets the path segment of a node starting in the current collection.
+    fn segment(&self) -> Option<String> {
+        match self.stack.last()? {
+            Collection::Mapping(key) => Some(key.clone().unwrap_or_default()),
+            Collection::Sequence(index) => Some(index.to_string()),
+        }
+    }
+
+    /// Moves on to the next node in the current collection.
+    fn advance(&mut self) {
+        match self.stack.last_mut() {
+            Some(Collection::Mapping(key)) => *key = None,
+            Some(Collection::Sequence(index)) => *index += 1,
+            None => {}
+        }
+    }
+
+    fn push_node(&mut self, text: String, marker: Marker, is_key: bool) {
+        let mut path = self.path.clone();
+        path.extend(self.segment());
+        self.nodes.push(Node {
+            path,
+            text,
+            location: Location::from_marker(marker),
+            is_key,
+        });
+    }
+}
+
+impl MarkedEventReceiver for Indexer {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        match event {
+            Event::Scalar(text, ..) => {
+                if let Some(i) = self.pending.take() {
+                    self.nodes[i].location = Location::from_marker(marker);
+                }
+                if let Some(Collection::Mapping(key @ None)) = self.stack.last_mut() {
+                    *key = Some(text.clone());
+                    self.push_node(text, marker, true);
+                } else {
+                    self.push_node(text, marker, false);
+                    self.advance();
+                }
+            }
+            Event::Alias(_) => {
+                self.push_node(String::new(), marker, false);
+                self.advance();
+            }
+            Event::MappingStart(_) | Event::SequenceStart(_) => {
+                if let Some(Collection::Sequence(_)) = self.stack.last() {
+                    // Elements have no key to point at, so record where they start.
+                    // The parser marks a block collection after its first token, so
+                    // the first scalar in it, if any, gives a better location.
+                    self.push_node(String::new(), marker, false);
+                    if self.pending.is_none() {
+                        self.pending = Some(self.nodes.len() - 1);
+                    }
+                }
+                let segment = self.segment();
+                self.path.extend(segment);
+                self.stack.push(match event {
+                    Event::MappingStart(_) => Collection::Mapping(None),
+                    _ => Collection::Sequence(0),
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                if !self.stack.is_empty() {
+                    self.path.pop();
+                }
+                self.advance();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A line of a source file, displayed below an error
+/// with a marker under the location it refers to.
+///
+/// ```text
+///  --> widgets/root.yml:5:15
+///   |
+/// 5 |           id: 1button
+///   |               ^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub file: Option<String>,
+    pub location: Location,
+    pub line: String,
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_number = self.location.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        match &self.file {
+            Some(file) => writeln!(f, "{}--> {}:{}", gutter, file, self.location)?,
+            None => writeln!(f, "{}--> {}", gutter, self.location)?,
+        }
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_number, self.line)?;
+        // Keep tabs, so the marker lines up however they are displayed.
+        let indent: String = self
+            .line
+            .chars()
+            .take(self.location.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(f, "{} | {}^", gutter, indent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    const SOURCE: &str = "\
+name: Root
+child:
+  Column:
+    children:
+      - Text: hello
+      - Button:
+          id: 1button
+";
+
+    #[test]
+    fn locates_keys_and_values() {
+        let source = SourceFile::with_name("root.yml", SOURCE);
+        assert_eq!(
+            source.locate(&["child", "Column", "children"]),
+            Some(Location { line: 4, column: 5 })
+        );
+        assert_eq!(
+            source.locate(&["child", "Column", "children", "1"]),
+            Some(Location { line: 6, column: 9 })
+        );
+        assert_eq!(
+            source.locate_value("id", "1button"),
+            Some(Location {
+                line: 7,
+                column: 15
+            })
+        );
+        assert_eq!(source.locate_value("Text", "Text"), None);
+        assert_eq!(
+            source.locate_in(&["child", "Column", "children", "0"], "hello"),
+            Some(Location {
+                line: 5,
+                column: 15
+            })
+        );
+        assert_eq!(
+            source.keys(&["child", "Column"]).collect::<Vec<_>>(),
+            ["children"]
+        );
+    }
+
+    #[test]
+    fn snippet() {
+        let source = SourceFile::with_name("root.yml", SOURCE);
+        let snippet = source.snippet(source.locate_value("id", "1button").unwrap());
+        assert_eq!(
+            snippet.to_string(),
+            " --> root.yml:7:15\n  |\n7 |           id: 1button\n  |               ^"
+        );
+    }
+
+    /// Checks `locate_yaml_error` against the messages serde_yaml 0.8
+    /// actually produces, so that a change in their format fails here.
+    #[test]
+    fn locates_serde_yaml_errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Form {
+            name: String,
+            items: Vec<Item>,
+            #[serde(flatten)]
+            layout: Layout,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Item {
+            size: f32,
+            kind: Kind,
+        }
+
+        #[derive(Debug, Deserialize)]
+        enum Kind {
+            Round,
+            Square,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Layout {
+            spacing: f32,
+        }
+
+        let error = |yaml: &str| {
+            let error = serde_yaml::from_str::<Form>(yaml).unwrap_err();
+            let location = SourceFile::new(yaml).locate_yaml_error(&error);
+            (error.to_string(), location.map(|l| (l.line, l.column)))
+        };
+
+        assert_eq!(
+            error("name: a\nspacing: 1\nitems:\n  - size: 1\n    kind: Round\n  - size: big\n    kind: Round\n"),
+            (
+                "items[1].size: invalid type: string \"big\", expected f32 at line 6 column 11".to_owned(),
+                Some((6, 11))
+            )
+        );
+        assert_eq!(
+            error("name: a\nspacing: 1\nitems:\n  - size: 1\n    kind: Oval\n"),
+            (
+                "items[0].kind: unknown variant `Oval`, expected `Round` or `Square` at line 5 column 11".to_owned(),
+                Some((5, 11))
+            )
+        );
+        assert_eq!(
+            error("name: a\nspacing: 1\nitems:\n  - kind: Round\n"),
+            (
+                "items[0]: missing field `size` at line 4 column 9".to_owned(),
+                Some((4, 5))
+            )
+        );
+        // Flattened fields lose their path and location.
+        assert_eq!(
+            error("name: a\nspacing: wide\nitems: []\n"),
+            (
+                "invalid type: string \"wide\", expected f32 at line 1 column 5".to_owned(),
+                Some((2, 10))
+            )
+        );
+        assert_eq!(
+            error("items: []\nspacing: 1\n"),
+            (
+                "missing field `name` at line 1 column 6".to_owned(),
+                Some((1, 6))
+            )
+        );
+    }
+
+    #[test]
+    fn yaml_error_messages() {
+        assert_eq!(
+            strip_yaml_location("missing field `child` at line 4 column 13"),
+            "missing field `child`"
+        );
+        assert_eq!(
+            split_path("child.Column.children[1]: invalid type: string \"a\", expected f32"),
+            (
+                vec![
+                    "child".to_owned(),
+                    "Column".to_owned(),
+                    "children".to_owned(),
+                    "1".to_owned()
+                ],
+                "invalid type: string \"a\", expected f32"
+            )
+        );
+        assert_eq!(
+            split_path("invalid type: unit value").0,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            quoted_name("unknown variant `Colum`, expected `Column`"),
+            Some("Colum")
+        );
+    }
+}
@@ -1,7 +1,11 @@
+use std::fmt;
+
 use serde::Deserialize;
 
 pub use self::widgets::Widget;
 
+use crate::source::{self, Location, Snippet, SourceFile};
+
 pub mod widgets;
 
 #[derive(Debug, Deserialize)]
@@ -32,24 +36,112 @@ impl Spec {
     }
 
     pub fn deserialize_from_str(s: &str) -> Result<Self, SpecError> {
-        let spec: Spec = serde_yaml::from_str(s)?;
-        spec.validate()?;
+        Self::deserialize(s, None)
+    }
+
+    /// Like `deserialize_from_str`, but errors name
+    /// the file the spec was read from.
+    pub fn deserialize_from_file(file_name: &str, s: &str) -> Result<Self, SpecError> {
+        Self::deserialize(s, Some(file_name))
+    }
+
+    /// The source file is only indexed if there is an error to locate.
+    fn deserialize(s: &str, file_name: Option<&str>) -> Result<Self, SpecError> {
+        let source = || match file_name {
+            Some(file_name) => SourceFile::with_name(file_name, s),
+            None => SourceFile::new(s),
+        };
+        let spec: Spec = serde_yaml::from_str(s).map_err(|error| {
+            let source = source();
+            let location = source.locate_yaml_error(&error);
+            let snippet = location.map(|location| source.snippet(location));
+            SpecError::Yaml { error, snippet }
+        })?;
+        spec.validate().map_err(|error| {
+            let source = source();
+            let location = error.locate(&source);
+            let snippet = location.map(|location| source.snippet(location));
+            SpecError::Validation { error, snippet }
+        })?;
         Ok(spec)
     }
 }
 
+/// An error loading a spec, along with the
+/// line of the spec it refers to, if known.
 #[derive(Debug, thiserror::Error)]
 pub enum SpecError {
-    #[error(transparent)]
-    Yaml(#[from] serde_yaml::Error),
-    #[error(transparent)]
-    Validation(#[from] ValidationError),
+    #[error("{}", format_error(.error, .snippet))]
+    Yaml {
+        error: serde_yaml::Error,
+        snippet: Option<Snippet>,
+    },
+    #[error("{}", format_error(.error, .snippet))]
+    Validation {
+        error: ValidationError,
+        snippet: Option<Snippet>,
+    },
+}
+
+impl SpecError {
+    /// The line of the spec the error refers to, if known.
+    pub fn snippet(&self) -> Option<&Snippet> {
+        match self {
+            SpecError::Yaml { snippet, .. } | SpecError::Validation { snippet, .. } => {
+                snippet.as_ref()
+            }
+        }
+    }
+}
+
+impl From<serde_yaml::Error> for SpecError {
+    fn from(error: serde_yaml::Error) -> Self {
+        SpecError::Yaml {
+            error,
+            snippet: None,
+        }
+    }
+}
+
+impl From<ValidationError> for SpecError {
+    fn from(error: ValidationError) -> Self {
+        SpecError::Validation {
+            error,
+            snippet: None,
+        }
+    }
+}
+
+fn format_error(error: &dyn fmt::Display, snippet: &Option<Snippet>) -> String {
+    match snippet {
+        // The snippet shows the location instead.
+        Some(snippet) => format!(
+            "{}\n{}",
+            source::strip_yaml_location(&error.to_string()),
+            snippet
+        ),
+        None => error.to_string(),
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ValidationError {
     #[error("invalid widget ID '{0}'. Widget IDs must be valid Rust identifiers.")]
     InvalidWidgetId(String),
+    #[error("invalid regex '{0}': {1}")]
+    InvalidRegex(String, #[source] regex::Error),
+}
+
+impl ValidationError {
+    /// Gets the location of the offending value in the spec.
+    pub fn locate(&self, source: &SourceFile) -> Option<Location> {
+        match self {
+            ValidationError::InvalidWidgetId(id) => source
+                .locate_value("id", id)
+                .or_else(|| source.locate_value("name", id)),
+            ValidationError::InvalidRegex(pattern, _) => source.locate_value("Regex", pattern),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -65,6 +157,89 @@ mod tests {
         s.validate().unwrap();
     }
 
+    #[test]
+    fn nested_widgets_are_validated() {
+        let spec: Spec = serde_yaml::from_str(
+            r#"
+name: Form
+child:
+  Column:
+    children:
+      - Tooltip:
+          child:
+            Divider:
+              id: divider
+          tooltip:
+            Divider:
+              id: not-an-ident
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            spec.validate(),
+            Err(ValidationError::InvalidWidgetId(id)) if id == "not-an-ident"
+        ));
+    }
+
+    #[test]
+    fn invalid_filter_regex() {
+        let err = Spec::deserialize_from_str(
+            r#"
+name: Form
+child:
+  Column:
+    children:
+      - TextInput:
+          filter:
+            Regex: "[a-z"
+"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            SpecError::Validation {
+                error: ValidationError::InvalidRegex(..),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn error_snippets() {
+        let err = Spec::deserialize_from_file(
+            "form.yml",
+            "name: Form\nchild:\n  Column:\n    children:\n      - Divider:\n          id: 1button\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid widget ID '1button'. Widget IDs must be valid Rust identifiers.
+ --> form.yml:6:15
+  |
+6 |           id: 1button
+  |               ^"
+        );
+
+        // serde_yaml reports the end of the flattened `Column` instead.
+        let err = Spec::deserialize_from_file(
+            "form.yml",
+            "name: Form\nchild:\n  Column:\n    spacing: wide\n    children: []\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.snippet().unwrap().location,
+            Location {
+                line: 4,
+                column: 14
+            }
+        );
+
+        let err = Spec::deserialize_from_str("name: Form\nchild:\n  Colum: {}\n").unwrap_err();
+        let snippet = err.snippet().unwrap();
+        assert_eq!(snippet.file, None);
+        assert_eq!(snippet.location, Location { line: 3, column: 3 });
+    }
+
     #[test]
     fn invalid_idents() {
         validate_ident("1foo").unwrap_err();
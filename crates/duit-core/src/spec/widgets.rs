@@ -4,7 +4,7 @@ use std::slice;
 
 use serde::Deserialize;
 
-use crate::{Align, Axis};
+use crate::{Align, Axis, Insets};
 
 use super::{validate_ident, ValidationError};
 
@@ -14,6 +14,8 @@ pub struct BaseSpec {
     pub flex: Option<f32>,
     #[serde(default)]
     pub classes: Vec<String>,
+    /// Style properties that take precedence over every rule.
+    pub style: Option<serde_yaml::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,6 +24,7 @@ pub enum Widget {
     Row(RowSpec),
     Text(TextSpec),
     TextInput(TextInputSpec),
+    TextArea(TextAreaSpec),
     Button(ButtonSpec),
     Image(ImageSpec),
     Container(ContainerSpec),
@@ -37,12 +40,42 @@ pub enum Widget {
 }
 
 impl Widget {
+    /// Validates this widget and all of its descendants.
     pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_own_spec()?;
+
+        for child in self.children() {
+            child.validate()?;
+        }
+
+        // Special case for Tooltip, because its children
+        // cannot be represented as a slice
+        if let Widget::Tooltip(t) = self {
+            for child in [&t.child, &t.tooltip].iter().copied().flatten() {
+                child.validate()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates this widget's spec, excluding its children.
+    fn validate_own_spec(&self) -> Result<(), ValidationError> {
         if let Some(base) = self.base_spec() {
             if let Some(id) = &base.id {
                 validate_ident(id)?;
             }
         }
+
+        if let Widget::TextInput(TextInputSpec {
+            filter: Some(TextInputFilter::Regex(pattern)),
+            ..
+        }) = self
+        {
+            regex::Regex::new(pattern)
+                .map_err(|error| ValidationError::InvalidRegex(pattern.clone(), error))?;
+        }
+
         Ok(())
     }
 
@@ -62,6 +95,7 @@ impl Widget {
                 TextSpec::Complex { base, .. } => Some(base),
             },
             Widget::TextInput(s) => Some(&s.base),
+            Widget::TextArea(s) => Some(&s.base),
             Widget::Button(s) => Some(&s.base),
             Widget::Image(s) => Some(&s.base),
             Widget::Container(s) => Some(&s.base),
@@ -109,6 +143,7 @@ impl Widget {
             Widget::Row(_) => "Flex",
             Widget::Text(_) => "Text",
             Widget::TextInput(_) => "TextInput",
+            Widget::TextArea(_) => "TextArea",
             Widget::Button(_) => "Button",
             Widget::Image(_) => "Image",
             Widget::Container(_) => "Container",
@@ -151,6 +186,9 @@ pub struct FlexSpec {
     pub spacing: f32,
 }
 
+/// Text is shown as written, unless `markup: true` is set, in which
+/// case `[label](action)` makes `label` an interactive span with
+/// the action id `action`, and a backslash escapes the next character.
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum TextSpec {
@@ -160,21 +198,85 @@ pub enum TextSpec {
         base: BaseSpec,
         text: Option<String>,
         #[serde(default)]
-        align_h: Align,
-        #[serde(default)]
-        align_v: Align,
+        markup: bool,
+        /// Overrides the alignment set by the style.
+        align_h: Option<Align>,
+        align_v: Option<Align>,
+        /// Override the truncation settings of the style.
+        max_lines: Option<u32>,
+        overflow: Option<TextOverflow>,
+        wrap: Option<bool>,
     },
 }
 
+/// What happens to text that does not fit in a `Text` widget.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum TextOverflow {
+    /// Lines past `max_lines` are hidden. Without `wrap`,
+    /// this is the same as `Clip`.
+    #[default]
+    Wrap,
+    /// Text past the widget's width or `max_lines` is cut off.
+    Clip,
+    /// Like `Clip`, but the text is shortened to end with "…".
+    Ellipsis,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TextInputSpec {
     #[serde(flatten)]
     pub base: BaseSpec,
     pub placeholder: Option<String>,
     pub width: Option<f32>,
+    /// Maximum number of characters.
     pub max_len: Option<usize>,
     #[serde(default)]
     pub is_password: bool,
+    pub filter: Option<TextInputFilter>,
+    /// Inclusive lower bound for the `Integer` and `Float` filters.
+    pub min: Option<f64>,
+    /// Inclusive upper bound for the `Integer` and `Float` filters.
+    pub max: Option<f64>,
+    /// Maximum number of undo steps to remember.
+    pub history_depth: Option<usize>,
+}
+
+/// Restricts what can be entered into a `TextInput`.
+#[derive(Clone, Debug, Deserialize)]
+pub enum TextInputFilter {
+    /// Only whole numbers can be typed.
+    Integer,
+    /// Only decimal numbers can be typed.
+    Float,
+    /// Only letters and digits can be typed.
+    Alphanumeric,
+    /// The text is invalid unless the regular expression matches all of it.
+    Regex(String),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TextAreaSpec {
+    #[serde(flatten)]
+    pub base: BaseSpec,
+    pub placeholder: Option<String>,
+    pub width: Option<f32>,
+    /// Height of the visible area. Longer text scrolls.
+    ///
+    /// Defaults to the height available from the parent.
+    pub height: Option<f32>,
+    /// Maximum number of characters.
+    pub max_len: Option<usize>,
+    /// Maximum number of lines, counting only explicit line breaks.
+    pub max_lines: Option<usize>,
+    /// Whether to wrap lines that are wider than the text area.
+    #[serde(default = "default_wrap")]
+    pub wrap: bool,
+    /// Maximum number of undo steps to remember.
+    pub history_depth: Option<usize>,
+}
+
+fn default_wrap() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize)]
@@ -199,8 +301,8 @@ pub struct ImageSpec {
 pub enum ContainerMode {
     Shrink,
     FillParent,
-    Pad(f32),
-    FillParentAndPad(f32),
+    Pad(Insets),
+    FillParentAndPad(Insets),
 }
 
 #[derive(Debug, Deserialize)]
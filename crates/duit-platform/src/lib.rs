@@ -1,21 +1,35 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use duit::Ui;
+use duit::{NativeWindowId, NativeWindowRequest, Ui};
 use dume::Canvas;
 use glam::Vec2;
 use wgpu::*;
 use winit::{
-    dpi::PhysicalSize,
+    dpi::{LogicalPosition, LogicalSize, PhysicalSize},
     event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::Window,
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    window::{Window, WindowBuilder},
 };
 
+/// Runs the UI until the main window is closed.
+///
+/// `window` becomes the `Ui`'s main native window. Further native
+/// windows are opened and closed as requested through
+/// [`Ui::open_native_window`] and [`Ui::close_native_window`].
+///
+/// `init_context` runs once, before any window is drawn, on the
+/// `dume::Context` shared by the canvases of every window. Fonts
+/// and textures added there are available in all windows.
+///
+/// Input methods are only partly supported: winit 0.26 does not report
+/// preedit text, so text being composed is never shown and no
+/// `Event::CompositionUpdate` is delivered. Committed text arrives as
+/// characters, and the candidate window is placed below the caret.
 pub fn run(
     event_loop: EventLoop<()>,
     window: Window,
     mut ui: Ui,
-    init_canvas: impl FnOnce(&mut Canvas),
+    init_context: impl FnOnce(&dume::Context),
     // mut handle_msg: impl FnMut(Message) + 'static,
     mut update: impl FnMut(&mut Ui) + 'static,
 ) {
@@ -43,84 +57,195 @@ pub fn run(
     let device = Arc::new(device);
     let queue = Arc::new(queue);
 
-    let mut swap_chain_desc = SurfaceConfiguration {
-        usage: TextureUsages::RENDER_ATTACHMENT,
-        format: dume::TARGET_FORMAT,
-        width: window.inner_size().width,
-        height: window.inner_size().height,
-        present_mode: PresentMode::Fifo,
-    };
-    surface.configure(&device, &swap_chain_desc);
-
-    let mut sample_texture = create_sample_texture(window.inner_size(), &*device);
-
     let context = dume::Context::builder(Arc::clone(&device), Arc::clone(&queue)).build();
+    init_context(&context);
+
+    let main_id = ui.main_native_window();
+    let main_window = NativeWindowState::new(main_id, window, surface, &device, &context);
+    main_window.update_ui_size(&mut ui);
 
-    let mut canvas = context.create_canvas(
-        Vec2::new(
-            window.inner_size().to_logical(window.scale_factor()).width,
-            window.inner_size().to_logical(window.scale_factor()).height,
-        ),
-        window.scale_factor() as f32,
-    );
-    init_canvas(&mut canvas);
+    let mut windows = HashMap::new();
+    windows.insert(main_window.window.id(), main_window);
 
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, target, control_flow| {
         *control_flow = ControlFlow::Wait;
 
-        let window_logical_size = Vec2::new(
-            window.inner_size().to_logical(window.scale_factor()).width,
-            window.inner_size().to_logical(window.scale_factor()).height,
-        );
         match event {
-            Event::RedrawRequested(_) => {
-                update(&mut ui);
+            Event::RedrawRequested(window_id) => {
+                let state = match windows.get_mut(&window_id) {
+                    Some(state) => state,
+                    None => return,
+                };
 
-                ui.render(&mut canvas, window_logical_size);
-                let frame = surface
+                ui.render_native_window(state.id, &mut state.canvas);
+                if let Some(caret) = ui.native_window(state.id).and_then(|w| w.ime_caret()) {
+                    // Place the candidate window just below the caret.
+                    state.window.set_ime_position(LogicalPosition::new(
+                        caret.pos.x,
+                        caret.pos.y + caret.size.y,
+                    ));
+                }
+                let frame = state
+                    .surface
                     .get_current_texture()
                     .expect("failed to get next frame");
 
-                canvas.render(
+                state.canvas.render(
                     &frame.texture.create_view(&Default::default()),
-                    &sample_texture.create_view(&Default::default()),
+                    &state.sample_texture.create_view(&Default::default()),
                 );
 
                 frame.present();
             }
-            Event::MainEventsCleared => window.request_redraw(),
-            Event::WindowEvent {
-                event: WindowEvent::Resized(new_size),
-                ..
-            } => {
-                swap_chain_desc.width = new_size.width;
-                swap_chain_desc.height = new_size.height;
-                surface.configure(&device, &swap_chain_desc);
-                sample_texture = create_sample_texture(new_size, &*device);
-                canvas.resize(
-                    Vec2::new(
-                        new_size.to_logical(window.scale_factor()).width,
-                        new_size.to_logical(window.scale_factor()).height,
-                    ),
-                    window.scale_factor() as f32,
-                );
-            }
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => *control_flow = ControlFlow::Exit,
-            Event::WindowEvent { event, .. } => {
-                if let Some(event) = ui.convert_event(&event, window.scale_factor()) {
-                    ui.handle_window_event(&mut canvas, &event, window_logical_size);
+            Event::MainEventsCleared => {
+                update(&mut ui);
+
+                for request in ui.take_native_window_requests() {
+                    match request {
+                        NativeWindowRequest::Open(id, options) => {
+                            let state = NativeWindowState::open(
+                                id, &options, target, &instance, &device, &context,
+                            );
+                            state.update_ui_size(&mut ui);
+                            windows.insert(state.window.id(), state);
+                        }
+                        NativeWindowRequest::Close(id) => {
+                            if id == main_id {
+                                *control_flow = ControlFlow::Exit;
+                            }
+                            windows.retain(|_, state| state.id != id);
+                        }
+                    }
+                }
+
+                for state in windows.values() {
+                    state.window.request_redraw();
                 }
+            }
+            Event::WindowEvent { window_id, event } => {
+                let state = match windows.get_mut(&window_id) {
+                    Some(state) => state,
+                    None => return,
+                };
 
-                // ui.handle_messages(|m: &Message| handle_msg(m));
+                match event {
+                    WindowEvent::Resized(new_size) => {
+                        state.resize(new_size, &device);
+                        state.update_ui_size(&mut ui);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        state.resize(*new_inner_size, &device);
+                        state.update_ui_size(&mut ui);
+                    }
+                    WindowEvent::CloseRequested => {
+                        if state.id == main_id {
+                            *control_flow = ControlFlow::Exit;
+                        } else {
+                            let id = state.id;
+                            windows.remove(&window_id);
+                            ui.close_native_window(id);
+                        }
+                    }
+                    event => {
+                        if let Some(event) = ui.convert_native_window_event(state.id, &event) {
+                            ui.handle_native_window_event(state.id, &mut state.canvas, &event);
+                        }
+
+                        // ui.handle_messages(|m: &Message| handle_msg(m));
+                    }
+                }
             }
             _ => (),
         }
     });
 }
 
+/// An operating system window, along with its surface and canvas.
+struct NativeWindowState {
+    id: NativeWindowId,
+    window: Window,
+    surface: Surface,
+    swap_chain_desc: SurfaceConfiguration,
+    sample_texture: Texture,
+    canvas: Canvas,
+}
+
+impl NativeWindowState {
+    fn new(
+        id: NativeWindowId,
+        window: Window,
+        surface: Surface,
+        device: &Device,
+        context: &dume::Context,
+    ) -> Self {
+        let swap_chain_desc = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: dume::TARGET_FORMAT,
+            width: window.inner_size().width,
+            height: window.inner_size().height,
+            present_mode: PresentMode::Fifo,
+        };
+        surface.configure(device, &swap_chain_desc);
+
+        let sample_texture = create_sample_texture(window.inner_size(), device);
+
+        let canvas = context.create_canvas(
+            logical_size(&window, window.inner_size()),
+            window.scale_factor() as f32,
+        );
+
+        Self {
+            id,
+            window,
+            surface,
+            swap_chain_desc,
+            sample_texture,
+            canvas,
+        }
+    }
+
+    fn open(
+        id: NativeWindowId,
+        options: &duit::NativeWindowOptions,
+        target: &EventLoopWindowTarget<()>,
+        instance: &Instance,
+        device: &Device,
+        context: &dume::Context,
+    ) -> Self {
+        let window = WindowBuilder::new()
+            .with_title(&options.title)
+            .with_inner_size(LogicalSize::new(options.size.x, options.size.y))
+            .build(target)
+            .expect("failed to create window");
+        let surface = unsafe { instance.create_surface(&window) };
+        Self::new(id, window, surface, device, context)
+    }
+
+    fn resize(&mut self, new_size: PhysicalSize<u32>, device: &Device) {
+        self.swap_chain_desc.width = new_size.width;
+        self.swap_chain_desc.height = new_size.height;
+        self.surface.configure(device, &self.swap_chain_desc);
+        self.sample_texture = create_sample_texture(new_size, device);
+        self.canvas.resize(
+            logical_size(&self.window, new_size),
+            self.window.scale_factor() as f32,
+        );
+    }
+
+    fn update_ui_size(&self, ui: &mut Ui) {
+        ui.set_native_window_size(
+            self.id,
+            logical_size(&self.window, self.window.inner_size()),
+            self.window.scale_factor(),
+        );
+    }
+}
+
+fn logical_size(window: &Window, size: PhysicalSize<u32>) -> Vec2 {
+    let size = size.to_logical(window.scale_factor());
+    Vec2::new(size.width, size.height)
+}
+
 fn create_sample_texture(window_size: PhysicalSize<u32>, device: &Device) -> Texture {
     device.create_texture(&TextureDescriptor {
         label: None,
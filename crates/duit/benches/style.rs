@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use duit::StyleEngine;
+use serde_yaml::Value;
+
+/// Builds a large sheet in which most styles apply to
+/// classes other than the ones being looked up.
+fn large_sheet(num_classes: usize) -> String {
+    let mut sheet = String::from("styles:\n");
+    for i in 0..num_classes {
+        sheet.push_str(&format!(
+            "  class{i}:\n    color: rgb({c}, 0, 0)\n  class{i} & hovered:\n    color: rgb(0, {c}, 0)\n",
+            i = i,
+            c = i % 256
+        ));
+    }
+    sheet
+}
+
+fn engine(num_classes: usize) -> StyleEngine {
+    let mut engine = StyleEngine::default();
+    engine
+        .append_sheet(include_bytes!("../../../themes/default.yml"))
+        .unwrap();
+    engine
+        .append_sheet(large_sheet(num_classes).as_bytes())
+        .unwrap();
+    engine
+}
+
+fn style_lookup(c: &mut Criterion) {
+    let classes = vec!["button".to_owned(), "hovered".to_owned()];
+
+    let mut group = c.benchmark_group("style_lookup");
+    for num_classes in [10, 100, 1000] {
+        let mut engine = engine(num_classes);
+
+        // The lookup as it was before indexing: a linear scan,
+        // merging matches in source order.
+        group.bench_with_input(
+            BenchmarkId::new("baseline", num_classes),
+            &classes,
+            |b, classes| b.iter(|| engine.create_style_baseline::<Value>(&[], black_box(classes))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("indexed", num_classes),
+            &classes,
+            |b, classes| b.iter(|| engine.create_style::<Value>(&[], black_box(classes))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("cached", num_classes),
+            &classes,
+            |b, classes| b.iter(|| engine.get_style::<Value>(&[], black_box(classes))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, style_lookup);
+criterion_main!(benches);
@@ -31,11 +31,13 @@ fn main() {
     let mut ui = Ui::new();
 
     ui.add_spec(
-        Spec::deserialize_from_str(
+        Spec::deserialize_from_file(
+            "crates/duit/examples/simple/root.yml",
             &fs::read_to_string("crates/duit/examples/simple/root.yml").unwrap(),
         )
-        .unwrap(),
-    );
+        .unwrap_or_else(|error| panic!("{}", error)),
+    )
+    .unwrap_or_else(|error| panic!("{}", error));
 
     let (instance_handle, root) = ui.create_spec_instance::<Simple>();
 
@@ -78,17 +80,16 @@ fn main() {
         event_loop,
         window,
         ui,
-        |cv| {
-            let mut texture_set = cv.context().create_texture_set_builder();
+        |context| {
+            let mut texture_set = context.create_texture_set_builder();
             texture_set
                 .add_texture(
                     include_bytes!("../../../../assets/ozymandias.jpeg"),
                     "ozymandias",
                 )
                 .unwrap();
-            cv.context()
-                .add_texture_set(texture_set.build(1024, 4096).unwrap());
-            cv.context()
+            context.add_texture_set(texture_set.build(1024, 4096).unwrap());
+            context
                 .add_font(
                     include_bytes!("../../../../assets/CormorantGaramond-Regular.ttf").to_vec(),
                 )
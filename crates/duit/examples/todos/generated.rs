@@ -0,0 +1,72 @@
+use duit::widgets::*;
+use duit::*;
+pub struct Todos {
+    pub todo_input: WidgetHandle<TextInput>,
+    pub todo_input_submit: WidgetHandle<Button>,
+    pub todos_list: WidgetHandle<Flex>,
+}
+impl ::duit::InstanceHandle for Todos {
+    fn name() -> &'static str {
+        "Todos"
+    }
+    fn init(widget_handles: Vec<(String, WidgetPodHandle)>) -> Self {
+        let mut todo_input = None;
+        let mut todo_input_submit = None;
+        let mut todos_list = None;
+        for (name, widget) in widget_handles {
+            match name.as_str() {
+                "todo_input" => todo_input = Some(widget),
+                "todo_input_submit" => todo_input_submit = Some(widget),
+                "todos_list" => todos_list = Some(widget),
+                _ => {}
+            }
+        }
+        Self {
+            todo_input: WidgetHandle::new(todo_input.unwrap_or_else(|| {
+                panic!(
+                    "missing widget with ID '{}' (generated code not up to date)",
+                    "todo_input"
+                )
+            })),
+            todo_input_submit: WidgetHandle::new(todo_input_submit.unwrap_or_else(|| {
+                panic!(
+                    "missing widget with ID '{}' (generated code not up to date)",
+                    "todo_input_submit"
+                )
+            })),
+            todos_list: WidgetHandle::new(todos_list.unwrap_or_else(|| {
+                panic!(
+                    "missing widget with ID '{}' (generated code not up to date)",
+                    "todos_list"
+                )
+            })),
+        }
+    }
+}
+use duit::widgets::*;
+use duit::*;
+pub struct Todo {
+    pub todo_text: WidgetHandle<Text>,
+}
+impl ::duit::InstanceHandle for Todo {
+    fn name() -> &'static str {
+        "Todo"
+    }
+    fn init(widget_handles: Vec<(String, WidgetPodHandle)>) -> Self {
+        let mut todo_text = None;
+        for (name, widget) in widget_handles {
+            match name.as_str() {
+                "todo_text" => todo_text = Some(widget),
+                _ => {}
+            }
+        }
+        Self {
+            todo_text: WidgetHandle::new(todo_text.unwrap_or_else(|| {
+                panic!(
+                    "missing widget with ID '{}' (generated code not up to date)",
+                    "todo_text"
+                )
+            })),
+        }
+    }
+}
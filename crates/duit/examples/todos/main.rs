@@ -0,0 +1,89 @@
+use std::fs;
+
+use duit::{Ui, WindowPositioner};
+use duit_core::spec::Spec;
+use dume::Rect;
+use glam::Vec2;
+use winit::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder};
+
+use crate::generated::{Todo, Todos};
+
+mod generated;
+
+struct Positioner;
+impl WindowPositioner for Positioner {
+    fn compute_position(&self, available_space: Vec2) -> Rect {
+        Rect {
+            size: available_space,
+            pos: Vec2::ZERO,
+        }
+    }
+}
+
+struct CreateTodo(String);
+
+struct CreateButtonClicked;
+
+fn main() {
+    let mut ui = Ui::new();
+
+    for spec in ["root.yml", "todo.yml"] {
+        let path = format!("crates/duit/examples/todos/widgets/{}", spec);
+        ui.add_spec(
+            Spec::deserialize_from_file(&path, &fs::read_to_string(&path).unwrap())
+                .unwrap_or_else(|error| panic!("{}", error)),
+        )
+        .unwrap_or_else(|error| panic!("{}", error));
+    }
+
+    let (instance_handle, root) = ui.create_spec_instance::<Todos>();
+
+    instance_handle
+        .todo_input
+        .get_mut()
+        .on_submit(|text| CreateTodo(text.to_owned()));
+    instance_handle
+        .todo_input_submit
+        .get_mut()
+        .on_click(|| CreateButtonClicked);
+
+    ui.create_window(root, Positioner, 1);
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Duit Todos Example")
+        .with_inner_size(LogicalSize::new(1920, 1080))
+        .build(&event_loop)
+        .unwrap();
+
+    duit_platform::run(
+        event_loop,
+        window,
+        ui,
+        |context| {
+            context
+                .add_font(
+                    include_bytes!("../../../../assets/CormorantGaramond-Regular.ttf").to_vec(),
+                )
+                .unwrap();
+        },
+        move |ui| {
+            let mut new_todos = Vec::new();
+            ui.handle_messages(|CreateTodo(text): &CreateTodo| new_todos.push(text.clone()));
+            while ui.pop_message::<CreateButtonClicked>().is_some() {
+                new_todos.push(instance_handle.todo_input.get().current_input().to_owned());
+            }
+
+            for text in new_todos {
+                if text.trim().is_empty() {
+                    continue;
+                }
+
+                let (todo, todo_root) = ui.create_spec_instance::<Todo>();
+                todo.todo_text.get_mut().set_text(dume::text!("{}", text));
+                instance_handle.todos_list.get_mut().add_child(todo_root);
+                instance_handle.todo_input.get_mut().clear();
+            }
+        },
+    );
+}
@@ -0,0 +1,46 @@
+use dume::{Canvas, Rect};
+use glam::Vec2;
+
+use crate::{Color, Insets};
+
+/// Strokes the border of a box, centered on its edges.
+///
+/// Borders of uniform width follow `border_radius`. Otherwise,
+/// each side is drawn separately, with square corners.
+pub(crate) fn stroke_border(
+    canvas: &mut Canvas,
+    bounds: Rect,
+    border_radius: f32,
+    width: Insets,
+    color: Color,
+) {
+    if width.is_uniform() {
+        canvas
+            .begin_path()
+            .rounded_rect(bounds.pos, bounds.size, border_radius)
+            .solid_color(color)
+            .stroke_width(width.top)
+            .stroke();
+        return;
+    }
+
+    let Rect { pos, size } = bounds;
+    let start = pos - Vec2::new(width.left, width.top) / 2.;
+    let end = pos + size - Vec2::new(width.right, width.bottom) / 2.;
+    let outer = end - start + Vec2::new(width.right, width.bottom);
+    let sides = [
+        (start, Vec2::new(outer.x, width.top)),
+        (Vec2::new(start.x, end.y), Vec2::new(outer.x, width.bottom)),
+        (start, Vec2::new(width.left, outer.y)),
+        (Vec2::new(end.x, start.y), Vec2::new(width.right, outer.y)),
+    ];
+    for (pos, size) in sides {
+        if size.x > 0. && size.y > 0. {
+            canvas
+                .begin_path()
+                .rect(pos, size)
+                .solid_color(color)
+                .fill();
+        }
+    }
+}
@@ -1,16 +1,32 @@
 use glam::{const_vec4, vec4, Vec4};
-use palette::Srgba;
-use serde::Deserialize;
+use palette::{Hsla, Hsva, IntoColor, LinSrgba, Srgba};
+use serde::{Deserialize, Serialize};
 
-use std::{hash::Hash, str::FromStr};
+use std::{fmt, hash::Hash, str::FromStr};
+
+use self::named::named_color;
+
+mod named;
 
 /// An sRGB color with alpha.
+///
+/// Parsed from, and serialized to, strings in one of these forms:
+///
+/// * `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, in hex;
+/// * `rgb(r, g, b)` or `rgba(r, g, b, a)`, with components from 0 to 255;
+/// * `hsl(h, s%, l%)` or `hsla(h, s%, l%, a)`, with the hue in degrees
+///   and alpha from 0 to 1;
+/// * a CSS color name, like `orange`, or `transparent`.
+///
+/// In YAML, hex colors must be quoted, as in `'#fc4c02'`;
+/// otherwise they are read as comments.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color(Vec4);
 
 impl Color {
     pub const WHITE: Color = Color(const_vec4!([1.0; 4]));
     pub const BLACK: Color = Color(const_vec4!([0.0, 0.0, 0.0, 1.0]));
+    pub const TRANSPARENT: Color = Color(const_vec4!([0.0; 4]));
 
     /// Creates a color from sRGB bytes.
     pub fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
@@ -27,6 +43,12 @@ impl Color {
         Self(vec4(r, g, b, a))
     }
 
+    /// Creates a color from a hue in degrees, and
+    /// saturation, lightness and alpha from 0 to 1.
+    pub fn hsla(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        Hsla::new(hue, saturation, lightness, alpha).into()
+    }
+
     pub fn red(self) -> f32 {
         self.0.x
     }
@@ -62,6 +84,43 @@ impl Color {
     pub fn to_rgba8(self) -> [u8; 4] {
         [self.red8(), self.green8(), self.blue8(), self.alpha8()]
     }
+
+    /// Returns this color with its alpha replaced.
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        Self::rgba(self.red(), self.green(), self.blue(), alpha)
+    }
+
+    /// Increases the HSL lightness by `amount`, clamping it to `[0, 1]`.
+    pub fn lighten(self, amount: f32) -> Self {
+        let mut hsl = Hsla::from(self);
+        hsl.lightness = (hsl.lightness + amount).clamp(0.0, 1.0);
+        hsl.into()
+    }
+
+    /// Decreases the HSL lightness by `amount`, clamping it to `[0, 1]`.
+    pub fn darken(self, amount: f32) -> Self {
+        self.lighten(-amount)
+    }
+
+    /// Interpolates the sRGB components, including alpha.
+    /// `t = 0` gives `self` and `t = 1` gives `other`.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        Self(self.0.lerp(other.0, t))
+    }
+
+    /// Like `lerp`, but blends in linear light, the way light
+    /// from the two colors would mix. Midpoints come out brighter.
+    pub fn mix(self, other: Color, t: f32) -> Self {
+        let a: LinSrgba = Srgba::from(self).into_linear();
+        let b: LinSrgba = Srgba::from(other).into_linear();
+        let mixed = LinSrgba::new(
+            a.red + (b.red - a.red) * t,
+            a.green + (b.green - a.green) * t,
+            a.blue + (b.blue - a.blue) * t,
+            a.alpha + (b.alpha - a.alpha) * t,
+        );
+        Srgba::from_linear(mixed).into()
+    }
 }
 
 impl Eq for Color {}
@@ -78,10 +137,14 @@ impl Hash for Color {
 pub enum ColorParseError {
     #[error("expected parenthesis after color type")]
     MissingParenthesis,
-    #[error("unknown color type - expected one of `rgb`, `rgba`")]
+    #[error("unknown color type - expected a hex color, one of `rgb`, `rgba`, `hsl`, `hsla`, or a color name")]
     UnknownType,
     #[error(transparent)]
     BadValue(std::num::ParseIntError),
+    #[error("invalid color component '{0}'")]
+    BadComponent(String),
+    #[error("expected 3, 4, 6 or 8 hex digits in '{0}'")]
+    BadHex(String),
     #[error("expected {expected} color components but found {actual}")]
     ComponentMismatch { expected: usize, actual: usize },
 }
@@ -90,11 +153,36 @@ impl FromStr for Color {
     type Err = ColorParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
         match *s.as_bytes() {
+            [b'#', ..] => parse_hex(&s[1..]),
             [b'r', b'g', b'b', b'a', ..] => parse_rgba(&s[4..]),
             [b'r', b'g', b'b', ..] => parse_rgb(&s[3..]),
-            _ => Err(ColorParseError::UnknownType),
+            [b'h', b's', b'l', b'a', ..] => parse_hsla(&s[4..]),
+            [b'h', b's', b'l', ..] => parse_hsl(&s[3..]),
+            _ if s.eq_ignore_ascii_case("transparent") => Ok(Color::TRANSPARENT),
+            _ => match named_color(s) {
+                Some([r, g, b]) => Ok(Color::rgba8(r, g, b, u8::MAX)),
+                None => Err(ColorParseError::UnknownType),
+            },
+        }
+    }
+}
+
+/// Formats the color in hex, leaving out the alpha if opaque.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{:02x}{:02x}{:02x}",
+            self.red8(),
+            self.green8(),
+            self.blue8()
+        )?;
+        if self.alpha8() != u8::MAX {
+            write!(f, "{:02x}", self.alpha8())?;
         }
+        Ok(())
     }
 }
 
@@ -104,6 +192,69 @@ impl From<Color> for Srgba<u8> {
     }
 }
 
+impl From<Color> for Srgba {
+    fn from(c: Color) -> Self {
+        Srgba::new(c.red(), c.green(), c.blue(), c.alpha())
+    }
+}
+
+impl From<Srgba> for Color {
+    fn from(c: Srgba) -> Self {
+        Color::rgba(c.red, c.green, c.blue, c.alpha)
+    }
+}
+
+impl From<Color> for Hsla {
+    fn from(c: Color) -> Self {
+        Srgba::from(c).into_color()
+    }
+}
+
+impl From<Hsla> for Color {
+    fn from(c: Hsla) -> Self {
+        let rgb: Srgba = c.into_color();
+        rgb.into()
+    }
+}
+
+impl From<Color> for Hsva {
+    fn from(c: Color) -> Self {
+        Srgba::from(c).into_color()
+    }
+}
+
+impl From<Hsva> for Color {
+    fn from(c: Hsva) -> Self {
+        let rgb: Srgba = c.into_color();
+        rgb.into()
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Color, ColorParseError> {
+    let bad_hex = || ColorParseError::BadHex(format!("#{}", s));
+    if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(bad_hex());
+    }
+    let digit = |i: usize| u8::from_str_radix(&s[i..i + 1], 16).unwrap();
+    let byte = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).unwrap();
+    match s.len() {
+        3 | 4 => {
+            let alpha = if s.len() == 4 { digit(3) * 17 } else { u8::MAX };
+            Ok(Color::rgba8(
+                digit(0) * 17,
+                digit(1) * 17,
+                digit(2) * 17,
+                alpha,
+            ))
+        }
+        6 | 8 => {
+            let alpha = if s.len() == 8 { byte(6) } else { u8::MAX };
+            Ok(Color::rgba8(byte(0), byte(2), byte(4), alpha))
+        }
+        _ => Err(bad_hex()),
+    }
+}
+
 fn parse_rgb(s: &str) -> Result<Color, ColorParseError> {
     let components = parse_components(parenthesized(s)?)?;
     if let [r, g, b] = *components.as_slice() {
@@ -120,6 +271,18 @@ fn parse_rgba(s: &str) -> Result<Color, ColorParseError> {
     let components = parse_components(parenthesized(s)?)?;
     if let [r, g, b, a] = *components.as_slice() {
         Ok(Color::rgba8(r, g, b, a))
+    } else {
+        Err(ColorParseError::ComponentMismatch {
+            expected: 4,
+            actual: components.len(),
+        })
+    }
+}
+
+fn parse_hsl(s: &str) -> Result<Color, ColorParseError> {
+    let components = parse_hsl_components(parenthesized(s)?)?;
+    if let [h, s, l] = *components.as_slice() {
+        Ok(Color::hsla(h, s / 100.0, l / 100.0, 1.0))
     } else {
         Err(ColorParseError::ComponentMismatch {
             expected: 3,
@@ -128,6 +291,18 @@ fn parse_rgba(s: &str) -> Result<Color, ColorParseError> {
     }
 }
 
+fn parse_hsla(s: &str) -> Result<Color, ColorParseError> {
+    let components = parse_hsl_components(parenthesized(s)?)?;
+    if let [h, s, l, a] = *components.as_slice() {
+        Ok(Color::hsla(h, s / 100.0, l / 100.0, a))
+    } else {
+        Err(ColorParseError::ComponentMismatch {
+            expected: 4,
+            actual: components.len(),
+        })
+    }
+}
+
 fn parenthesized(s: &str) -> Result<&str, ColorParseError> {
     let s = s.trim();
     match (s.chars().next(), s.chars().last()) {
@@ -146,6 +321,23 @@ fn parse_components(s: &str) -> Result<Vec<u8>, ColorParseError> {
     Ok(result)
 }
 
+/// Parses floats, ignoring `deg` and `%` units.
+fn parse_hsl_components(s: &str) -> Result<Vec<f32>, ColorParseError> {
+    let mut result = Vec::new();
+    for part in s.split(',') {
+        let part = part.trim();
+        let number = part
+            .strip_suffix("deg")
+            .or_else(|| part.strip_suffix('%'))
+            .unwrap_or(part)
+            .trim_end();
+        let component =
+            f32::from_str(number).map_err(|_| ColorParseError::BadComponent(part.to_owned()))?;
+        result.push(component);
+    }
+    Ok(result)
+}
+
 impl<'de> Deserialize<'de> for Color {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -156,6 +348,15 @@ impl<'de> Deserialize<'de> for Color {
     }
 }
 
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,5 +371,87 @@ mod tests {
     fn color_rgba_from_str() {
         let color = Color::from_str("rgba ( 10, 11, 12, 90 )").unwrap();
         assert_eq!(color, Color::rgba8(10, 11, 12, 90));
+        assert!(matches!(
+            Color::from_str("rgba(10, 11, 12)"),
+            Err(ColorParseError::ComponentMismatch {
+                expected: 4,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn color_hex_from_str() {
+        let parse = |s: &str| Color::from_str(s).unwrap().to_rgba8();
+        assert_eq!(parse("#fc4c02"), [252, 76, 2, 255]);
+        assert_eq!(parse("#FC4C0280"), [252, 76, 2, 128]);
+        assert_eq!(parse("#f80"), [255, 136, 0, 255]);
+        assert_eq!(parse("#f808"), [255, 136, 0, 136]);
+        assert!(matches!(
+            Color::from_str("#fc4c0"),
+            Err(ColorParseError::BadHex(_))
+        ));
+        assert!(matches!(
+            Color::from_str("#fc4g02"),
+            Err(ColorParseError::BadHex(_))
+        ));
+    }
+
+    #[test]
+    fn color_hsl_from_str() {
+        let parse = |s: &str| Color::from_str(s).unwrap().to_rgba8();
+        assert_eq!(parse("hsl(0, 100%, 50%)"), [255, 0, 0, 255]);
+        assert_eq!(parse("hsl(120deg, 100%, 25%)"), [0, 128, 0, 255]);
+        assert_eq!(parse("hsla(240, 100%, 50%, 0.5)"), [0, 0, 255, 128]);
+        assert!(matches!(
+            Color::from_str("hsl(0, 100%)"),
+            Err(ColorParseError::ComponentMismatch {
+                expected: 3,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn color_name_from_str() {
+        let parse = |s: &str| Color::from_str(s).unwrap().to_rgba8();
+        assert_eq!(parse("orange"), [255, 165, 0, 255]);
+        assert_eq!(parse("RebeccaPurple"), [102, 51, 153, 255]);
+        assert_eq!(parse("transparent"), [0, 0, 0, 0]);
+        assert!(matches!(
+            Color::from_str("blurple"),
+            Err(ColorParseError::UnknownType)
+        ));
+    }
+
+    #[test]
+    fn color_round_trips() {
+        for s in ["#fc4c02", "#fc4c0280", "#000000"].iter().copied() {
+            let color = Color::from_str(s).unwrap();
+            assert_eq!(color.to_string(), s);
+            let yaml = serde_yaml::to_string(&color).unwrap();
+            assert_eq!(serde_yaml::from_str::<Color>(&yaml).unwrap(), color);
+        }
+    }
+
+    #[test]
+    fn color_math() {
+        let gray = Color::rgba8(128, 128, 128, 255);
+        assert_eq!(gray.lighten(0.2).to_rgba8(), [179, 179, 179, 255]);
+        assert_eq!(gray.darken(0.2).to_rgba8(), [77, 77, 77, 255]);
+        assert_eq!(gray.darken(1.0), Color::BLACK);
+        assert_eq!(
+            Color::BLACK.lerp(Color::WHITE, 0.25).to_rgba8(),
+            [64, 64, 64, 255]
+        );
+        assert_eq!(
+            Color::BLACK.mix(Color::WHITE, 0.5).to_rgba8(),
+            [188, 188, 188, 255]
+        );
+        assert_eq!(Color::WHITE.with_alpha(0.5).alpha8(), 128);
+
+        let hsv = Hsva::from(Color::rgba8(255, 0, 0, 255));
+        assert_eq!(hsv.saturation, 1.0);
+        assert_eq!(Color::from(hsv), Color::rgba8(255, 0, 0, 255));
     }
 }
@@ -0,0 +1,737 @@
+//! A headless text editing model, shared by widgets that edit text.
+
+use std::{any::Any, collections::VecDeque, ops::Range, time::Instant};
+
+/// The default number of undo steps kept by an [`Editor`].
+pub const DEFAULT_HISTORY_DEPTH: usize = 100;
+
+/// How long the caret stays solid after a change, in seconds.
+const CARET_SOLID_TIME: f32 = 0.75;
+
+/// Creates a message from the text of an editing widget.
+pub(crate) type TextCallback = Box<dyn FnMut(&str) -> Box<dyn Any>>;
+
+pub(crate) fn text_callback<Message: 'static>(
+    mut message: impl FnMut(&str) -> Message + 'static,
+) -> TextCallback {
+    Box::new(move |text| Box::new(message(text)))
+}
+
+/// Blinks a caret, keeping it solid for a moment after each change.
+#[derive(Debug)]
+pub(crate) struct CaretBlink {
+    created: Instant,
+    last_change: Instant,
+}
+
+impl CaretBlink {
+    pub fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            last_change: Instant::now(),
+        }
+    }
+
+    /// Restarts the solid period, as after an edit or caret movement.
+    pub fn reset(&mut self) {
+        self.last_change = Instant::now();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        let time = self.created.elapsed().as_secs_f32();
+        self.last_change.elapsed().as_secs_f32() <= CARET_SOLID_TIME
+            || ((time * 2.) as u32 & 1) == 0
+    }
+}
+
+/// Implements the `on_change`, undo and redo methods
+/// of a widget that edits text with an [`Editor`].
+///
+/// The widget provides `edit`, which calls a closure with its editor
+/// and then updates the displayed text, `read_editor`, and
+/// `set_change_callback`, which stores an `on_change` callback.
+macro_rules! impl_editing_methods {
+    ($widget:ty) => {
+        impl $widget {
+            /// Causes a message to be sent when the user edits the text.
+            ///
+            /// If an `on_change` message is already set, it is overriden.
+            pub fn on_change<Message: 'static>(
+                &mut self,
+                message: impl FnMut(&str) -> Message + 'static,
+            ) -> &mut Self {
+                self.set_change_callback($crate::editor::text_callback(message));
+                self
+            }
+
+            /// Reverts the last edit. Returns whether anything was undone.
+            ///
+            /// Does not trigger the `on_change` message.
+            pub fn undo(&mut self) -> bool {
+                self.edit(|editor| editor.undo())
+            }
+
+            /// Reapplies the last undone edit. Returns whether anything was redone.
+            ///
+            /// Does not trigger the `on_change` message.
+            pub fn redo(&mut self) -> bool {
+                self.edit(|editor| editor.redo())
+            }
+
+            pub fn can_undo(&self) -> bool {
+                self.read_editor(|editor| editor.can_undo())
+            }
+
+            pub fn can_redo(&self) -> bool {
+                self.read_editor(|editor| editor.can_redo())
+            }
+
+            /// Sets the maximum number of undo steps to remember.
+            pub fn set_history_depth(&mut self, depth: usize) -> &mut Self {
+                self.edit(|editor| editor.set_history_depth(depth));
+                self
+            }
+        }
+    };
+}
+
+pub(crate) use impl_editing_methods;
+
+/// Text being edited, along with a caret and an undo / redo history.
+///
+/// Contains no rendering or input handling, so it can be
+/// driven directly in tests.
+#[derive(Debug)]
+pub(crate) struct Editor {
+    text: String,
+    /// Byte index of the caret. Always lies on a char boundary.
+    caret: usize,
+    /// The end of the selection opposite the caret, if any.
+    anchor: Option<usize>,
+    history: History,
+    preedit: Option<Preedit>,
+}
+
+/// Uncommitted text from an input method, displayed at the caret
+/// in place of any selected text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Preedit {
+    pub text: String,
+    /// Byte range of the input method's cursor within `text`.
+    pub cursor: Option<(usize, usize)>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            caret: 0,
+            anchor: None,
+            history: History::new(DEFAULT_HISTORY_DEPTH),
+            preedit: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// Replaces the entire text and moves the caret to the end.
+    ///
+    /// Recorded as its own undo step.
+    pub fn set_text(&mut self, text: String) {
+        if text == self.text {
+            return;
+        }
+        self.history.record(EditKind::Replace, self.snapshot());
+        self.caret = text.len();
+        self.anchor = None;
+        self.text = text;
+    }
+
+    /// Returns the selected byte range, if the selection is not empty.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        match anchor.cmp(&self.caret) {
+            std::cmp::Ordering::Less => Some(anchor..self.caret),
+            std::cmp::Ordering::Greater => Some(self.caret..anchor),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|range| &self.text[range])
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.caret = self.text.len();
+        self.history.end_group();
+    }
+
+    /// Inserts text at the caret, then moves the caret past it.
+    /// Any selected text is replaced.
+    ///
+    /// Consecutive insertions are grouped into a single undo step.
+    pub fn insert(&mut self, s: &str) {
+        if self.selection().is_some() {
+            self.history.record(EditKind::Replace, self.snapshot());
+            self.remove_selection();
+        } else if s.is_empty() {
+            return;
+        } else {
+            self.history.record(EditKind::Insert, self.snapshot());
+        }
+        self.text.insert_str(self.caret, s);
+        self.caret += s.len();
+    }
+
+    /// Returns the text as it would be after calling `insert(s)`.
+    pub fn preview_insert(&self, s: &str) -> String {
+        let mut text = self.text.clone();
+        match self.selection() {
+            Some(selection) => text.replace_range(selection, s),
+            None => text.insert_str(self.caret, s),
+        }
+        text
+    }
+
+    /// Deletes the selection, or the character before the caret (backspace).
+    ///
+    /// Returns whether anything was deleted.
+    pub fn delete_backward(&mut self) -> bool {
+        if self.delete_selection() {
+            return true;
+        }
+        match self.prev_boundary() {
+            Some(start) => {
+                self.history.record(EditKind::Delete, self.snapshot());
+                self.text.replace_range(start..self.caret, "");
+                self.caret = start;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deletes the selection, or the character after the caret.
+    ///
+    /// Returns whether anything was deleted.
+    pub fn delete_forward(&mut self) -> bool {
+        if self.delete_selection() {
+            return true;
+        }
+        match self.next_boundary() {
+            Some(end) => {
+                self.history.record(EditKind::Delete, self.snapshot());
+                self.text.replace_range(self.caret..end, "");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the caret one character left. Without `select`,
+    /// a non-empty selection collapses to its start instead.
+    pub fn move_left(&mut self, select: bool) {
+        match self.selection() {
+            Some(selection) if !select => self.set_caret(selection.start, false),
+            _ => {
+                if let Some(pos) = self.prev_boundary() {
+                    self.set_caret(pos, select);
+                }
+            }
+        }
+    }
+
+    /// Moves the caret one character right. Without `select`,
+    /// a non-empty selection collapses to its end instead.
+    pub fn move_right(&mut self, select: bool) {
+        match self.selection() {
+            Some(selection) if !select => self.set_caret(selection.end, false),
+            _ => {
+                if let Some(pos) = self.next_boundary() {
+                    self.set_caret(pos, select);
+                }
+            }
+        }
+    }
+
+    pub fn move_home(&mut self, select: bool) {
+        self.set_caret(0, select);
+    }
+
+    pub fn move_end(&mut self, select: bool) {
+        self.set_caret(self.text.len(), select);
+    }
+
+    /// Moves the caret to the given byte index. If `select` is set,
+    /// the selection is extended to the new position; otherwise
+    /// it is cleared.
+    ///
+    /// Moving the caret ends the current group of edits.
+    pub fn set_caret(&mut self, caret: usize, select: bool) {
+        assert!(
+            self.text.is_char_boundary(caret),
+            "caret must lie on a char boundary"
+        );
+        if select {
+            self.anchor.get_or_insert(self.caret);
+        } else {
+            self.anchor = None;
+        }
+        self.caret = caret;
+        self.history.end_group();
+    }
+
+    /// Sets the text being composed by an input method.
+    ///
+    /// Empty text cancels the composition. The cursor is moved
+    /// into `text` and back to char boundaries if it is outside them.
+    pub fn set_preedit(&mut self, text: String, cursor: Option<(usize, usize)>) {
+        let snap = |index: usize| {
+            let mut index = index.min(text.len());
+            while !text.is_char_boundary(index) {
+                index -= 1;
+            }
+            index
+        };
+        let cursor = cursor.map(|(start, end)| {
+            let end = snap(end);
+            (snap(start).min(end), end)
+        });
+        self.preedit = if text.is_empty() {
+            None
+        } else {
+            Some(Preedit { text, cursor })
+        };
+    }
+
+    pub fn clear_preedit(&mut self) {
+        self.preedit = None;
+    }
+
+    pub fn preedit(&self) -> Option<&Preedit> {
+        self.preedit.as_ref()
+    }
+
+    /// Returns the text with any preedit text inserted at the caret,
+    /// replacing the selection like `insert` would.
+    pub fn composed_text(&self) -> String {
+        match &self.preedit {
+            Some(preedit) => self.preview_insert(&preedit.text),
+            None => self.text.clone(),
+        }
+    }
+
+    /// Returns the byte range of the preedit text within `composed_text()`.
+    pub fn preedit_range(&self) -> Option<Range<usize>> {
+        let start = self.replaced_range().start;
+        self.preedit
+            .as_ref()
+            .map(|preedit| start..start + preedit.text.len())
+    }
+
+    /// Returns the caret position within `composed_text()`.
+    ///
+    /// While composing, this is the end of the input method's cursor.
+    pub fn composed_caret(&self) -> usize {
+        let start = self.replaced_range().start;
+        match &self.preedit {
+            Some(Preedit {
+                cursor: Some((_, end)),
+                ..
+            }) => start + end,
+            Some(preedit) => start + preedit.text.len(),
+            None => self.caret,
+        }
+    }
+
+    /// Maps a byte index in `text()` to the same position in `composed_text()`.
+    ///
+    /// Positions inside a selection replaced by preedit text
+    /// map to the start of the preedit text.
+    pub fn composed_index(&self, index: usize) -> usize {
+        let replaced = self.replaced_range();
+        match &self.preedit {
+            Some(preedit) if index >= replaced.end => index - replaced.len() + preedit.text.len(),
+            Some(_) if index > replaced.start => replaced.start,
+            _ => index,
+        }
+    }
+
+    /// Maps a byte index in `composed_text()` back to `text()`.
+    ///
+    /// Positions inside the preedit text map to where it is inserted.
+    pub fn text_index(&self, index: usize) -> usize {
+        let replaced = self.replaced_range();
+        match self.preedit_range() {
+            Some(range) if index >= range.end => index - range.len() + replaced.len(),
+            Some(range) if index > range.start => replaced.start,
+            _ => index,
+        }
+    }
+
+    /// Returns the byte range of `text()` that preedit text replaces:
+    /// the selection, or an empty range at the caret.
+    fn replaced_range(&self) -> Range<usize> {
+        self.selection().unwrap_or(self.caret..self.caret)
+    }
+
+    /// Reverts the last undo step. Returns whether anything was undone.
+    pub fn undo(&mut self) -> bool {
+        let current = self.snapshot();
+        match self.history.undo(current) {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the last undone step. Returns whether anything was redone.
+    pub fn redo(&mut self) -> bool {
+        let current = self.snapshot();
+        match self.history.redo(current) {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Sets the maximum number of undo steps to keep.
+    ///
+    /// The oldest steps are discarded first.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history.max_depth = depth;
+        self.history.truncate();
+    }
+
+    /// Deletes the selected text, if any, as its own undo step.
+    fn delete_selection(&mut self) -> bool {
+        if self.selection().is_none() {
+            return false;
+        }
+        self.history.record(EditKind::Replace, self.snapshot());
+        self.remove_selection();
+        true
+    }
+
+    fn remove_selection(&mut self) {
+        if let Some(selection) = self.selection() {
+            self.text.replace_range(selection.clone(), "");
+            self.caret = selection.start;
+        }
+        self.anchor = None;
+    }
+
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.caret]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.caret..]
+            .chars()
+            .next()
+            .map(|c| self.caret + c.len_utf8())
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            caret: self.caret,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.caret = snapshot.caret;
+        self.anchor = None;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    text: String,
+    caret: usize,
+}
+
+/// Kinds of edits. Consecutive edits of the same kind
+/// are merged into one undo step, except for `Replace`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Replace,
+}
+
+#[derive(Debug)]
+struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    max_depth: usize,
+    /// The kind of the edit group currently being extended, if any.
+    group: Option<EditKind>,
+}
+
+impl History {
+    fn new(max_depth: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_depth,
+            group: None,
+        }
+    }
+
+    /// Called before an edit is applied, with the state before the edit.
+    fn record(&mut self, kind: EditKind, before: Snapshot) {
+        if self.group != Some(kind) || kind == EditKind::Replace {
+            self.undo.push_back(before);
+            self.truncate();
+        }
+        self.group = Some(kind);
+        self.redo.clear();
+    }
+
+    fn end_group(&mut self) {
+        self.group = None;
+    }
+
+    fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let snapshot = self.undo.pop_back()?;
+        self.redo.push(current);
+        self.group = None;
+        Some(snapshot)
+    }
+
+    fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push_back(current);
+        self.group = None;
+        Some(snapshot)
+    }
+
+    fn truncate(&mut self) {
+        while self.undo.len() > self.max_depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_str(editor: &mut Editor, s: &str) {
+        for c in s.chars() {
+            editor.insert(c.encode_utf8(&mut [0; 4]));
+        }
+    }
+
+    #[test]
+    fn typing_is_grouped() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "hello");
+        assert!(editor.can_undo());
+
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "");
+        assert!(!editor.can_undo());
+
+        assert!(editor.redo());
+        assert_eq!(editor.text(), "hello");
+        assert_eq!(editor.caret(), 5);
+    }
+
+    #[test]
+    fn caret_movement_splits_groups() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "ac");
+        editor.move_left(false);
+        type_str(&mut editor, "b");
+        assert_eq!(editor.text(), "abc");
+
+        editor.undo();
+        assert_eq!(editor.text(), "ac");
+        editor.undo();
+        assert_eq!(editor.text(), "");
+    }
+
+    #[test]
+    fn deletion_is_a_separate_step() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "hello");
+        editor.delete_backward();
+        editor.delete_backward();
+        assert_eq!(editor.text(), "hel");
+
+        editor.undo();
+        assert_eq!(editor.text(), "hello");
+        editor.undo();
+        assert_eq!(editor.text(), "");
+    }
+
+    #[test]
+    fn edit_clears_redo() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "a");
+        editor.undo();
+        assert!(editor.can_redo());
+        type_str(&mut editor, "b");
+        assert!(!editor.can_redo());
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn history_depth() {
+        let mut editor = Editor::new();
+        editor.set_history_depth(2);
+        editor.set_text("a".to_owned());
+        editor.set_text("b".to_owned());
+        editor.set_text("c".to_owned());
+
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert!(!editor.undo());
+        assert_eq!(editor.text(), "a");
+    }
+
+    #[test]
+    fn composition() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "ab");
+        editor.move_left(false);
+
+        editor.set_preedit("にほ".to_owned(), Some((3, 3)));
+        assert_eq!(editor.text(), "ab");
+        assert_eq!(editor.composed_text(), "aにほb");
+        assert_eq!(editor.preedit_range(), Some(1..7));
+        assert_eq!(editor.composed_caret(), 4);
+
+        editor.clear_preedit();
+        editor.insert("日本");
+        assert_eq!(editor.text(), "a日本b");
+        assert_eq!(editor.composed_text(), editor.text());
+
+        editor.set_preedit("x".to_owned(), None);
+        editor.set_preedit(String::new(), None);
+        assert!(editor.preedit().is_none());
+    }
+
+    #[test]
+    fn preedit_cursor_is_clamped() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "ab");
+        editor.move_left(false);
+
+        editor.set_preedit("にほ".to_owned(), Some((4, 100)));
+        assert_eq!(editor.preedit().unwrap().cursor, Some((3, 6)));
+        assert_eq!(editor.composed_caret(), 7);
+        let composed = editor.composed_text();
+        assert_eq!(&composed[..editor.composed_caret()], "aにほ");
+
+        editor.set_preedit("にほ".to_owned(), Some((5, 2)));
+        assert_eq!(editor.preedit().unwrap().cursor, Some((0, 0)));
+        assert_eq!(editor.composed_caret(), 1);
+    }
+
+    #[test]
+    fn composition_replaces_selection() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "abcdef");
+        editor.set_caret(1, false);
+        editor.set_caret(4, true);
+
+        editor.set_preedit("xy".to_owned(), None);
+        let composed = editor.composed_text();
+        assert_eq!(composed, "axyef");
+        assert_eq!(editor.preedit_range(), Some(1..3));
+        assert_eq!(editor.composed_caret(), 3);
+        assert_eq!(editor.composed_index(0), 0);
+        assert_eq!(editor.composed_index(2), 1);
+        assert_eq!(editor.composed_index(5), 4);
+        assert_eq!(editor.text_index(2), 1);
+        assert_eq!(editor.text_index(4), 5);
+
+        editor.select_all();
+        editor.set_preedit("x".to_owned(), None);
+        assert_eq!(editor.composed_text(), "x");
+        assert_eq!(editor.composed_caret(), 1);
+        assert_eq!(editor.composed_index(editor.caret()), 1);
+    }
+
+    #[test]
+    fn selection() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "hello world");
+        editor.move_left(true);
+        editor.move_left(true);
+        assert_eq!(editor.selection(), Some(9..11));
+        assert_eq!(editor.selected_text(), Some("ld"));
+
+        type_str(&mut editor, "k");
+        assert_eq!(editor.text(), "hello work");
+        assert!(editor.selection().is_none());
+
+        editor.set_caret(0, false);
+        editor.set_caret(6, true);
+        assert!(editor.delete_forward());
+        assert_eq!(editor.text(), "work");
+        editor.undo();
+        assert_eq!(editor.text(), "hello work");
+
+        editor.select_all();
+        editor.move_right(false);
+        assert!(editor.selection().is_none());
+        assert_eq!(editor.caret(), 10);
+    }
+
+    #[test]
+    fn composed_positions() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "ab");
+        editor.move_left(false);
+        editor.set_preedit("xyz".to_owned(), None);
+        assert_eq!(editor.composed_index(0), 0);
+        assert_eq!(editor.composed_index(2), 5);
+        assert_eq!(editor.text_index(2), 1);
+        assert_eq!(editor.text_index(5), 2);
+    }
+
+    #[test]
+    fn multibyte_chars() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "aé日");
+        editor.move_left(false);
+        editor.delete_backward();
+        assert_eq!(editor.text(), "a日");
+        editor.delete_forward();
+        assert_eq!(editor.text(), "a");
+        assert_eq!(editor.caret(), 1);
+    }
+}
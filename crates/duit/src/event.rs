@@ -9,7 +9,7 @@ pub use winit::event::{ModifiersState, MouseButton, VirtualKeyCode};
 /// An event delivered to an element.
 ///
 /// All elements are notified of all events.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum Event {
     /// A mouse click.
     MousePress {
@@ -33,12 +33,29 @@ pub enum Event {
     Character(char),
     /// Scrolling along an axis.
     Scroll { offset: Vec2, mouse_pos: Vec2 },
+    /// An input method updated the text being composed.
+    ///
+    /// `text` is the uncommitted (preedit) text, and `cursor`
+    /// is the byte range of the cursor within it, if any.
+    /// Empty text ends the composition without committing anything.
+    ///
+    /// winit 0.26 reports no preedit text, so neither `EventTracker` nor
+    /// the bundled `duit-platform` ever produce composition events.
+    /// Text committed by an input method still arrives as `Character`
+    /// events. Platforms with their own IME support (and tests) deliver
+    /// composition events directly through `Ui::handle_window_event`.
+    CompositionUpdate {
+        text: String,
+        cursor: Option<(usize, usize)>,
+    },
+    /// An input method finished composing `text`, which should be inserted.
+    CompositionCommit { text: String },
 }
 
 impl Event {
     /// Applies a translation to any coordinates in this event.
     pub fn translated(&self, delta: Vec2) -> Self {
-        match *self {
+        match self.clone() {
             Event::MousePress {
                 pos,
                 button,
@@ -60,7 +77,7 @@ impl Event {
                 offset,
                 mouse_pos: mouse_pos + delta,
             },
-            // events that contain no coordinates (keyboard events)
+            // events that contain no coordinates (keyboard and composition events)
             e => e,
         }
     }
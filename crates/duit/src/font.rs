@@ -0,0 +1,81 @@
+use dume::font::{Query, Style, Weight};
+use serde::Deserialize;
+
+/// The weight of a font, as set by the `default_weight`
+/// and `font_weight` style properties.
+///
+/// Deserializes from snake case names like `semi_bold`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontWeight {
+    Thin,
+    ExtraLight,
+    Light,
+    #[default]
+    Normal,
+    Medium,
+    SemiBold,
+    Bold,
+    ExtraBold,
+    Black,
+}
+
+impl From<FontWeight> for Weight {
+    fn from(weight: FontWeight) -> Self {
+        match weight {
+            FontWeight::Thin => Weight::Thin,
+            FontWeight::ExtraLight => Weight::ExtraLight,
+            FontWeight::Light => Weight::Light,
+            FontWeight::Normal => Weight::Normal,
+            FontWeight::Medium => Weight::Medium,
+            FontWeight::SemiBold => Weight::SemiBold,
+            FontWeight::Bold => Weight::Bold,
+            FontWeight::ExtraBold => Weight::ExtraBold,
+            FontWeight::Black => Weight::Black,
+        }
+    }
+}
+
+/// Whether a font is upright or slanted, as set by the
+/// `default_font_style` and `font_style` style properties.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl From<FontStyle> for Style {
+    fn from(style: FontStyle) -> Self {
+        match style {
+            FontStyle::Normal => Style::Normal,
+            FontStyle::Italic => Style::Italic,
+            FontStyle::Oblique => Style::Oblique,
+        }
+    }
+}
+
+/// Builds a font query from style properties.
+pub(crate) fn query(family: &str, weight: FontWeight, style: FontStyle) -> Query {
+    Query {
+        family: Some(family.into()),
+        weight: weight.into(),
+        style: style.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let weight: FontWeight = serde_yaml::from_str("semi_bold").unwrap();
+        assert_eq!(weight, FontWeight::SemiBold);
+        let style: FontStyle = serde_yaml::from_str("italic").unwrap();
+        assert_eq!(Style::from(style), Style::Italic);
+        assert!(serde_yaml::from_str::<FontWeight>("heavy").is_err());
+    }
+}
@@ -1,5 +1,11 @@
+mod border;
 mod color;
+mod editor;
 mod event;
+mod font;
+mod native_window;
+mod paint;
+mod shadow;
 mod spec;
 mod style;
 mod ui;
@@ -11,16 +17,26 @@ use std::{cell::RefCell, rc::Rc};
 
 pub use color::Color;
 pub use event::Event;
+pub use font::{FontStyle, FontWeight};
+pub use native_window::{NativeWindow, NativeWindowId, NativeWindowOptions, NativeWindowRequest};
+pub use paint::{GradientStop, Paint};
+pub use shadow::Shadow;
 pub use spec::InstanceHandle;
-pub use style::StyleError;
-pub use ui::{Ui, WindowId};
+#[doc(hidden)]
+pub use style::StyleEngine;
+pub use style::{
+    ExpressionError, ExpressionErrorKind, FileResolver, StyleError, StyleResolver,
+    StyleValidationError,
+};
+pub use ui::{AddSpecError, Ui, WindowId};
 use widget::WidgetPod;
 pub use widget::{Widget, WidgetData, WidgetHandle, WidgetPodHandle, WidgetState};
 pub use window::WindowPositioner;
 
 pub use duit_core::{
+    source::{Location, Snippet},
     spec::{Spec, SpecError, ValidationError},
-    Align, Axis,
+    Align, Axis, Insets,
 };
 
 pub use dume::Rect;
@@ -30,6 +46,10 @@ pub use dume::text;
 
 pub trait RectExt {
     fn expanded(self, radius: f32) -> Self;
+
+    /// The area covered by both rectangles, which
+    /// is empty if they do not overlap.
+    fn intersection(self, other: Self) -> Self;
 }
 
 impl RectExt for Rect {
@@ -39,6 +59,30 @@ impl RectExt for Rect {
             self.size + Vec2::splat(radius),
         )
     }
+
+    fn intersection(self, other: Self) -> Self {
+        let pos = self.pos.max(other.pos);
+        let end = (self.pos + self.size).min(other.pos + other.size);
+        Rect::new(pos, (end - pos).max(Vec2::ZERO))
+    }
+}
+
+pub trait InsetsExt {
+    /// The offset of the inner box from the outer box.
+    fn top_left(self) -> Vec2;
+
+    /// The total horizontal and vertical insets.
+    fn size(self) -> Vec2;
+}
+
+impl InsetsExt for Insets {
+    fn top_left(self) -> Vec2 {
+        Vec2::new(self.left, self.top)
+    }
+
+    fn size(self) -> Vec2 {
+        Vec2::new(self.horizontal(), self.vertical())
+    }
 }
 
 trait AlignExt {
@@ -0,0 +1,86 @@
+use dume::Rect;
+use glam::{vec2, Vec2};
+
+use crate::event::EventTracker;
+
+slotmap::new_key_type! {
+    /// Identifies a native (operating system) window tracked by a [`Ui`](crate::Ui).
+    pub struct NativeWindowId;
+}
+
+/// Options for opening a native window.
+#[derive(Debug, Clone)]
+pub struct NativeWindowOptions {
+    pub title: String,
+    /// Initial inner size in logical pixels.
+    pub size: Vec2,
+}
+
+impl Default for NativeWindowOptions {
+    fn default() -> Self {
+        Self {
+            title: "duit".to_owned(),
+            size: vec2(800., 600.),
+        }
+    }
+}
+
+/// A change to the set of native windows, requested by the
+/// application and carried out by the platform.
+///
+/// Retrieve pending requests with [`Ui::take_native_window_requests`](crate::Ui::take_native_window_requests).
+#[derive(Debug, Clone)]
+pub enum NativeWindowRequest {
+    Open(NativeWindowId, NativeWindowOptions),
+    Close(NativeWindowId),
+}
+
+/// The state a `Ui` keeps for each native window.
+///
+/// Each native window contains its own set of duit windows
+/// and is rendered to its own canvas.
+pub struct NativeWindow {
+    title: String,
+    logical_size: Vec2,
+    scale_factor: f64,
+    pub(crate) event_tracker: EventTracker,
+    pub(crate) ime_caret: Option<Rect>,
+}
+
+impl NativeWindow {
+    pub(crate) fn new(options: NativeWindowOptions) -> Self {
+        Self {
+            title: options.title,
+            logical_size: options.size,
+            scale_factor: 1.,
+            event_tracker: EventTracker::default(),
+            ime_caret: None,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The inner size of the window in logical pixels.
+    pub fn logical_size(&self) -> Vec2 {
+        self.logical_size
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Gets the text caret reported by the focused text widget
+    /// during the last render, in logical window coordinates.
+    ///
+    /// Used to position the IME candidate window.
+    pub fn ime_caret(&self) -> Option<Rect> {
+        self.ime_caret
+    }
+
+    pub(crate) fn set_size(&mut self, logical_size: Vec2, scale_factor: f64) {
+        self.logical_size = logical_size;
+        self.scale_factor = scale_factor;
+    }
+}
@@ -0,0 +1,349 @@
+//! Paints: what the background of a widget is filled with.
+//!
+//! A paint is written the way a color is, and can be any color
+//! or a call to one of the paint functions:
+//!
+//! ```yaml
+//! background_color: '#fc4c02'
+//! background_color: linear_gradient(90, $primary, $secondary 70%, white)
+//! background_color: radial_gradient(white, $primary 80%)
+//! background_color: pattern('wood', 64)
+//! ```
+//!
+//! See the `style` module for the arguments these functions take.
+
+use std::{fmt, str::FromStr};
+
+use dume::Rect;
+use glam::Vec2;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{style::parse_paint, widget::Context, Color, ExpressionError};
+
+/// A solid color, gradient or texture pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Solid(Color),
+    /// A gradient along a line through the center of the filled area.
+    LinearGradient {
+        /// The direction of the gradient in degrees
+        /// clockwise from pointing up.
+        angle: f32,
+        /// Sorted by offset. The first stop lies on the
+        /// corner the gradient starts from, at offset 0.
+        stops: Vec<GradientStop>,
+    },
+    /// A gradient from the center of the filled area outwards.
+    RadialGradient {
+        inner: Color,
+        outer: Color,
+        /// Where the outer color is reached, as a fraction
+        /// of the distance to the farthest corner.
+        radius: f32,
+    },
+    /// A texture repeated across the filled area.
+    Pattern {
+        texture: String,
+        /// The width of each tile. Defaults to the width of the texture.
+        width: Option<f32>,
+    },
+}
+
+/// A color at some offset along a [`Paint::LinearGradient`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GradientStop {
+    pub color: Color,
+    pub offset: f32,
+}
+
+impl Paint {
+    /// Fills the current path of the canvas, which lies within `bounds`.
+    ///
+    /// Patterns are clipped to `bounds` rather than to the path,
+    /// so they ignore rounded corners.
+    pub fn fill(&self, cx: &mut Context, bounds: Rect) {
+        match self {
+            Paint::Solid(color) => {
+                cx.canvas.solid_color(*color).fill();
+            }
+            Paint::LinearGradient { angle, stops } => fill_linear(cx, bounds, *angle, stops),
+            Paint::RadialGradient {
+                inner,
+                outer,
+                radius,
+            } => {
+                let center = bounds.pos + bounds.size / 2.;
+                let radius = bounds.size.length() / 2. * radius;
+                cx.canvas
+                    .radial_gradient(center, radius, *inner, *outer)
+                    .fill();
+            }
+            Paint::Pattern { texture, width } => fill_pattern(cx, bounds, texture, *width),
+        }
+    }
+}
+
+fn fill_linear(cx: &mut Context, bounds: Rect, angle: f32, stops: &[GradientStop]) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let direction = Vec2::new(sin, -cos);
+    // Long enough for the gradient to reach the corners
+    // on both ends, as in CSS.
+    let length = (bounds.size.x * sin).abs() + (bounds.size.y * cos).abs();
+    let center = bounds.pos + bounds.size / 2.;
+    let point = |offset: f32| center + direction * length * (offset - 0.5);
+
+    if let [a, b] = stops {
+        cx.canvas
+            .linear_gradient(point(a.offset), point(b.offset), a.color, b.color)
+            .fill();
+        return;
+    }
+
+    // The canvas only draws two-color gradients, so draw
+    // each segment clipped to its band of the area.
+    // Such gradients always run along an axis.
+    let horizontal = direction.x.abs() > direction.y.abs();
+    for (i, pair) in stops.windows(2).enumerate() {
+        let (a, b) = (pair[0], pair[1]);
+        let start = if i == 0 { a.offset.min(0.) } else { a.offset };
+        let end = if i == stops.len() - 2 {
+            b.offset.max(1.)
+        } else {
+            b.offset
+        };
+        let (p0, p1) = (point(start), point(end));
+        let band = if horizontal {
+            Rect::new(
+                Vec2::new(p0.x.min(p1.x), bounds.pos.y),
+                Vec2::new((p1.x - p0.x).abs(), bounds.size.y),
+            )
+        } else {
+            Rect::new(
+                Vec2::new(bounds.pos.x, p0.y.min(p1.y)),
+                Vec2::new(bounds.size.x, (p1.y - p0.y).abs()),
+            )
+        };
+
+        cx.push_clip(band);
+        cx.canvas
+            .linear_gradient(point(a.offset), point(b.offset), a.color, b.color)
+            .fill();
+        cx.pop_clip();
+    }
+}
+
+fn fill_pattern(cx: &mut Context, bounds: Rect, texture: &str, width: Option<f32>) {
+    let texture = match cx.canvas.context().texture_for_name(texture) {
+        Ok(texture) => texture,
+        Err(_) => return,
+    };
+    let dimensions = cx.canvas.context().texture_dimensions(texture).as_f32();
+    let tile_width = width.unwrap_or(dimensions.x);
+    let tile_height = tile_width * dimensions.y / dimensions.x;
+    if tile_width <= 0. || tile_height <= 0. {
+        return;
+    }
+
+    cx.push_clip(bounds);
+    let mut y = bounds.pos.y;
+    while y < bounds.pos.y + bounds.size.y {
+        let mut x = bounds.pos.x;
+        while x < bounds.pos.x + bounds.size.x {
+            cx.canvas.draw_sprite(texture, Vec2::new(x, y), tile_width);
+            x += tile_width;
+        }
+        y += tile_height;
+    }
+    cx.pop_clip();
+}
+
+/// Fills in missing stop offsets: the first stop defaults to 0, the last
+/// to 1, and the others are spread evenly between their neighbors.
+pub(crate) fn spread_stops(stops: Vec<(Color, Option<f32>)>) -> Vec<GradientStop> {
+    let mut offsets: Vec<Option<f32>> = stops.iter().map(|(_, offset)| *offset).collect();
+    if let Some(first) = offsets.first_mut() {
+        first.get_or_insert(0.);
+    }
+    if let Some(last) = offsets.last_mut() {
+        last.get_or_insert(1.);
+    }
+
+    let mut i = 0;
+    while i < offsets.len() {
+        if offsets[i].is_none() {
+            let start = i - 1;
+            let end = (i..offsets.len())
+                .find(|&j| offsets[j].is_some())
+                .expect("last offset is set");
+            let (a, b) = (offsets[start].unwrap(), offsets[end].unwrap());
+            for (j, offset) in offsets.iter_mut().enumerate().take(end).skip(i) {
+                *offset = Some(a + (b - a) * (j - start) as f32 / (end - start) as f32);
+            }
+            i = end;
+        }
+        i += 1;
+    }
+
+    stops
+        .into_iter()
+        .zip(offsets)
+        .map(|((color, _), offset)| GradientStop {
+            color,
+            offset: offset.unwrap(),
+        })
+        .collect()
+}
+
+impl FromStr for Paint {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Color::from_str(s) {
+            Ok(color) => Ok(Paint::Solid(color)),
+            Err(_) => parse_paint(s),
+        }
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl fmt::Display for Paint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Paint::Solid(color) => write!(f, "{}", color),
+            Paint::LinearGradient { angle, stops } => {
+                write!(f, "linear_gradient({}", angle)?;
+                for stop in stops {
+                    write!(f, ", {} {}", stop.color, stop.offset)?;
+                }
+                f.write_str(")")
+            }
+            Paint::RadialGradient {
+                inner,
+                outer,
+                radius,
+            } => write!(f, "radial_gradient({}, {} {})", inner, outer, radius),
+            Paint::Pattern { texture, width } => {
+                write!(f, "pattern('{}'", texture)?;
+                if let Some(width) = width {
+                    write!(f, ", {}", width)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Paint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Paint::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Paint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let red = Color::rgba8(255, 0, 0, 255);
+        let blue = Color::rgba8(0, 0, 255, 255);
+        assert_eq!(Paint::from_str("red"), Ok(Paint::Solid(red)));
+        assert_eq!(
+            Paint::from_str("linear_gradient(red, blue 80%)"),
+            Ok(Paint::LinearGradient {
+                angle: 180.,
+                stops: vec![
+                    GradientStop {
+                        color: red,
+                        offset: 0.,
+                    },
+                    GradientStop {
+                        color: blue,
+                        offset: 0.8,
+                    },
+                ],
+            })
+        );
+        assert_eq!(
+            Paint::from_str("radial_gradient(red, blue)"),
+            Ok(Paint::RadialGradient {
+                inner: red,
+                outer: blue,
+                radius: 1.,
+            })
+        );
+        assert_eq!(
+            Paint::from_str("pattern('wood', 2 * 32)"),
+            Ok(Paint::Pattern {
+                texture: "wood".to_owned(),
+                width: Some(64.),
+            })
+        );
+    }
+
+    #[test]
+    fn spread_offsets() {
+        let paint = Paint::from_str("linear_gradient(90, red, red 40%, red, red)").unwrap();
+        let offsets: Vec<f32> = match paint {
+            Paint::LinearGradient { stops, .. } => stops.iter().map(|s| s.offset).collect(),
+            _ => unreachable!(),
+        };
+        for (offset, expected) in offsets.into_iter().zip([0., 0.4, 0.7, 1.]) {
+            assert!(
+                (offset - expected).abs() < 1e-6,
+                "{} != {}",
+                offset,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for paint in [
+            "#fc4c02",
+            "linear_gradient(45, red, blue)",
+            "linear_gradient(0, red, lime 25%, blue)",
+            "radial_gradient(white, black 50%)",
+            "pattern('wood')",
+            "pattern(\"wood\", 16)",
+        ] {
+            let parsed = Paint::from_str(paint).unwrap();
+            assert_eq!(Paint::from_str(&parsed.to_string()), Ok(parsed));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for paint in [
+            "linear_gradient(red)",
+            "linear_gradient(45, red, lime, blue)",
+            "radial_gradient(red 10%, blue)",
+            "radial_gradient(red, lime, blue)",
+            "pattern(red)",
+            "pattern()",
+            "linear_gradient(red, blue $offset)",
+            "12",
+        ] {
+            assert!(Paint::from_str(paint).is_err(), "{}", paint);
+        }
+    }
+}
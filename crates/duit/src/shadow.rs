@@ -0,0 +1,139 @@
+use dume::{Canvas, Rect};
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::{widget::Context, Color};
+
+/// The most layers drawn to approximate a blurred shadow.
+const MAX_BLUR_LAYERS: u32 = 8;
+
+/// A drop shadow, glow or inner shadow around a box, as used
+/// by the `shadow` property of box-like widget styles.
+///
+/// ```yaml
+/// shadow:
+///   color: rgba(0, 0, 0, 120)
+///   offset: [0, 2]
+///   blur: 6
+///   spread: 1
+/// ```
+///
+/// Only `color` is required. With `inset: true`, the shadow is cast
+/// inwards from the edges instead, as for a pressed button. A shadow with
+/// no offset and a bright color makes an outer glow.
+///
+/// The canvas cannot blur, so blur is approximated by
+/// stacking translucent layers of increasing size.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Shadow {
+    color: Color,
+    #[serde(default)]
+    offset: [f32; 2],
+    #[serde(default)]
+    blur: f32,
+    #[serde(default)]
+    spread: f32,
+    #[serde(default)]
+    inset: bool,
+}
+
+impl Shadow {
+    /// Paints the shadow behind a box, before its background.
+    /// Does nothing for inner shadows.
+    pub fn paint_outer(&self, canvas: &mut Canvas, bounds: Rect, border_radius: f32) {
+        if self.inset {
+            return;
+        }
+
+        let pos = bounds.pos + Vec2::from(self.offset);
+        for (grow, color) in self.layers() {
+            // Shrinking by more than half the box leaves nothing to draw.
+            let grow = grow.max(-bounds.size.min_element() / 2.);
+            canvas
+                .begin_path()
+                .rounded_rect(
+                    pos - Vec2::splat(grow),
+                    bounds.size + Vec2::splat(grow * 2.),
+                    (border_radius + grow).max(0.),
+                )
+                .solid_color(color)
+                .fill();
+        }
+    }
+
+    /// Paints the shadow inside a box, after its background.
+    /// Does nothing for outer shadows.
+    ///
+    /// The shadow is clipped to `bounds`, so it can bleed
+    /// past rounded corners.
+    pub fn paint_inner(&self, cx: &mut Context, bounds: Rect, border_radius: f32) {
+        if !self.inset {
+            return;
+        }
+
+        let offset = Vec2::from(self.offset);
+        // How far the offset moves an edge into the box, which the
+        // layers have to cover on the side facing away from it.
+        let reach = offset.abs().max_element();
+        cx.push_clip(bounds);
+        for (grow, color) in self.layers() {
+            // Mirror the outer layers, so the shadow reaches from `spread - blur / 2`
+            // to `spread + blur / 2` deep, darkest where the layers overlap at the edges.
+            let depth = (self.spread * 2. - grow).clamp(0., bounds.size.min_element() / 2.);
+            // A stroke centered between `-reach` and `depth`.
+            let center = (depth - reach) / 2.;
+            cx.canvas
+                .begin_path()
+                .rounded_rect(
+                    bounds.pos + offset + Vec2::splat(center),
+                    bounds.size - Vec2::splat(center * 2.),
+                    (border_radius - center).max(0.),
+                )
+                .solid_color(color)
+                .stroke_width(depth + reach)
+                .stroke();
+        }
+        cx.pop_clip();
+    }
+
+    /// Returns how far each layer extends past the box, and its color.
+    fn layers(&self) -> impl Iterator<Item = (f32, Color)> + '_ {
+        let count = (self.blur.ceil() as u32).clamp(1, MAX_BLUR_LAYERS);
+        let color = self.color.with_alpha(self.color.alpha() / count as f32);
+        (0..count).map(move |i| {
+            let t = (i as f32 + 0.5) / count as f32;
+            let grow = if count == 1 {
+                self.spread
+            } else {
+                self.spread + self.blur * (0.5 - t)
+            };
+            (grow, color)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let shadow: Shadow = serde_yaml::from_str("{color: black, blur: 4}").unwrap();
+        assert_eq!(shadow.offset, [0., 0.]);
+        assert!(!shadow.inset);
+
+        let layers: Vec<_> = shadow.layers().collect();
+        assert_eq!(layers.len(), 4);
+        assert_eq!(layers[0].0, 1.5);
+        assert_eq!(layers[3].0, -1.5);
+        assert_eq!(layers[0].1, Color::BLACK.with_alpha(0.25));
+    }
+
+    #[test]
+    fn unblurred() {
+        let shadow: Shadow =
+            serde_yaml::from_str("{color: black, offset: [1, 2], spread: 3, inset: true}").unwrap();
+        let layers: Vec<_> = shadow.layers().collect();
+        assert_eq!(layers, [(3., Color::BLACK)]);
+    }
+}
@@ -1,27 +1,138 @@
 //! The style system.
+//!
+//! # Precedence
+//!
+//! When several styles match an element, they are merged
+//! so that properties from later styles override earlier ones.
+//! Matching styles are ordered by:
+//!
+//! 1. explicit `priority`, an optional integer key in a rule
+//!    (default `0`); higher priorities apply later;
+//! 2. specificity, the number of classes a query requires of
+//!    the element and its ancestors (`a & b` and `a > b` have 2;
+//!    an `a | b & c` query counts the most specific of its alternatives);
+//! 3. source order: the theme layers first, then stylesheets in the
+//!    order they were added, then rules within a sheet. A sheet's
+//!    imports come before the sheet itself.
+//!
+//! A property whose value ends in `!important`, like
+//! `color: $fg !important`, is applied after all other
+//! properties, in the same order as above.
+//!
+//! Last of all come a widget's style overrides, set by the
+//! `style` mapping of its spec or with `WidgetHandle::set_style_override`.
 
-use std::{any::Any, collections::HashMap, rc::Rc, str::FromStr};
+use std::{
+    any::{Any, TypeId},
+    borrow::Cow,
+    collections::HashMap,
+    rc::Rc,
+};
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
+use duit_core::source::{Snippet, SourceFile};
 use indexmap::IndexMap;
 use serde::{de::DeserializeOwned, Deserialize};
 use serde_yaml::Value;
 
+use self::{query::ClassQuery, resolve::Resolver, validation::Registration};
+
+pub(crate) use self::expr::parse_paint;
+
+pub use self::{
+    expr::{ExpressionError, ExpressionErrorKind},
+    resolve::{FileResolver, StyleResolver},
+    validation::StyleValidationError,
+};
+
+mod expr;
+mod query;
+mod resolve;
+mod validation;
+
 const VARIABLE_PREFIX: char = '$';
+const IMPORTANT_SUFFIX: &str = "!important";
+const PRIORITY_KEY: &str = "priority";
+/// Stands in for the rule name in errors from style overrides.
+const OVERRIDE_RULE: &str = "style override";
 
 #[derive(Debug, thiserror::Error)]
 pub enum StyleError {
-    #[error("missing variable '{0}'")]
-    MissingVariable(String),
+    #[error("in rule '{rule}': {error}{}", format_snippet(.snippet))]
+    Expression {
+        rule: String,
+        error: ExpressionError,
+        /// Where the expression is in the sheet, if known.
+        snippet: Option<Box<Snippet>>,
+    },
     #[error(transparent)]
     Yaml(#[from] serde_yaml::Error),
+    #[error(
+        "{}{}",
+        duit_core::source::strip_yaml_location(&.error.to_string()),
+        format_snippet(.snippet)
+    )]
+    Parse {
+        error: serde_yaml::Error,
+        snippet: Option<Box<Snippet>>,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("in stylesheet '{path}': {error}")]
+    Sheet {
+        path: String,
+        error: Box<StyleError>,
+    },
+    #[error("stylesheet '{0}' imports itself")]
+    ImportCycle(String),
     #[error("style missing")]
     Missing,
+    #[error("no stylesheet named '{0}'")]
+    MissingStylesheet(String),
+    #[error("no theme named '{0}'")]
+    MissingTheme(String),
+    #[error("style priority must be an integer")]
+    InvalidPriority,
+    #[error("invalid styles:{}", format_validation_errors(.0))]
+    Invalid(Vec<StyleValidationError>),
+}
+
+fn format_validation_errors(errors: &[StyleValidationError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("\n  {}", error.to_string().replace('\n', "\n  ")))
+        .collect()
+}
+
+fn format_snippet(snippet: &Option<Box<Snippet>>) -> String {
+    match snippet {
+        Some(snippet) => format!("\n{}", snippet),
+        None => String::new(),
+    }
 }
 
 /// Manages styles.
+///
+/// Styles come from the layers of the current theme, followed by
+/// each stylesheet in the order they were added. Whenever these
+/// change, variables and styles are rebuilt from scratch.
 #[derive(Debug, Default)]
-pub(crate) struct StyleEngine {
+pub struct StyleEngine {
+    themes: AHashMap<String, StyleSheet>,
+    /// Names of the selected themes, bottom layer first.
+    theme_layers: Vec<String>,
+    /// Stylesheets applied on top of the theme. Only
+    /// named sheets can be replaced or removed.
+    sheets: Vec<(Option<String>, StyleSheet)>,
+    /// Style types to check styles against, by base class.
+    registrations: IndexMap<String, Registration>,
+    /// Style types that widgets of every base class read.
+    base_styles: Vec<Registration>,
+    resolver: Resolver,
+    /// Variables set with `set_variable`, which take
+    /// precedence over those defined by sheets.
+    runtime_variables: HashMap<String, Value>,
+
     variables: Variables,
     styles: Styles,
     cache: Cache,
@@ -33,159 +144,617 @@ impl StyleEngine {
     /// Existing variables are overriden; existing styles
     /// are augmented.
     pub fn append_sheet(&mut self, sheet_bytes: &[u8]) -> Result<(), StyleError> {
-        let sheet: StyleSheet = serde_yaml::from_slice(sheet_bytes)?;
-        self.variables.append(&sheet);
-        self.styles.append(&sheet, &self.variables)?;
+        let sheet = self.resolver.parse(sheet_bytes)?;
+        self.sheets.push((None, sheet));
+        self.rebuild_or_else(|engine| {
+            engine.sheets.pop();
+        })
+    }
+
+    /// Adds a named stylesheet, or replaces the
+    /// sheet with the same name in place.
+    pub fn add_named_sheet(&mut self, name: &str, sheet_bytes: &[u8]) -> Result<(), StyleError> {
+        let sheet = self.resolver.parse(sheet_bytes)?;
+        self.insert_named_sheet(name, sheet)
+    }
+
+    /// Loads a stylesheet through the resolver and appends it, named
+    /// by its path. Loading the same path again reloads the sheet in place.
+    pub fn load_sheet(&mut self, path: &str) -> Result<(), StyleError> {
+        let sheet = self.resolver.load(path)?;
+        self.insert_named_sheet(path, sheet)
+    }
+
+    fn insert_named_sheet(&mut self, name: &str, sheet: StyleSheet) -> Result<(), StyleError> {
+        match self.sheet_index(name) {
+            Some(index) => {
+                let previous = std::mem::replace(&mut self.sheets[index].1, sheet);
+                self.rebuild_or_else(|engine| engine.sheets[index].1 = previous)
+            }
+            None => {
+                self.sheets.push((Some(name.to_owned()), sheet));
+                self.rebuild_or_else(|engine| {
+                    engine.sheets.pop();
+                })
+            }
+        }
+    }
+
+    /// Removes a named stylesheet.
+    pub fn remove_sheet(&mut self, name: &str) -> Result<(), StyleError> {
+        let index = self
+            .sheet_index(name)
+            .ok_or_else(|| StyleError::MissingStylesheet(name.to_owned()))?;
+        let removed = self.sheets.remove(index);
+        self.rebuild_or_else(|engine| engine.sheets.insert(index, removed))
+    }
+
+    /// Adds a theme, which takes effect once selected
+    /// with `set_theme`. Replaces any theme with the same name.
+    pub fn add_theme(&mut self, name: &str, sheet_bytes: &[u8]) -> Result<(), StyleError> {
+        let sheet = self.resolver.parse(sheet_bytes)?;
+        self.insert_theme(name, sheet)
+    }
+
+    /// Loads a theme through the resolver. See `add_theme`.
+    pub fn load_theme(&mut self, name: &str, path: &str) -> Result<(), StyleError> {
+        let sheet = self.resolver.load(path)?;
+        self.insert_theme(name, sheet)
+    }
+
+    fn insert_theme(&mut self, name: &str, sheet: StyleSheet) -> Result<(), StyleError> {
+        let previous = self.themes.insert(name.to_owned(), sheet);
+        if !self.theme_layers.iter().any(|layer| layer == name) {
+            return Ok(());
+        }
+        let name = name.to_owned();
+        self.rebuild_or_else(|engine| match previous {
+            Some(previous) => {
+                engine.themes.insert(name, previous);
+            }
+            None => {
+                engine.themes.remove(&name);
+            }
+        })
+    }
+
+    /// Selects the theme that styles are based on.
+    pub fn set_theme(&mut self, name: &str) -> Result<(), StyleError> {
+        self.set_theme_layers(&[name])
+    }
+
+    /// Selects several themes that styles are based on, applied
+    /// in order, like a base theme followed by one that adjusts it.
+    pub fn set_theme_layers(&mut self, names: &[&str]) -> Result<(), StyleError> {
+        if let Some(missing) = names.iter().find(|name| !self.themes.contains_key(**name)) {
+            return Err(StyleError::MissingTheme((*missing).to_owned()));
+        }
+        let layers = names.iter().map(|&name| name.to_owned()).collect();
+        let previous = std::mem::replace(&mut self.theme_layers, layers);
+        self.rebuild_or_else(|engine| engine.theme_layers = previous)
+    }
+
+    /// Returns the topmost selected theme.
+    pub fn theme(&self) -> Option<&str> {
+        self.theme_layers.last().map(String::as_str)
+    }
+
+    /// Returns the selected themes, bottom layer first.
+    pub fn theme_layers(&self) -> &[String] {
+        &self.theme_layers
+    }
+
+    /// Sets the resolver used to load sheets by path, including imports.
+    /// Defaults to a [`FileResolver`] rooted at the working directory.
+    ///
+    /// Sheets that were already added keep the imports they were loaded with.
+    pub fn set_resolver(&mut self, resolver: impl StyleResolver) {
+        self.resolver.set(resolver);
+    }
+
+    fn sheet_index(&self, name: &str) -> Option<usize> {
+        self.sheets
+            .iter()
+            .position(|(sheet_name, _)| sheet_name.as_deref() == Some(name))
+    }
+
+    /// Rebuilds variables and styles from the current theme and
+    /// stylesheets, checks them against registered style types,
+    /// then recomputes the cache.
+    ///
+    /// On failure, calls `undo` to revert the change that
+    /// was just made, and the old styles stay in effect.
+    fn rebuild_or_else(&mut self, undo: impl FnOnce(&mut Self)) -> Result<(), StyleError> {
+        let mut variables = Variables {
+            runtime: self.runtime_variables.clone(),
+            ..Default::default()
+        };
+        let mut styles = Styles::default();
+        let themes = self
+            .theme_layers
+            .iter()
+            .filter_map(|name| self.themes.get(name));
+        let mut layers = Vec::new();
+        for sheet in themes.chain(self.sheets.iter().map(|(_, sheet)| sheet)) {
+            sheet.flatten_imports(&mut layers);
+        }
+        let result = layers.into_iter().try_for_each(|sheet| {
+            variables.append(sheet);
+            styles.append(sheet, &variables)
+        });
+
+        let result = result.and_then(|()| {
+            styles.build_index();
+            validate_all(&self.registrations, &self.base_styles, &styles)
+        });
+
+        match result {
+            Ok(()) => {
+                // Cached ancestor chains only keep the classes that
+                // queries used to depend on, so they cannot be reused
+                // once queries depend on other classes.
+                let ancestors_changed = styles.ancestor_classes != self.styles.ancestor_classes
+                    || styles.ancestor_matches_empty != self.styles.ancestor_matches_empty;
+                self.variables = variables;
+                self.styles = styles;
+                if ancestors_changed {
+                    self.cache = Cache::default();
+                }
+                self.recompute_cache();
+                Ok(())
+            }
+            Err(e) => {
+                undo(self);
+                Err(e)
+            }
+        }
+    }
+
+    /// Sets a variable, overriding its definitions in every theme
+    /// and stylesheet, then re-evaluates the styles.
+    ///
+    /// The name may start with `$`. If the new value breaks
+    /// a style, the previous value stays in effect.
+    pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), StyleError> {
+        let name = variable_name(name).to_owned();
+        let previous = self.runtime_variables.insert(name.clone(), value);
+        self.rebuild_or_else(|engine| match previous {
+            Some(previous) => {
+                engine.runtime_variables.insert(name, previous);
+            }
+            None => {
+                engine.runtime_variables.remove(&name);
+            }
+        })
+    }
+
+    /// Removes a variable set with `set_variable`, so
+    /// that its definitions in sheets apply again.
+    pub fn remove_variable(&mut self, name: &str) -> Result<(), StyleError> {
+        let name = variable_name(name).to_owned();
+        match self.runtime_variables.remove(&name) {
+            Some(previous) => self.rebuild_or_else(|engine| {
+                engine.runtime_variables.insert(name, previous);
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the current value of a variable.
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables.get(variable_name(name))
+    }
+
+    /// Registers the style type of widgets with the given base class.
+    /// Whenever styles change, they are checked against every
+    /// registered type, and changes that break one are rejected.
+    ///
+    /// `state_classes` lists classes, like `hovered`, that such widgets
+    /// may have in addition to their base class. Every combination of
+    /// them is checked, so keep the list short. Rules involving ancestors
+    /// or other classes are not checked.
+    ///
+    /// The styles for these class sets are computed ahead of time,
+    /// and again whenever the styles change.
+    pub fn register_style<S: DeserializeOwned + 'static>(
+        &mut self,
+        base_class: &str,
+        state_classes: &[&str],
+    ) -> Result<(), StyleError> {
+        let registration = Registration::new::<S>(state_classes);
+        let mut errors = validation::validate(&self.styles, base_class, &registration);
+        for base_style in &self.base_styles {
+            errors.extend(validation::validate_as(
+                &self.styles,
+                base_class,
+                &registration,
+                base_style,
+            ));
+        }
+        check_errors(errors)?;
+
+        self.precompute(base_class, &registration, &registration);
+        let base_styles = std::mem::take(&mut self.base_styles);
+        for base_style in &base_styles {
+            self.precompute(base_class, &registration, base_style);
+        }
+        self.base_styles = base_styles;
+        self.registrations
+            .insert(base_class.to_owned(), registration);
+        Ok(())
+    }
+
+    /// Registers a style type that widgets of every base class read
+    /// from their rules, besides their own style type. It is checked
+    /// and computed ahead of time for the class sets of each base
+    /// class registered with [`StyleEngine::register_style`].
+    pub(crate) fn register_base_style<S: DeserializeOwned + 'static>(
+        &mut self,
+    ) -> Result<(), StyleError> {
+        let base_style = Registration::new::<S>(&[]);
+        check_errors(
+            self.registrations
+                .iter()
+                .flat_map(|(base_class, registration)| {
+                    validation::validate_as(&self.styles, base_class, registration, &base_style)
+                })
+                .collect(),
+        )?;
+
+        let registrations = std::mem::take(&mut self.registrations);
+        for (base_class, registration) in &registrations {
+            self.precompute(base_class, registration, &base_style);
+        }
+        self.registrations = registrations;
+        self.base_styles.push(base_style);
         Ok(())
     }
 
-    /// Gets the style for the given set of style classes.
+    /// Checks a style override against the style type registered for
+    /// `base_class`, if any, and against the base styles.
+    pub(crate) fn check_override(
+        &self,
+        base_class: &str,
+        style_override: &Value,
+    ) -> Result<(), StyleError> {
+        check_errors(
+            self.registrations
+                .get(base_class)
+                .into_iter()
+                .chain(&self.base_styles)
+                .filter_map(|registration| {
+                    validation::check_override(registration.check, base_class, style_override)
+                })
+                .collect(),
+        )
+    }
+
+    /// Checks the current styles against every registered style type.
+    pub fn validate(&self) -> Result<(), StyleError> {
+        validate_all(&self.registrations, &self.base_styles, &self.styles)
+    }
+
+    /// Gets the style for an element with the given set of style classes.
+    ///
+    /// `ancestors` holds the classes of each of the element's ancestors,
+    /// root first, as filtered by [`StyleEngine::ancestor_classes`].
     pub fn get_style<S: DeserializeOwned + 'static>(
         &mut self,
+        ancestors: &[Vec<String>],
         classes: &[String],
     ) -> Result<Rc<S>, StyleError> {
-        if self.cache.cached_styles.contains_key(classes) {
-            return Ok(Rc::downcast(self.cache.get(classes).unwrap()).unwrap());
+        let chain = self.cached_ancestors(ancestors);
+        let ancestors = &*chain;
+        let type_id = TypeId::of::<S>();
+        if let Some(style) = self.cache.get(ancestors, classes, type_id) {
+            return Ok(Rc::downcast(style).unwrap());
         }
 
-        self.cache
-            .insert(classes.to_vec(), Rc::new(self.create_style::<S>(classes)?));
-        Ok(Rc::downcast(self.cache.get(classes).unwrap()).unwrap())
+        let merged = self.merged(ancestors, classes);
+        let style = deserialize_style::<S>(merged)?;
+        self.cache.insert(
+            ancestors,
+            classes,
+            type_id,
+            CachedStyle {
+                style: Rc::clone(&style),
+                deserialize: deserialize_style::<S>,
+            },
+        );
+        Ok(Rc::downcast(style).unwrap())
     }
 
-    fn create_style<S: DeserializeOwned>(&self, classes: &[String]) -> Result<S, StyleError> {
-        let mut value = Value::Null;
-        for style in self.styles.matching_styles(classes) {
-            merge_values(&mut value, style.value.clone());
+    /// Gets the style for an element like [`StyleEngine::get_style`],
+    /// with the properties in `style_override` merged over those
+    /// of every matching rule.
+    ///
+    /// Expressions in the override are evaluated
+    /// like those in stylesheets.
+    pub fn get_style_with_override<S: DeserializeOwned + 'static>(
+        &mut self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+        style_override: Option<&Value>,
+    ) -> Result<Rc<S>, StyleError> {
+        let style_override = match style_override {
+            Some(style_override) => style_override,
+            None => return self.get_style(ancestors, classes),
+        };
+
+        let chain = self.cached_ancestors(ancestors);
+        let ancestors = &*chain;
+        let type_id = TypeId::of::<S>();
+        if let Some(style) = self
+            .cache
+            .get_overridden(ancestors, classes, style_override, type_id)
+        {
+            return Ok(Rc::downcast(style).unwrap());
         }
-        let x: S = serde_yaml::from_value(value)?;
-        Ok(x)
-    }
-}
 
-/// Stores variables accessible to all stylesheets.
-#[derive(Debug, Default)]
-struct Variables {
-    variables: HashMap<String, Value>,
-}
+        let merged = self.merged(ancestors, classes);
+        let merged = self.apply_override(merged, style_override)?;
+        let style = deserialize_style::<S>(merged)?;
+        self.cache.insert_overridden(
+            ancestors,
+            classes,
+            style_override,
+            type_id,
+            CachedStyle {
+                style: Rc::clone(&style),
+                deserialize: deserialize_style::<S>,
+            },
+        );
+        Ok(Rc::downcast(style).unwrap())
+    }
 
-impl Variables {
-    pub fn get(&self, var: &str) -> Result<&Value, StyleError> {
-        self.variables
-            .get(var)
-            .ok_or_else(|| StyleError::MissingVariable(var.to_owned()))
+    /// Keeps only the part of the chain that can affect styles,
+    /// so that it does not split the cache needlessly.
+    fn cached_ancestors<'a>(&self, ancestors: &'a [Vec<String>]) -> Cow<'a, [Vec<String>]> {
+        self.styles.normalize_ancestors(ancestors)
     }
 
-    pub fn append(&mut self, sheet: &StyleSheet) {
-        for (name, value) in &sheet.variables {
-            self.variables.insert(name.clone(), value.clone());
+    /// Gets the merged value of the styles matching
+    /// an element, from the cache if possible.
+    fn merged(&mut self, ancestors: &[Vec<String>], classes: &[String]) -> Value {
+        match self.cache.merged(ancestors, classes) {
+            Some(merged) => merged.clone(),
+            None => {
+                let merged = self.merge_styles(ancestors, classes);
+                self.cache
+                    .insert_merged(ancestors, classes.to_vec(), merged.clone());
+                merged
+            }
         }
     }
-}
 
-#[derive(Debug, thiserror::Error)]
-enum ClassQueryParseError {
-    #[error("no query specified (empty / blank string)")]
-    Empty,
-}
+    fn apply_override(
+        &self,
+        mut merged: Value,
+        style_override: &Value,
+    ) -> Result<Value, StyleError> {
+        let mut style_override = style_override.clone();
+        expr::evaluate(&mut style_override, &self.variables).map_err(|error| {
+            StyleError::Expression {
+                rule: OVERRIDE_RULE.to_owned(),
+                error,
+                snippet: None,
+            }
+        })?;
+        merge_values(&mut merged, style_override);
+        Ok(merged)
+    }
 
-/// A class query specifies to which elements a style applies.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct ClassQuery {
-    predicate: QueryPredicate,
-}
+    /// Gets the classes of an element that matter
+    /// when it is an ancestor of another element.
+    pub fn ancestor_classes(&self, classes: &[String]) -> Vec<String> {
+        self.styles.ancestor_classes(classes)
+    }
 
-impl FromStr for ClassQuery {
-    type Err = ClassQueryParseError;
-
-    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        let mut predicate = None;
-        while !s.trim().is_empty() {
-            s = s.trim();
-            if let Some(and) = s.strip_prefix('&') {
-                let and = and.trim();
-                let end = and
-                    .char_indices()
-                    .find_map(|(p, c)| (!is_valid_class_char(c)).then(|| p))
-                    .unwrap_or_else(|| and.len());
-                let p = QueryPredicate::from_str(&and[..end])?;
-                if let Some(pred) = predicate {
-                    predicate = Some(QueryPredicate::And(Box::new(p), Box::new(pred)));
-                } else {
-                    predicate = Some(p);
-                }
-                s = &and[end..];
-            } else if let Some(or) = s.strip_prefix('|') {
-                let or = or.trim();
-                let end = or
-                    .char_indices()
-                    .find_map(|(p, c)| (!is_valid_class_char(c)).then(|| p))
-                    .unwrap_or_else(|| or.len());
-                let p = QueryPredicate::from_str(&or[..end])?;
-                if let Some(pred) = predicate {
-                    predicate = Some(QueryPredicate::Or(Box::new(p), Box::new(pred)));
-                } else {
-                    predicate = Some(p);
+    /// Computes a style without using the cache.
+    #[doc(hidden)]
+    pub fn create_style<S: DeserializeOwned>(
+        &self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+    ) -> Result<S, StyleError> {
+        Ok(serde_yaml::from_value(
+            self.merge_styles(ancestors, classes),
+        )?)
+    }
+
+    /// Computes a style the way the engine did before styles were
+    /// indexed: by testing every style's query and merging the
+    /// matches in source order, ignoring priority, specificity and
+    /// `!important`. Used as the baseline in benchmarks.
+    #[cfg(feature = "bench")]
+    pub fn create_style_baseline<S: DeserializeOwned>(
+        &self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+    ) -> Result<S, StyleError> {
+        let mut value = Value::Null;
+        for style in self.styles.matching_styles_unindexed(ancestors, classes) {
+            merge_values(&mut value, style.value.clone());
+        }
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    fn merge_styles(&self, ancestors: &[Vec<String>], classes: &[String]) -> Value {
+        merge_matching(self.styles.matching_styles(ancestors, classes))
+    }
+
+    /// Recomputes every cached style after the styles changed,
+    /// so that widgets do not have to on their next lookup,
+    /// then fills in the styles of the registered class sets.
+    ///
+    /// Styles that no longer deserialize are dropped; the
+    /// error surfaces when they are next requested.
+    fn recompute_cache(&mut self) {
+        let chains = std::mem::take(&mut self.cache.chains);
+        for (ancestors, class_sets) in chains {
+            for (classes, entry) in class_sets {
+                let merged = self.merge_styles(&ancestors, &classes);
+                for (style_override, styles) in entry.overridden {
+                    let merged = match self.apply_override(merged.clone(), &style_override) {
+                        Ok(merged) => merged,
+                        Err(_) => continue,
+                    };
+                    for (type_id, cached) in styles {
+                        if let Ok(style) = (cached.deserialize)(merged.clone()) {
+                            self.cache.insert_overridden(
+                                &ancestors,
+                                &classes,
+                                &style_override,
+                                type_id,
+                                CachedStyle {
+                                    style,
+                                    deserialize: cached.deserialize,
+                                },
+                            );
+                        }
+                    }
                 }
-                s = &or[end..];
-            } else {
-                let end = s
-                    .char_indices()
-                    .find_map(|(p, c)| (!is_valid_class_char(c)).then(|| p))
-                    .unwrap_or_else(|| s.len());
-                let p = QueryPredicate::from_str(&s[..end])?;
-                if let Some(pred) = predicate {
-                    predicate = Some(QueryPredicate::And(Box::new(p), Box::new(pred)));
-                } else {
-                    predicate = Some(p);
+                for (type_id, cached) in entry.styles {
+                    if let Ok(style) = (cached.deserialize)(merged.clone()) {
+                        self.cache.insert(
+                            &ancestors,
+                            &classes,
+                            type_id,
+                            CachedStyle {
+                                style,
+                                deserialize: cached.deserialize,
+                            },
+                        );
+                    }
                 }
-                s = &s[end..];
+                self.cache.insert_merged(&ancestors, classes, merged);
             }
         }
 
-        Ok(Self {
-            predicate: predicate.ok_or(ClassQueryParseError::Empty)?,
-        })
+        let registrations = std::mem::take(&mut self.registrations);
+        let base_styles = std::mem::take(&mut self.base_styles);
+        for (base_class, registration) in &registrations {
+            self.precompute(base_class, registration, registration);
+            for base_style in &base_styles {
+                self.precompute(base_class, registration, base_style);
+            }
+        }
+        self.registrations = registrations;
+        self.base_styles = base_styles;
     }
-}
 
-impl<'de> Deserialize<'de> for ClassQuery {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let s: String = String::deserialize(deserializer)?;
-        Self::from_str(&s).map_err(|e| serde::de::Error::custom(e.to_string()))
+    /// Caches the `style_type` of each class set of a registered
+    /// base class that is not cached yet. Class sets that no
+    /// style applies to are skipped, like in validation.
+    fn precompute(
+        &mut self,
+        base_class: &str,
+        registration: &Registration,
+        style_type: &Registration,
+    ) {
+        for mut classes in registration.class_sets(base_class) {
+            // Widgets keep their classes sorted.
+            classes.sort();
+            if self.cache.get(&[], &classes, style_type.type_id).is_some() {
+                continue;
+            }
+            let merged = match self.cache.merged(&[], &classes) {
+                Some(merged) => merged.clone(),
+                None => self.merge_styles(&[], &classes),
+            };
+            if merged.is_null() {
+                continue;
+            }
+            self.cache
+                .insert_merged(&[], classes.clone(), merged.clone());
+            if let Ok(style) = (style_type.deserialize)(merged) {
+                self.cache.insert(
+                    &[],
+                    &classes,
+                    style_type.type_id,
+                    CachedStyle {
+                        style,
+                        deserialize: style_type.deserialize,
+                    },
+                );
+            }
+        }
     }
 }
 
-fn is_valid_class_char(c: char) -> bool {
-    c.is_alphanumeric() || c == '-' || c == '_' || c == '(' || c == ')'
+/// Checks a style override against a style type.
+pub(crate) fn check_override<S: DeserializeOwned>(
+    base_class: &str,
+    style_override: &Value,
+) -> Result<(), StyleError> {
+    check_errors(
+        validation::check_override(validation::check::<S>, base_class, style_override)
+            .into_iter()
+            .collect(),
+    )
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum QueryPredicate {
-    And(Box<QueryPredicate>, Box<QueryPredicate>),
-    Or(Box<QueryPredicate>, Box<QueryPredicate>),
+fn validate_all(
+    registrations: &IndexMap<String, Registration>,
+    base_styles: &[Registration],
+    styles: &Styles,
+) -> Result<(), StyleError> {
+    check_errors(
+        registrations
+            .iter()
+            .flat_map(|(base_class, registration)| {
+                let mut errors = validation::validate(styles, base_class, registration);
+                for base_style in base_styles {
+                    errors.extend(validation::validate_as(
+                        styles,
+                        base_class,
+                        registration,
+                        base_style,
+                    ));
+                }
+                errors
+            })
+            .collect(),
+    )
+}
 
-    /// The element must have a certain class.
-    Class(String),
+fn check_errors(errors: Vec<StyleValidationError>) -> Result<(), StyleError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(StyleError::Invalid(errors))
+    }
 }
 
-impl FromStr for QueryPredicate {
-    type Err = ClassQueryParseError;
+type DeserializeFn = fn(Value) -> Result<Rc<dyn Any>, StyleError>;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(QueryPredicate::Class(s.trim().to_owned()))
-    }
+fn deserialize_style<S: DeserializeOwned + 'static>(
+    value: Value,
+) -> Result<Rc<dyn Any>, StyleError> {
+    let style: S = serde_yaml::from_value(value)?;
+    Ok(Rc::new(style))
+}
+
+fn variable_name(name: &str) -> &str {
+    name.strip_prefix(VARIABLE_PREFIX).unwrap_or(name)
+}
+
+/// Stores variables accessible to all stylesheets.
+#[derive(Debug, Default)]
+struct Variables {
+    variables: HashMap<String, Value>,
+    /// Variables set at runtime, which take precedence.
+    runtime: HashMap<String, Value>,
 }
 
-impl QueryPredicate {
-    fn matches(&self, classes: &[String]) -> bool {
-        match self {
-            QueryPredicate::And(a, b) => a.matches(classes) && b.matches(classes),
-            QueryPredicate::Or(a, b) => a.matches(classes) || b.matches(classes),
-            QueryPredicate::Class(c) => classes.iter().any(|class| class == c),
+impl Variables {
+    pub fn get(&self, var: &str) -> Option<&Value> {
+        self.runtime.get(var).or_else(|| self.variables.get(var))
+    }
+
+    pub fn append(&mut self, sheet: &StyleSheet) {
+        for (name, value) in &sheet.variables {
+            self.variables.insert(name.clone(), value.clone());
         }
     }
 }
@@ -193,27 +762,121 @@ impl QueryPredicate {
 /// A single style sheet.
 #[derive(Debug, Deserialize)]
 struct StyleSheet {
+    /// Paths of the sheets this one builds on.
+    #[serde(default)]
+    import: Vec<String>,
+    /// The imported sheets, loaded by the resolver.
+    #[serde(skip)]
+    imported: Vec<StyleSheet>,
     #[serde(default)]
     variables: HashMap<String, Value>,
     #[serde(default)]
     styles: IndexMap<ClassQuery, Value>,
+    /// The text the sheet was parsed from.
+    #[serde(skip)]
+    source: Option<Rc<SheetSource>>,
+}
+
+impl StyleSheet {
+    /// Points an expression error from the rule
+    /// at `index` at the offending expression.
+    fn locate_error(&self, index: usize, error: StyleError) -> StyleError {
+        match (error, &self.source) {
+            (StyleError::Expression { rule, error, .. }, Some(source)) => StyleError::Expression {
+                snippet: source
+                    .snippet_in_rule(index, &error.expression)
+                    .map(Box::new),
+                rule,
+                error,
+            },
+            (error, _) => error,
+        }
+    }
+
+    /// Collects this sheet, preceded by its imports, in the order they apply.
+    fn flatten_imports<'a>(&'a self, layers: &mut Vec<&'a StyleSheet>) {
+        for imported in &self.imported {
+            imported.flatten_imports(layers);
+        }
+        layers.push(self);
+    }
+}
+
+/// The text of a sheet, kept to point errors at its rules.
+#[derive(Debug)]
+struct SheetSource {
+    path: Option<String>,
+    text: String,
+}
+
+impl SheetSource {
+    /// Indexes the text. Only done when there is an error to locate.
+    fn index(&self) -> SourceFile {
+        match &self.path {
+            Some(path) => SourceFile::with_name(path.as_str(), self.text.as_str()),
+            None => SourceFile::new(self.text.as_str()),
+        }
+    }
+
+    /// Points at `text` within the `rule`th rule, or at the rule itself.
+    fn snippet_in_rule(&self, rule: usize, text: &str) -> Option<Snippet> {
+        let source = self.index();
+        let key = source.keys(&["styles"]).nth(rule)?;
+        let location = source.locate_in(&["styles", key], text)?;
+        Some(source.snippet(location))
+    }
+
+    /// Points at `field` of the `rule`th rule, or at the
+    /// nearest enclosing field the rule sets.
+    fn snippet_at_field(&self, rule: usize, field: &[&str]) -> Option<Snippet> {
+        let source = self.index();
+        let key = source.keys(&["styles"]).nth(rule)?;
+        let mut path = vec!["styles", key];
+        path.extend(field);
+        let location = (2..=path.len())
+            .rev()
+            .find_map(|len| source.locate(&path[..len]))?;
+        Some(source.snippet(location))
+    }
 }
 
 /// Stores styles based on class queries.
 #[derive(Debug, Default)]
 struct Styles {
-    styles: Vec<(ClassQuery, Style)>,
+    styles: Vec<Style>,
+    /// Maps each class to the indices of styles whose
+    /// queries may match an element with that class.
+    index: AHashMap<String, Vec<usize>>,
+    /// Indices of styles whose queries may match elements
+    /// without any of the classes they mention, like `!disabled`.
+    unindexed: Vec<usize>,
+    /// Classes that queries require of ancestors.
+    ancestor_classes: AHashSet<String>,
+    /// Whether a query requires of an ancestor something
+    /// an ancestor without classes has, like `!toolbar`.
+    ancestor_matches_empty: bool,
 }
 
 impl Styles {
-    /// Gets all styles matching the given classes.
+    /// Gets all styles matching an element, in the order they were added.
     pub fn matching_styles<'a>(
         &'a self,
+        ancestors: &'a [Vec<String>],
         classes: &'a [String],
     ) -> impl Iterator<Item = &'a Style> + 'a {
-        // TODO: avoid linear search
-        self.styles.iter().filter_map(move |(query, style)| {
-            if query.predicate.matches(classes) {
+        let mut candidates: Vec<usize> = classes
+            .iter()
+            .filter_map(|class| self.index.get(class))
+            .flatten()
+            .chain(&self.unindexed)
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        candidates.into_iter().filter_map(move |i| {
+            let style = &self.styles[i];
+            if style.query.matches(ancestors, classes) {
                 Some(style)
             } else {
                 None
@@ -221,80 +884,390 @@ impl Styles {
         })
     }
 
+    /// Like `matching_styles`, but tests every style.
+    #[cfg(any(test, feature = "bench"))]
+    pub fn matching_styles_unindexed<'a>(
+        &'a self,
+        ancestors: &'a [Vec<String>],
+        classes: &'a [String],
+    ) -> impl Iterator<Item = &'a Style> + 'a {
+        self.styles
+            .iter()
+            .filter(move |style| style.query.matches(ancestors, classes))
+    }
+
     /// Appends a style sheet.
     ///
     /// Variables need to be appended first.
     pub fn append(&mut self, sheet: &StyleSheet, variables: &Variables) -> Result<(), StyleError> {
-        for (query, style_value) in &sheet.styles {
-            let style = Style::new(style_value.clone(), variables)?;
-            self.styles.push((query.clone(), style));
+        for (i, (query, style_value)) in sheet.styles.iter().enumerate() {
+            let mut style = Style::new(style_value.clone(), query.clone(), variables)
+                .map_err(|error| sheet.locate_error(i, error))?;
+            style.origin = sheet.source.clone().map(|source| (source, i));
+            self.styles.push(style);
         }
         Ok(())
     }
+
+    /// Indexes the styles by class. Must be called after
+    /// appending sheets, before matching styles.
+    pub fn build_index(&mut self) {
+        let mut frequency = AHashMap::new();
+        for style in &self.styles {
+            style
+                .query
+                .predicate
+                .for_each_class(&mut |class| *frequency.entry(class).or_insert(0) += 1);
+        }
+
+        let mut index: AHashMap<String, Vec<usize>> = AHashMap::new();
+        let mut unindexed = Vec::new();
+        let mut ancestor_classes = AHashSet::new();
+        let mut ancestor_matches_empty = false;
+        for (i, style) in self.styles.iter().enumerate() {
+            let query = &style.query;
+            match query.predicate.trigger_classes(&frequency) {
+                Some(classes) => {
+                    for class in classes {
+                        index.entry(class.to_owned()).or_default().push(i);
+                    }
+                }
+                None => unindexed.push(i),
+            }
+            for ancestor in &query.ancestors {
+                ancestor.predicate.for_each_class(&mut |class| {
+                    ancestor_classes.insert(class.to_owned());
+                });
+                ancestor_matches_empty |= ancestor.predicate.matches(&[]);
+            }
+        }
+        self.index = index;
+        self.unindexed = unindexed;
+        self.ancestor_classes = ancestor_classes;
+        self.ancestor_matches_empty = ancestor_matches_empty;
+    }
+
+    /// Drops the parts of an ancestor chain, as filtered by
+    /// `ancestor_classes`, that cannot change which queries match,
+    /// so that elements at different depths can share cache entries.
+    ///
+    /// If no query requires anything of an ancestor without classes,
+    /// such ancestors only matter as a break between an ancestor and
+    /// the element or ancestor a `>` combinator relates it to. Those
+    /// above the topmost ancestor with classes are dropped, and each
+    /// run of them elsewhere is collapsed into one.
+    pub fn normalize_ancestors<'a>(&self, ancestors: &'a [Vec<String>]) -> Cow<'a, [Vec<String>]> {
+        if self.ancestor_classes.is_empty() {
+            return Cow::Borrowed(&[]);
+        }
+        if self.ancestor_matches_empty {
+            return Cow::Borrowed(ancestors);
+        }
+
+        let ancestors = match ancestors.iter().position(|classes| !classes.is_empty()) {
+            Some(top) => &ancestors[top..],
+            None => return Cow::Borrowed(&[]),
+        };
+        let has_run = ancestors
+            .windows(2)
+            .any(|pair| pair[0].is_empty() && pair[1].is_empty());
+        if !has_run {
+            return Cow::Borrowed(ancestors);
+        }
+
+        let mut normalized: Vec<Vec<String>> = Vec::with_capacity(ancestors.len());
+        for classes in ancestors {
+            if classes.is_empty() && normalized.last().map_or(false, Vec::is_empty) {
+                continue;
+            }
+            normalized.push(classes.clone());
+        }
+        Cow::Owned(normalized)
+    }
+
+    /// Gets the classes of an ancestor that queries may depend on.
+    pub fn ancestor_classes(&self, classes: &[String]) -> Vec<String> {
+        if self.ancestor_classes.is_empty() {
+            return Vec::new();
+        }
+        classes
+            .iter()
+            .filter(|class| self.ancestor_classes.contains(*class))
+            .cloned()
+            .collect()
+    }
 }
 
 /// A style, stored as a YAML value.
 ///
-/// Variables have been applied.
+/// Expressions have been evaluated.
 #[derive(Debug)]
 struct Style {
+    query: ClassQuery,
     value: Value,
+    /// Properties marked `!important`.
+    important: Option<Value>,
+    priority: i64,
+    specificity: u32,
+    /// The sheet the style came from and the index of its rule.
+    origin: Option<(Rc<SheetSource>, usize)>,
 }
 
 impl Style {
-    pub fn new(mut value: Value, variables: &Variables) -> Result<Self, StyleError> {
-        apply_variables(&mut value, variables)?;
-        Ok(Self { value })
+    pub fn new(
+        mut value: Value,
+        query: ClassQuery,
+        variables: &Variables,
+    ) -> Result<Self, StyleError> {
+        let priority = match &mut value {
+            Value::Mapping(mapping) => match mapping.remove(&Value::from(PRIORITY_KEY)) {
+                Some(priority) => priority.as_i64().ok_or(StyleError::InvalidPriority)?,
+                None => 0,
+            },
+            _ => 0,
+        };
+        let mut important = split_important(&mut value);
+
+        let evaluated = expr::evaluate(&mut value, variables).and_then(|()| match &mut important {
+            Some(important) => expr::evaluate(important, variables),
+            None => Ok(()),
+        });
+        if let Err(error) = evaluated {
+            return Err(StyleError::Expression {
+                rule: query.to_string(),
+                error,
+                snippet: None,
+            });
+        }
+        Ok(Self {
+            specificity: query.specificity(),
+            query,
+            value,
+            important,
+            priority,
+            origin: None,
+        })
+    }
+
+    /// Points at `field` in the sheet the style came from.
+    fn snippet(&self, field: &[&str]) -> Option<Snippet> {
+        let (source, rule) = self.origin.as_ref()?;
+        source.snippet_at_field(*rule, field)
     }
 }
 
-fn apply_variables(value: &mut Value, variables: &Variables) -> Result<(), StyleError> {
-    // Recursively traverse the tree, looking for string values starting
-    // with the variable prefix character '$.'
-    match value {
-        Value::String(s) => {
-            if let Some(var) = s.strip_prefix(VARIABLE_PREFIX) {
-                *value = variables.get(var)?.clone();
-            }
-        }
-        Value::Mapping(mapping) => {
-            for (_, child_value) in mapping.iter_mut() {
-                apply_variables(child_value, variables)?;
-            }
+/// Moves properties marked `!important` out of a style,
+/// returning them with the marker stripped.
+fn split_important(value: &mut Value) -> Option<Value> {
+    let mapping = match value {
+        Value::Mapping(mapping) => mapping,
+        _ => return None,
+    };
+
+    let mut important = serde_yaml::Mapping::new();
+    let mut marked = Vec::new();
+    for (key, child_value) in mapping.iter_mut() {
+        let stripped = match child_value {
+            Value::String(s) => s.trim_end().strip_suffix(IMPORTANT_SUFFIX),
+            _ => None,
+        };
+        if let Some(stripped) = stripped {
+            // Re-parse the remaining scalar, so that `2 !important` is a number.
+            let stripped = stripped.trim();
+            let parsed = serde_yaml::from_str(stripped)
+                .unwrap_or_else(|_| Value::String(stripped.to_owned()));
+            important.insert(key.clone(), parsed);
+            marked.push(key.clone());
+        } else if let Some(nested) = split_important(child_value) {
+            important.insert(key.clone(), nested);
         }
-        Value::Sequence(s) => {
-            for child_value in s {
-                apply_variables(child_value, variables)?;
-            }
+    }
+
+    for key in &marked {
+        mapping.remove(key);
+    }
+
+    if important.is_empty() {
+        None
+    } else {
+        Some(Value::Mapping(important))
+    }
+}
+
+/// Merges matching styles, given in source order,
+/// according to the precedence rules.
+fn merge_matching<'a>(styles: impl Iterator<Item = &'a Style>) -> Value {
+    merge_ordered(&precedence_order(styles))
+}
+
+/// Sorts matching styles, given in source order,
+/// by the precedence rules, lowest precedence first.
+fn precedence_order<'a>(styles: impl Iterator<Item = &'a Style>) -> Vec<&'a Style> {
+    let mut styles: Vec<&Style> = styles.collect();
+    // The sort is stable, so source order breaks ties.
+    styles.sort_by_key(|style| (style.priority, style.specificity));
+    styles
+}
+
+/// Merges styles sorted by `precedence_order`.
+fn merge_ordered(styles: &[&Style]) -> Value {
+    let mut value = Value::Null;
+    for style in styles {
+        merge_values(&mut value, style.value.clone());
+    }
+    for style in styles {
+        if let Some(important) = &style.important {
+            merge_values(&mut value, important.clone());
         }
-        _ => (),
     }
-    Ok(())
+    value
+}
+
+/// A deserialized style, along with the function
+/// that created it, so it can be recomputed.
+struct CachedStyle {
+    style: Rc<dyn Any>,
+    deserialize: DeserializeFn,
+}
+
+/// Cached styles for one set of style classes.
+#[derive(Default)]
+struct ClassSetEntry {
+    /// The merged value of all matching styles.
+    merged: Option<Value>,
+    /// Deserialized styles, by style type.
+    styles: AHashMap<TypeId, CachedStyle>,
+    /// Deserialized styles with an override applied,
+    /// by override and then by style type.
+    overridden: AHashMap<Value, AHashMap<TypeId, CachedStyle>>,
 }
 
-/// A cache of deserialized style values for sets of style classes.
+/// A cache of style values for sets of style classes.
+///
+/// Entries are keyed by the element's classes and
+/// by the relevant classes of its ancestors.
 #[derive(Default)]
 struct Cache {
-    cached_styles: AHashMap<Vec<String>, Rc<dyn Any>>,
+    chains: AHashMap<Vec<Vec<String>>, AHashMap<Vec<String>, ClassSetEntry>>,
 }
 
 impl std::fmt::Debug for Cache {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut s = f.debug_struct("Cache");
-        s.field("size", &self.cached_styles.len());
+        s.field(
+            "size",
+            &self
+                .chains
+                .values()
+                .map(|class_sets| class_sets.len())
+                .sum::<usize>(),
+        );
         s.finish()
     }
 }
 
 impl Cache {
-    /// Gets the cached style for a set of classes, if any.
-    pub fn get(&self, classes: &[String]) -> Option<Rc<dyn Any>> {
-        self.cached_styles.get(classes).cloned()
+    /// Gets the cached style of a given type for a set of classes, if any.
+    pub fn get(
+        &self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+        type_id: TypeId,
+    ) -> Option<Rc<dyn Any>> {
+        self.chains
+            .get(ancestors)?
+            .get(classes)?
+            .styles
+            .get(&type_id)
+            .map(|cached| Rc::clone(&cached.style))
+    }
+
+    /// Gets the cached style of a given type for a set
+    /// of classes with an override applied, if any.
+    pub fn get_overridden(
+        &self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+        style_override: &Value,
+        type_id: TypeId,
+    ) -> Option<Rc<dyn Any>> {
+        self.chains
+            .get(ancestors)?
+            .get(classes)?
+            .overridden
+            .get(style_override)?
+            .get(&type_id)
+            .map(|cached| Rc::clone(&cached.style))
+    }
+
+    /// Gets the merged style value for a set of classes, if any.
+    pub fn merged(&self, ancestors: &[Vec<String>], classes: &[String]) -> Option<&Value> {
+        self.chains.get(ancestors)?.get(classes)?.merged.as_ref()
     }
 
     /// Inserts a cached style for a class set.
-    pub fn insert(&mut self, classes: Vec<String>, style: Rc<dyn Any>) {
-        self.cached_styles.insert(classes, style);
+    pub fn insert(
+        &mut self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+        type_id: TypeId,
+        style: CachedStyle,
+    ) {
+        let class_sets = self.class_sets(ancestors);
+        if !class_sets.contains_key(classes) {
+            class_sets.insert(classes.to_vec(), ClassSetEntry::default());
+        }
+        class_sets
+            .get_mut(classes)
+            .unwrap()
+            .styles
+            .insert(type_id, style);
+    }
+
+    /// Inserts a cached style for a class set with an override applied.
+    pub fn insert_overridden(
+        &mut self,
+        ancestors: &[Vec<String>],
+        classes: &[String],
+        style_override: &Value,
+        type_id: TypeId,
+        style: CachedStyle,
+    ) {
+        let class_sets = self.class_sets(ancestors);
+        if !class_sets.contains_key(classes) {
+            class_sets.insert(classes.to_vec(), ClassSetEntry::default());
+        }
+        let overridden = &mut class_sets.get_mut(classes).unwrap().overridden;
+        if !overridden.contains_key(style_override) {
+            overridden.insert(style_override.clone(), AHashMap::new());
+        }
+        overridden
+            .get_mut(style_override)
+            .unwrap()
+            .insert(type_id, style);
+    }
+
+    /// Inserts the merged style value for a class set.
+    pub fn insert_merged(
+        &mut self,
+        ancestors: &[Vec<String>],
+        classes: Vec<String>,
+        merged: Value,
+    ) {
+        self.class_sets(ancestors)
+            .entry(classes)
+            .or_default()
+            .merged = Some(merged);
+    }
+
+    fn class_sets(
+        &mut self,
+        ancestors: &[Vec<String>],
+    ) -> &mut AHashMap<Vec<String>, ClassSetEntry> {
+        if !self.chains.contains_key(ancestors) {
+            self.chains.insert(ancestors.to_vec(), AHashMap::new());
+        }
+        self.chains.get_mut(ancestors).unwrap()
     }
 }
 
@@ -316,35 +1289,573 @@ fn merge_values(bottom: &mut Value, top: Value) {
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use super::*;
 
+    #[derive(Debug, Deserialize)]
+    struct TestStyle {
+        color: String,
+    }
+
+    fn color(engine: &mut StyleEngine, class: &str) -> String {
+        engine
+            .get_style::<TestStyle>(&[], &[class.to_owned()])
+            .unwrap()
+            .color
+            .clone()
+    }
+
+    #[test]
+    fn switch_theme() {
+        let mut engine = StyleEngine::default();
+        engine
+            .add_theme(
+                "dark",
+                b"variables: {fg: white}\nstyles: {text: {color: black}}",
+            )
+            .unwrap();
+        engine
+            .add_theme(
+                "light",
+                b"variables: {fg: black}\nstyles: {text: {color: white}}",
+            )
+            .unwrap();
+        engine
+            .append_sheet(b"styles: {button: {color: $fg}}")
+            .unwrap_err();
+
+        engine.set_theme("dark").unwrap();
+        engine
+            .append_sheet(b"styles: {button: {color: $fg}}")
+            .unwrap();
+        assert_eq!(color(&mut engine, "text"), "black");
+        assert_eq!(color(&mut engine, "button"), "white");
+
+        engine.set_theme("light").unwrap();
+        assert_eq!(engine.theme(), Some("light"));
+        assert_eq!(color(&mut engine, "text"), "white");
+        assert_eq!(color(&mut engine, "button"), "black");
+
+        assert!(matches!(
+            engine.set_theme("high_contrast"),
+            Err(StyleError::MissingTheme(_))
+        ));
+        assert_eq!(engine.theme(), Some("light"));
+    }
+
+    #[test]
+    fn named_sheets() {
+        let mut engine = StyleEngine::default();
+        engine
+            .append_sheet(b"styles: {text: {color: red}}")
+            .unwrap();
+        engine
+            .add_named_sheet("overrides", b"styles: {text: {color: green}}")
+            .unwrap();
+        assert_eq!(color(&mut engine, "text"), "green");
+
+        engine
+            .add_named_sheet("overrides", b"styles: {text: {color: blue}}")
+            .unwrap();
+        assert_eq!(color(&mut engine, "text"), "blue");
+
+        engine.remove_sheet("overrides").unwrap();
+        assert_eq!(color(&mut engine, "text"), "red");
+        assert!(matches!(
+            engine.remove_sheet("overrides"),
+            Err(StyleError::MissingStylesheet(_))
+        ));
+    }
+
+    #[test]
+    fn failed_change_keeps_styles() {
+        let mut engine = StyleEngine::default();
+        engine
+            .add_named_sheet("vars", b"variables: {c: red}")
+            .unwrap();
+        engine.append_sheet(b"styles: {text: {color: $c}}").unwrap();
+
+        assert!(matches!(
+            engine.remove_sheet("vars"),
+            Err(StyleError::Expression { rule, .. }) if rule == "text"
+        ));
+        assert_eq!(color(&mut engine, "text"), "red");
+    }
+
+    fn load_test_sheet(path: &str) -> std::io::Result<Vec<u8>> {
+        let sheet: &[u8] = match path {
+            "themes/base.yml" => {
+                b"variables: {fg: red}\nstyles: {text: {color: $fg}, button: {color: base}}"
+            }
+            "themes/game/main.yml" => b"import: [../base.yml]\nstyles: {button: {color: $fg}}",
+            "a.yml" => b"import: [nested/b.yml]",
+            "nested/b.yml" => b"import: [../a.yml]",
+            _ => return Err(std::io::ErrorKind::NotFound.into()),
+        };
+        Ok(sheet.to_vec())
+    }
+
+    #[test]
+    fn imports() {
+        let mut engine = StyleEngine::default();
+        engine.set_resolver(load_test_sheet);
+        engine.load_sheet("themes/game/main.yml").unwrap();
+        assert_eq!(color(&mut engine, "text"), "red");
+        assert_eq!(color(&mut engine, "button"), "red");
+
+        engine
+            .append_sheet(b"import: [themes/base.yml]\nstyles: {text: {color: blue}}")
+            .unwrap();
+        assert_eq!(color(&mut engine, "text"), "blue");
+        assert_eq!(color(&mut engine, "button"), "base");
+
+        assert_eq!(
+            engine.load_sheet("a.yml").unwrap_err().to_string(),
+            "in stylesheet 'a.yml': in stylesheet 'nested/b.yml': stylesheet 'a.yml' imports itself"
+        );
+        assert!(matches!(
+            engine.append_sheet(b"import: [missing.yml]"),
+            Err(StyleError::Sheet { path, .. }) if path == "missing.yml"
+        ));
+    }
+
     #[test]
-    fn simple_class_query() {
-        let query = ClassQuery::from_str("class").unwrap();
-        assert_eq!(query.predicate, QueryPredicate::Class("class".to_owned()));
+    fn theme_layers() {
+        let mut engine = StyleEngine::default();
+        engine
+            .add_theme(
+                "base",
+                b"variables: {fg: red}\nstyles: {text: {color: $fg}, button: {color: base}}",
+            )
+            .unwrap();
+        engine
+            .add_theme(
+                "game",
+                b"variables: {fg: blue}\nstyles: {button: {color: $fg}}",
+            )
+            .unwrap();
+        engine.set_theme_layers(&["base", "game"]).unwrap();
+        engine
+            .append_sheet(b"styles: {text: {color: user}}")
+            .unwrap();
+
+        assert_eq!(color(&mut engine, "text"), "user");
+        assert_eq!(color(&mut engine, "button"), "blue");
+        assert_eq!(engine.theme(), Some("game"));
+
+        assert!(matches!(
+            engine.set_theme_layers(&["base", "missing"]),
+            Err(StyleError::MissingTheme(name)) if name == "missing"
+        ));
+        assert_eq!(engine.theme_layers(), ["base", "game"]);
     }
 
     #[test]
-    fn and_class_query() {
-        let query = ClassQuery::from_str("class1 & class2").unwrap();
+    fn validation_errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct SizedStyle {
+            color: String,
+            size: f32,
+        }
+
+        let mut engine = StyleEngine::default();
+        engine
+            .register_style::<SizedStyle>("text", &["hovered"])
+            .unwrap();
+        engine
+            .append_sheet(b"styles: {text: {color: red, size: 12}, other: {size: 3}}")
+            .unwrap();
+
+        let errors = match engine.append_sheet(b"styles: {text & hovered: {size: big}}") {
+            Err(StyleError::Invalid(errors)) => errors,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].classes, ["text", "hovered"]);
+        assert_eq!(errors[0].field.as_deref(), Some("size"));
+        assert_eq!(errors[0].rule.as_deref(), Some("text & hovered"));
+        assert_eq!(color(&mut engine, "text"), "red");
+
+        let errors = match engine.register_style::<SizedStyle>("other", &[]) {
+            Err(StyleError::Invalid(errors)) => errors,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field.as_deref(), Some("color"));
+        assert_eq!(errors[0].rule, None);
+    }
+
+    #[test]
+    fn error_snippets() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct SizedStyle {
+            size: f32,
+        }
+
+        let mut engine = StyleEngine::default();
+        engine.set_resolver(|path: &str| match path {
+            "broken.yml" => Ok(b"styles:\n  text: {size: [1}\n".to_vec()),
+            "sized.yml" => {
+                Ok(b"styles:\n  text:\n    size: 1\n  text&hovered:\n    size: big\n".to_vec())
+            }
+            "exprs.yml" => Ok(b"styles:\n  text:\n    size: $missing * 2\n".to_vec()),
+            _ => Err(std::io::ErrorKind::NotFound.into()),
+        });
+        engine
+            .register_style::<SizedStyle>("text", &["hovered"])
+            .unwrap();
+
+        let snippet = match engine.load_sheet("broken.yml") {
+            Err(StyleError::Sheet { error, .. }) => match *error {
+                StyleError::Parse { snippet, .. } => snippet.unwrap(),
+                other => panic!("expected a parse error, got {:?}", other),
+            },
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+        assert_eq!(snippet.file.as_deref(), Some("broken.yml"));
+        assert_eq!(snippet.location.line, 2);
+
+        let error = engine.load_sheet("sized.yml").unwrap_err().to_string();
+        assert!(
+            error.ends_with(" --> sized.yml:5:5\n    |\n  5 |     size: big\n    |     ^"),
+            "{}",
+            error
+        );
+
+        let snippet = match engine.load_sheet("exprs.yml") {
+            Err(StyleError::Expression { snippet, .. }) => snippet.unwrap(),
+            other => panic!("expected an expression error, got {:?}", other),
+        };
         assert_eq!(
-            query.predicate,
-            QueryPredicate::And(
-                Box::new(QueryPredicate::Class("class2".to_owned())),
-                Box::new(QueryPredicate::Class("class1".to_owned())),
+            snippet.location,
+            duit_core::source::Location {
+                line: 3,
+                column: 11
+            }
+        );
+    }
+
+    #[test]
+    fn indexed_matching() {
+        let mut engine = StyleEngine::default();
+        engine
+            .append_sheet(
+                b"styles: {a: {color: a}, b & c: {color: bc}, d | b: {color: db}, e: {color: e}, '!a & !x': {color: na}}",
             )
+            .unwrap();
+
+        let classes = |classes: &[&str]| classes.iter().map(|&c| c.to_owned()).collect::<Vec<_>>();
+        for set in [
+            &["a"][..],
+            &["b"],
+            &["b", "c"],
+            &["c", "b", "a"],
+            &["d"],
+            &["x"],
+        ] {
+            let classes = classes(set);
+            let indexed: Vec<_> = engine.styles.matching_styles(&[], &classes).collect();
+            let unindexed: Vec<_> = engine
+                .styles
+                .matching_styles_unindexed(&[], &classes)
+                .collect();
+            assert_eq!(indexed.len(), unindexed.len());
+            for (a, b) in indexed.iter().zip(&unindexed) {
+                assert!(std::ptr::eq(*a, *b));
+            }
+        }
+
+        assert_eq!(
+            engine
+                .create_style::<TestStyle>(&[], &classes(&["c", "b", "x"]))
+                .unwrap()
+                .color,
+            "bc"
+        );
+        assert_eq!(
+            engine
+                .create_style::<TestStyle>(&[], &classes(&["d"]))
+                .unwrap()
+                .color,
+            "na"
+        );
+    }
+
+    #[test]
+    fn cache_is_per_style_type() {
+        #[derive(Debug, Deserialize)]
+        struct Other {
+            size: Option<f32>,
+        }
+
+        let mut engine = StyleEngine::default();
+        engine.append_sheet(b"styles: {a: {color: red}}").unwrap();
+        let classes = ["a".to_owned()];
+        assert_eq!(
+            engine.get_style::<TestStyle>(&[], &classes).unwrap().color,
+            "red"
         );
+        assert_eq!(engine.get_style::<Other>(&[], &classes).unwrap().size, None);
     }
 
     #[test]
-    fn or_class_query() {
-        let query = ClassQuery::from_str("class1 | class2").unwrap();
+    fn style_overrides() {
+        let mut engine = StyleEngine::default();
+        engine
+            .append_sheet(b"variables: {fg: white}\nstyles: {a: {color: red}}")
+            .unwrap();
+        let classes = ["a".to_owned()];
+        let mut color = |style_override: Option<&str>| {
+            let style_override = style_override.map(yaml);
+            engine
+                .get_style_with_override::<TestStyle>(&[], &classes, style_override.as_ref())
+                .unwrap()
+                .color
+                .clone()
+        };
+        assert_eq!(color(Some("{color: blue}")), "blue");
+        assert_eq!(color(Some("{color: $fg}")), "white");
+        assert_eq!(color(None), "red");
+        assert_eq!(color(Some("{color: blue}")), "blue");
+
+        engine
+            .add_named_sheet("b", b"styles: {a: {color: green}}")
+            .unwrap();
+        let blue = engine
+            .get_style_with_override::<TestStyle>(&[], &classes, Some(&yaml("{color: blue}")))
+            .unwrap();
+        assert_eq!(blue.color, "blue");
         assert_eq!(
-            query.predicate,
-            QueryPredicate::Or(
-                Box::new(QueryPredicate::Class("class2".to_owned())),
-                Box::new(QueryPredicate::Class("class1".to_owned())),
+            engine.get_style::<TestStyle>(&[], &classes).unwrap().color,
+            "green"
+        );
+    }
+
+    #[test]
+    fn runtime_variables() {
+        #[derive(Debug, Deserialize)]
+        struct SizedStyle {
+            size: f32,
+        }
+
+        let mut engine = StyleEngine::default();
+        engine.register_style::<SizedStyle>("b", &[]).unwrap();
+        engine
+            .append_sheet(
+                b"variables: {fg: white, size: 2}\nstyles: {a: {color: $fg}, b: {size: $size}}",
             )
+            .unwrap();
+        assert_eq!(color(&mut engine, "a"), "white");
+
+        engine.set_variable("$fg", Value::from("red")).unwrap();
+        assert_eq!(color(&mut engine, "a"), "red");
+        engine.append_sheet(b"variables: {fg: blue}").unwrap();
+        assert_eq!(color(&mut engine, "a"), "red");
+        assert_eq!(engine.variable("fg"), Some(&Value::from("red")));
+
+        assert!(engine.set_variable("size", Value::from("big")).is_err());
+        let classes = ["b".to_owned()];
+        assert_eq!(
+            engine.get_style::<SizedStyle>(&[], &classes).unwrap().size,
+            2.
+        );
+
+        // The rule comes before the sheet that redefines the variable.
+        engine.remove_variable("fg").unwrap();
+        assert_eq!(color(&mut engine, "a"), "white");
+    }
+
+    #[test]
+    fn style_override_validation() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct SizedStyle {
+            color: String,
+            size: f32,
+        }
+
+        let check = |s: &str| check_override::<SizedStyle>("text", &yaml(s));
+        assert!(check("{size: 3}").is_ok());
+        assert!(check("{size: $big, color: blue}").is_ok());
+        let errors = match check("{size: big}") {
+            Err(StyleError::Invalid(errors)) => errors,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        assert_eq!(errors[0].field.as_deref(), Some("size"));
+        assert_eq!(errors[0].rule.as_deref(), Some(OVERRIDE_RULE));
+    }
+
+    /// Merges styles whose queries all match, given in source order.
+    fn merged(styles: &[(&str, &str)]) -> Value {
+        let mut variables = Variables::default();
+        variables
+            .variables
+            .insert("fg".to_owned(), Value::from("white"));
+        let styles: Vec<Style> = styles
+            .iter()
+            .map(|(query, value)| {
+                Style::new(
+                    serde_yaml::from_str(value).unwrap(),
+                    ClassQuery::from_str(query).unwrap(),
+                    &variables,
+                )
+                .unwrap()
+            })
+            .collect();
+        merge_matching(styles.iter())
+    }
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn specificity_beats_source_order() {
+        assert_eq!(
+            merged(&[
+                ("button & hovered", "{color: red, width: 2}"),
+                ("button", "{color: blue, height: 1}"),
+            ]),
+            yaml("{color: red, width: 2, height: 1}")
+        );
+        // An `|` query is as specific as its most specific alternative.
+        assert_eq!(
+            merged(&[
+                ("button & hovered | focused", "{color: red}"),
+                ("button & pressed", "{color: blue}"),
+            ]),
+            yaml("{color: blue}")
+        );
+        // Source order breaks ties.
+        assert_eq!(
+            merged(&[("a & b", "{color: red}"), ("b & a", "{color: blue}")]),
+            yaml("{color: blue}")
+        );
+    }
+
+    #[test]
+    fn explicit_priority() {
+        assert_eq!(
+            merged(&[
+                ("button", "{color: red, priority: 1}"),
+                ("button & hovered & focused", "{color: blue}"),
+                ("button & hovered", "{color: green, priority: -1}"),
+            ]),
+            yaml("{color: red}")
+        );
+        let value = yaml("{color: red, priority: high}");
+        assert!(matches!(
+            Style::new(
+                value,
+                ClassQuery::from_str("a").unwrap(),
+                &Variables::default()
+            ),
+            Err(StyleError::InvalidPriority)
+        ));
+    }
+
+    #[test]
+    fn important_properties() {
+        assert_eq!(
+            merged(&[
+                (
+                    "button",
+                    "{color: $fg !important, width: 2 !important, font: {size: 12 !important, family: Serif}}"
+                ),
+                (
+                    "button & hovered",
+                    "{color: blue, width: 3, height: 4, font: {size: 14, family: Sans}, priority: 10}"
+                ),
+            ]),
+            yaml("{color: white, width: 2, height: 4, font: {size: 12, family: Sans}}")
         );
+        // Among important properties, the usual order applies.
+        assert_eq!(
+            merged(&[
+                ("button & hovered", "{color: red !important}"),
+                ("button", "{color: blue !important}"),
+            ]),
+            yaml("{color: red}")
+        );
+    }
+
+    #[test]
+    fn registered_styles_are_precomputed() {
+        let mut engine = StyleEngine::default();
+        engine
+            .register_style::<TestStyle>("a", &["hovered"])
+            .unwrap();
+        engine
+            .append_sheet(b"styles:\n  a: {color: red}\n  a & hovered: {color: blue}\n")
+            .unwrap();
+
+        let cached = |engine: &StyleEngine, classes: &[&str]| {
+            let classes: Vec<String> = classes.iter().map(|&c| c.to_owned()).collect();
+            let style = engine.cache.get(&[], &classes, TypeId::of::<TestStyle>())?;
+            Some(style.downcast_ref::<TestStyle>().unwrap().color.clone())
+        };
+        assert_eq!(cached(&engine, &["a"]).as_deref(), Some("red"));
+        assert_eq!(cached(&engine, &["a", "hovered"]).as_deref(), Some("blue"));
+
+        engine.append_sheet(b"styles: {a: {color: green}}").unwrap();
+        assert_eq!(cached(&engine, &["a"]).as_deref(), Some("green"));
+        assert_eq!(cached(&engine, &["a", "hovered"]).as_deref(), Some("blue"));
+    }
+
+    #[test]
+    fn cache_is_per_ancestor_chain() {
+        let mut engine = StyleEngine::default();
+        engine
+            .append_sheet(b"styles: {button: {color: red}, toolbar button: {color: blue}}")
+            .unwrap();
+
+        let classes = |classes: &[&str]| classes.iter().map(|&c| c.to_owned()).collect::<Vec<_>>();
+        let toolbar = engine.ancestor_classes(&classes(&["toolbar", "hovered"]));
+        assert_eq!(toolbar, classes(&["toolbar"]));
+        let other = engine.ancestor_classes(&classes(&["container"]));
+        assert!(other.is_empty());
+
+        let mut color = |ancestors: &[Vec<String>]| {
+            engine
+                .get_style::<TestStyle>(ancestors, &classes(&["button"]))
+                .unwrap()
+                .color
+                .clone()
+        };
+        assert_eq!(color(&[]), "red");
+        assert_eq!(color(&[toolbar.clone(), other.clone()]), "blue");
+        assert_eq!(color(std::slice::from_ref(&other)), "red");
+        assert_eq!(color(&[toolbar.clone()]), "blue");
+
+        // Ancestors without relevant classes only split
+        // the cache where a `>` combinator could see them.
+        assert_eq!(color(&[other.clone(), other.clone(), other.clone()]), "red");
+        assert_eq!(color(&[toolbar, other.clone(), other]), "blue");
+        assert_eq!(engine.cache.chains.len(), 3);
+    }
+
+    #[test]
+    fn normalized_ancestors() {
+        let mut engine = StyleEngine::default();
+        engine
+            .append_sheet(b"styles: {a > b: {color: red}, a c: {color: blue}}")
+            .unwrap();
+        let a = || vec!["a".to_owned()];
+        let none = Vec::new;
+
+        let normalize = |chain: &[Vec<String>]| engine.styles.normalize_ancestors(chain).to_vec();
+        assert!(normalize(&[none(), none()]).is_empty());
+        assert_eq!(normalize(&[none(), a(), none()]), [a(), none()]);
+        assert_eq!(normalize(&[a(), none(), none(), a()]), [a(), none(), a()]);
+
+        // `!a` matches ancestors without classes, so they all matter.
+        engine
+            .append_sheet(b"styles: {'!a > d': {color: green}}")
+            .unwrap();
+        let normalize = |chain: &[Vec<String>]| engine.styles.normalize_ancestors(chain).to_vec();
+        assert_eq!(normalize(&[none(), none()]), [none(), none()]);
     }
 }
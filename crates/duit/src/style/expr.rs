@@ -0,0 +1,833 @@
+//! Expressions in style values, evaluated when styles are built.
+//!
+//! # Syntax
+//!
+//! ```text
+//! expr    := term (('+' | '-') term)*
+//! term    := unary (('*' | '/') unary)*
+//! unary   := '-' unary | primary
+//! primary := number '%'? | '$' name | name '(' (expr (',' expr)*)? ')' | '(' expr ')'
+//! ```
+//!
+//! A percentage is a fraction, so `10%` is the same as `0.1`.
+//! A number may end in `deg`, which does not change it.
+//!
+//! Operands are numbers, colors, text or paints. Arithmetic applies to
+//! numbers only; colors are combined with these functions:
+//!
+//! * `lighten(color, amount)` and `darken(color, amount)` change the
+//!   HSL lightness by `amount`, so `darken($c, 10%)` makes `$c` 10% darker;
+//! * `alpha(color, alpha)` replaces the alpha of `color`;
+//! * `lerp(a, b, t)` interpolates from `a` (`t = 0`) to `b` (`t = 1`),
+//!   and `mix(a, b, t)` does the same in linear light;
+//! * `rgb(r, g, b)` and `rgba(r, g, b, a)` create colors from
+//!   components between 0 and 255;
+//! * `hsl(h, s, l)` and `hsla(h, s, l, a)` create colors from a hue in
+//!   degrees and the rest between 0 and 1, like `hsl(30deg, 100%, 50%)`.
+//!   As in color strings, a saturation or lightness written as a plain
+//!   number is a percentage, so `hsl(30, 100, 50)` is the same color.
+//!
+//! Hex colors, like `#fc4c02`, color names, like `orange`, and
+//! quoted text, like `'wood'`, can be used as operands too.
+//!
+//! [`Paint`]s are built with these functions:
+//!
+//! * `linear_gradient(angle, stops...)`, where the optional angle is in
+//!   degrees clockwise from pointing up, and defaults to 180, top to bottom;
+//! * `radial_gradient(inner, outer)`, from the center to the farthest corner;
+//! * `pattern(texture, width)`, which tiles a texture, scaled to the
+//!   optional width.
+//!
+//! Each gradient stop is a color, optionally followed by its offset along
+//! the gradient, like `linear_gradient($a, $b 30%, $c)`. Stops without
+//! offsets are spread evenly between their neighbors. Only two-color
+//! gradients can run at an angle other than a multiple of 90, and radial
+//! gradients take two colors, of which only the outer one has an offset.
+//!
+//! A string is only evaluated if it starts with a variable, like `$a * 2`,
+//! or with a call to one of the functions above other than `rgb`, `rgba`,
+//! `hsl` and `hsla`, like `darken($c, 10%)`. Other strings, like `2 * $a`
+//! or `costs $5`, are left as they are.
+//! A string that consists of a single variable, like `$font`, is replaced
+//! by the variable's value, whatever its type.
+
+use std::{fmt, str::FromStr};
+
+use serde_yaml::Value;
+
+use crate::{
+    paint::{spread_stops, Paint},
+    Color,
+};
+
+use super::{Variables, VARIABLE_PREFIX};
+
+/// Functions that make a string an expression when it starts with them.
+const FUNCTIONS: &[&str] = &[
+    "lighten",
+    "darken",
+    "alpha",
+    "lerp",
+    "mix",
+    "linear_gradient",
+    "radial_gradient",
+    "pattern",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionErrorKind {
+    MissingVariable(String),
+    CyclicVariable(String),
+    /// Evaluating the variable's own expression failed.
+    InVariable {
+        variable: String,
+        error: Box<ExpressionError>,
+    },
+    /// The variable holds a value, like a mapping, that
+    /// cannot be used as an operand.
+    NotAnOperand(String),
+    UnexpectedChar(char),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnknownFunction(String),
+    UnknownColor(String),
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+    TypeMismatch(String),
+    InvalidPaint(String),
+    DivisionByZero,
+}
+
+impl fmt::Display for ExpressionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionErrorKind::MissingVariable(var) => write!(f, "missing variable '{}'", var),
+            ExpressionErrorKind::CyclicVariable(var) => {
+                write!(f, "variable '{}' refers to itself", var)
+            }
+            ExpressionErrorKind::InVariable { variable, error } => {
+                write!(f, "in variable '{}': {}", variable, error)
+            }
+            ExpressionErrorKind::NotAnOperand(var) => {
+                write!(f, "variable '{}' is neither a number nor a color", var)
+            }
+            ExpressionErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            ExpressionErrorKind::UnexpectedToken(token) => write!(f, "unexpected '{}'", token),
+            ExpressionErrorKind::UnexpectedEnd => f.write_str("unexpected end of expression"),
+            ExpressionErrorKind::UnknownFunction(function) => {
+                write!(f, "unknown function '{}'", function)
+            }
+            ExpressionErrorKind::UnknownColor(color) => write!(f, "unknown color '{}'", color),
+            ExpressionErrorKind::ArgumentCount {
+                function,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "function '{}' takes {} arguments but {} were given",
+                function, expected, actual
+            ),
+            ExpressionErrorKind::TypeMismatch(message)
+            | ExpressionErrorKind::InvalidPaint(message) => f.write_str(message),
+            ExpressionErrorKind::DivisionByZero => f.write_str("division by zero"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{kind} in expression '{expression}'")]
+pub struct ExpressionError {
+    pub kind: ExpressionErrorKind,
+    pub expression: String,
+}
+
+/// Evaluates the expressions in a style value in place.
+pub(super) fn evaluate(value: &mut Value, variables: &Variables) -> Result<(), ExpressionError> {
+    Evaluator {
+        variables,
+        stack: Vec::new(),
+    }
+    .evaluate_value(value)
+}
+
+/// Parses a paint, which cannot refer to variables.
+pub(crate) fn parse_paint(s: &str) -> Result<Paint, ExpressionError> {
+    let variables = Variables::default();
+    let mut evaluator = Evaluator {
+        variables: &variables,
+        stack: Vec::new(),
+    };
+    match evaluator.evaluate_operand(s)? {
+        Operand::Color(color) => Ok(Paint::Solid(color)),
+        Operand::Paint(paint) => Ok(paint),
+        other => Err(ExpressionError {
+            kind: ExpressionErrorKind::TypeMismatch(format!(
+                "expected a color or paint, not {}",
+                other.type_name()
+            )),
+            expression: s.to_owned(),
+        }),
+    }
+}
+
+/// Tests whether a string is evaluated as an expression.
+pub(super) fn is_expression(s: &str) -> bool {
+    s.starts_with(VARIABLE_PREFIX)
+        || FUNCTIONS
+            .iter()
+            .any(|function| matches!(s.strip_prefix(function), Some(rest) if rest.starts_with('(')))
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Number(f64),
+    Color(Color),
+    Text(String),
+    Paint(Paint),
+}
+
+impl Operand {
+    fn into_value(self) -> Value {
+        match self {
+            // Keep whole numbers integers, so they can
+            // deserialize into integer fields.
+            Operand::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                Value::from(n as i64)
+            }
+            Operand::Number(n) => Value::from(n),
+            Operand::Color(c) => Value::String(c.to_string()),
+            Operand::Text(text) => Value::String(text),
+            Operand::Paint(paint) => Value::String(paint.to_string()),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Operand::Number(_) => "a number",
+            Operand::Color(_) => "a color",
+            Operand::Text(_) => "text",
+            Operand::Paint(_) => "a paint",
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    variables: &'a Variables,
+    /// Variables being evaluated, to detect cycles.
+    stack: Vec<String>,
+}
+
+impl Evaluator<'_> {
+    fn evaluate_value(&mut self, value: &mut Value) -> Result<(), ExpressionError> {
+        match value {
+            Value::String(s) if is_expression(s) => {
+                *value = self.evaluate_str(&s.clone())?;
+            }
+            Value::Mapping(mapping) => {
+                for (_, child_value) in mapping.iter_mut() {
+                    self.evaluate_value(child_value)?;
+                }
+            }
+            Value::Sequence(s) => {
+                for child_value in s {
+                    self.evaluate_value(child_value)?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn evaluate_str(&mut self, expression: &str) -> Result<Value, ExpressionError> {
+        if let Some(var) = expression.trim().strip_prefix(VARIABLE_PREFIX) {
+            if var.chars().all(is_name_char) {
+                return self.variable(var).map_err(|kind| ExpressionError {
+                    kind,
+                    expression: expression.to_owned(),
+                });
+            }
+        }
+        self.evaluate_operand(expression).map(Operand::into_value)
+    }
+
+    fn evaluate_operand(&mut self, expression: &str) -> Result<Operand, ExpressionError> {
+        let error = |kind| ExpressionError {
+            kind,
+            expression: expression.to_owned(),
+        };
+
+        let tokens = tokenize(expression).map_err(error)?;
+        let mut parser = Parser {
+            evaluator: self,
+            tokens: &tokens,
+            pos: 0,
+        };
+        let result = parser.parse_expr().and_then(|operand| match parser.peek() {
+            Some(token) => Err(ExpressionErrorKind::UnexpectedToken(token.to_string())),
+            None => Ok(operand),
+        });
+        result.map_err(error)
+    }
+
+    /// Gets the value of a variable, with its expressions evaluated.
+    fn variable(&mut self, var: &str) -> Result<Value, ExpressionErrorKind> {
+        if self.stack.iter().any(|v| v == var) {
+            return Err(ExpressionErrorKind::CyclicVariable(var.to_owned()));
+        }
+        let mut value = self
+            .variables
+            .get(var)
+            .ok_or_else(|| ExpressionErrorKind::MissingVariable(var.to_owned()))?
+            .clone();
+
+        self.stack.push(var.to_owned());
+        let result = self.evaluate_value(&mut value);
+        self.stack.pop();
+        result.map_err(|error| ExpressionErrorKind::InVariable {
+            variable: var.to_owned(),
+            error: Box::new(error),
+        })?;
+        Ok(value)
+    }
+
+    fn variable_operand(&mut self, var: &str) -> Result<Operand, ExpressionErrorKind> {
+        match self.variable(var)? {
+            Value::Number(n) => Ok(Operand::Number(n.as_f64().unwrap_or_default())),
+            Value::String(s) => Ok(Color::from_str(&s)
+                .map(Operand::Color)
+                .unwrap_or(Operand::Text(s))),
+            _ => Err(ExpressionErrorKind::NotAnOperand(var.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    Percent,
+    Variable(&'a str),
+    Ident(&'a str),
+    /// A hex color, including the `#`.
+    Hex(&'a str),
+    /// Quoted text, without the quotes.
+    Str(&'a str),
+    Op(char),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Percent => f.write_str("%"),
+            Token::Variable(var) => write!(f, "{}{}", VARIABLE_PREFIX, var),
+            Token::Ident(ident) | Token::Hex(ident) => f.write_str(ident),
+            Token::Str(text) => write!(f, "'{}'", text),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::OpenParen => f.write_str("("),
+            Token::CloseParen => f.write_str(")"),
+            Token::Comma => f.write_str(","),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token<'_>>, ExpressionErrorKind> {
+    // Finds the end of the run of matching characters starting at `start`.
+    let run_end = |start: usize, pred: &dyn Fn(char) -> bool| {
+        s[start..]
+            .find(|c: char| !pred(c))
+            .map_or(s.len(), |len| start + len)
+    };
+
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = s[pos..].chars().next() {
+        let (token, end) = match c {
+            c if c.is_whitespace() => {
+                pos += c.len_utf8();
+                continue;
+            }
+            '%' => (Token::Percent, pos + 1),
+            '(' => (Token::OpenParen, pos + 1),
+            ')' => (Token::CloseParen, pos + 1),
+            ',' => (Token::Comma, pos + 1),
+            '+' | '-' | '*' | '/' => (Token::Op(c), pos + 1),
+            VARIABLE_PREFIX => {
+                let end = run_end(pos + 1, &is_name_char);
+                if end == pos + 1 {
+                    return Err(ExpressionErrorKind::UnexpectedChar(c));
+                }
+                (Token::Variable(&s[pos + 1..end]), end)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let end = run_end(pos, &|c| c.is_ascii_digit() || c == '.');
+                let number = s[pos..end]
+                    .parse()
+                    .map_err(|_| ExpressionErrorKind::UnexpectedToken(s[pos..end].to_owned()))?;
+                let unit_end = run_end(end, &is_name_char);
+                match &s[end..unit_end] {
+                    "deg" => (Token::Number(number), unit_end),
+                    _ => (Token::Number(number), end),
+                }
+            }
+            '\'' | '"' => {
+                let len = s[pos + 1..]
+                    .find(c)
+                    .ok_or(ExpressionErrorKind::UnexpectedEnd)?;
+                (Token::Str(&s[pos + 1..pos + 1 + len]), pos + len + 2)
+            }
+            '#' => {
+                let end = run_end(pos + 1, &is_name_char);
+                (Token::Hex(&s[pos..end]), end)
+            }
+            c if is_name_char(c) => {
+                let end = run_end(pos, &is_name_char);
+                (Token::Ident(&s[pos..end]), end)
+            }
+            c => return Err(ExpressionErrorKind::UnexpectedChar(c)),
+        };
+        tokens.push(token);
+        pos = end;
+    }
+    Ok(tokens)
+}
+
+/// Evaluates tokens while parsing them.
+struct Parser<'a, 'b, 'c> {
+    evaluator: &'a mut Evaluator<'b>,
+    tokens: &'a [Token<'c>],
+    pos: usize,
+}
+
+impl<'c> Parser<'_, '_, 'c> {
+    fn peek(&self) -> Option<&Token<'c>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token<'c>, ExpressionErrorKind> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(ExpressionErrorKind::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token<'c>) -> Result<(), ExpressionErrorKind> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(ExpressionErrorKind::UnexpectedToken(token.to_string()))
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Operand, ExpressionErrorKind> {
+        let mut lhs = self.parse_term()?;
+        while let Some(&Token::Op(op @ ('+' | '-'))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.parse_term()?;
+            lhs = arithmetic(op, lhs, rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_term(&mut self) -> Result<Operand, ExpressionErrorKind> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(&Token::Op(op @ ('*' | '/'))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.parse_unary()?;
+            lhs = arithmetic(op, lhs, rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Operand, ExpressionErrorKind> {
+        if self.peek() == Some(&Token::Op('-')) {
+            self.pos += 1;
+            let operand = self.parse_unary()?;
+            return arithmetic('*', operand, Operand::Number(-1.0));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Operand, ExpressionErrorKind> {
+        match self.next()? {
+            Token::Number(n) => {
+                if self.peek() == Some(&Token::Percent) {
+                    self.pos += 1;
+                    Ok(Operand::Number(n / 100.0))
+                } else {
+                    Ok(Operand::Number(n))
+                }
+            }
+            Token::Variable(var) => self.evaluator.variable_operand(var),
+            Token::Hex(color) => parse_color(color),
+            Token::Str(text) => Ok(Operand::Text(text.to_owned())),
+            Token::Ident(name) if self.peek() != Some(&Token::OpenParen) => parse_color(name),
+            Token::Ident(function) => {
+                self.expect(Token::OpenParen)?;
+                let gradient = matches!(function, "linear_gradient" | "radial_gradient");
+                let mut args = Vec::new();
+                let mut offsets = Vec::new();
+                // Whether each argument is a number without a `%`.
+                let mut plain_numbers = Vec::new();
+                if self.peek() == Some(&Token::CloseParen) {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        let start = self.pos;
+                        args.push(self.parse_expr()?);
+                        plain_numbers.push(
+                            self.pos == start + 1 && matches!(self.tokens[start], Token::Number(_)),
+                        );
+                        // Gradient stops may be followed by their offset.
+                        let offset = match self.peek() {
+                            Some(Token::Comma) | Some(Token::CloseParen) => None,
+                            _ if gradient => Some(self.parse_expr()?),
+                            _ => None,
+                        };
+                        offsets.push(offset);
+                        match self.next()? {
+                            Token::Comma => continue,
+                            Token::CloseParen => break,
+                            token => {
+                                return Err(ExpressionErrorKind::UnexpectedToken(token.to_string()))
+                            }
+                        }
+                    }
+                }
+                if matches!(function, "hsl" | "hsla") {
+                    // Saturation and lightness.
+                    for i in 1..args.len().min(3) {
+                        if let (true, Operand::Number(n)) = (plain_numbers[i], &mut args[i]) {
+                            *n /= 100.0;
+                        }
+                    }
+                }
+                match function {
+                    "linear_gradient" | "radial_gradient" | "pattern" => {
+                        call_paint(function, args, offsets)
+                    }
+                    _ => call(function, &args),
+                }
+            }
+            Token::OpenParen => {
+                let operand = self.parse_expr()?;
+                self.expect(Token::CloseParen)?;
+                Ok(operand)
+            }
+            token => Err(ExpressionErrorKind::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Result<Operand, ExpressionErrorKind> {
+    Color::from_str(s)
+        .map(Operand::Color)
+        .map_err(|_| ExpressionErrorKind::UnknownColor(s.to_owned()))
+}
+
+fn arithmetic(op: char, lhs: Operand, rhs: Operand) -> Result<Operand, ExpressionErrorKind> {
+    let (a, b) = match (&lhs, &rhs) {
+        (Operand::Number(a), Operand::Number(b)) => (*a, *b),
+        _ => {
+            return Err(ExpressionErrorKind::TypeMismatch(format!(
+                "cannot apply '{}' to {} and {}",
+                op,
+                lhs.type_name(),
+                rhs.type_name()
+            )))
+        }
+    };
+    Ok(Operand::Number(match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        _ if b == 0.0 => return Err(ExpressionErrorKind::DivisionByZero),
+        _ => a / b,
+    }))
+}
+
+fn call(function: &str, args: &[Operand]) -> Result<Operand, ExpressionErrorKind> {
+    let arity = match function {
+        "lighten" | "darken" | "alpha" => 2,
+        "lerp" | "mix" | "rgb" | "hsl" => 3,
+        "rgba" | "hsla" => 4,
+        _ => return Err(ExpressionErrorKind::UnknownFunction(function.to_owned())),
+    };
+    if args.len() != arity {
+        return Err(ExpressionErrorKind::ArgumentCount {
+            function: function.to_owned(),
+            expected: arity,
+            actual: args.len(),
+        });
+    }
+
+    let color = |i: usize| match &args[i] {
+        Operand::Color(c) => Ok(*c),
+        other => Err(argument_mismatch(function, i, "a color", other)),
+    };
+    let number = |i: usize| match &args[i] {
+        Operand::Number(n) => Ok(*n as f32),
+        other => Err(argument_mismatch(function, i, "a number", other)),
+    };
+    let component = |i: usize| number(i).map(|n| n.round().clamp(0.0, 255.0) as u8);
+
+    let color = match function {
+        "lighten" => color(0)?.lighten(number(1)?),
+        "darken" => color(0)?.darken(number(1)?),
+        "alpha" => color(0)?.with_alpha(number(1)?),
+        "lerp" => color(0)?.lerp(color(1)?, number(2)?),
+        "mix" => color(0)?.mix(color(1)?, number(2)?),
+        "rgb" => Color::rgba8(component(0)?, component(1)?, component(2)?, u8::MAX),
+        "rgba" => Color::rgba8(component(0)?, component(1)?, component(2)?, component(3)?),
+        "hsl" => Color::hsla(number(0)?, number(1)?, number(2)?, 1.0),
+        _ => Color::hsla(number(0)?, number(1)?, number(2)?, number(3)?),
+    };
+    Ok(Operand::Color(color))
+}
+
+/// Builds a gradient or pattern. `offsets` holds the
+/// offset that follows each argument, if any.
+fn call_paint(
+    function: &str,
+    args: Vec<Operand>,
+    offsets: Vec<Option<Operand>>,
+) -> Result<Operand, ExpressionErrorKind> {
+    let invalid = |message: &str| ExpressionErrorKind::InvalidPaint(message.to_owned());
+
+    if function == "pattern" {
+        if !(1..=2).contains(&args.len()) {
+            return Err(ExpressionErrorKind::ArgumentCount {
+                function: function.to_owned(),
+                expected: 2,
+                actual: args.len(),
+            });
+        }
+        let mut args = args.into_iter();
+        let texture = match args.next() {
+            Some(Operand::Text(texture)) => texture,
+            Some(other) => return Err(argument_mismatch(function, 0, "text", &other)),
+            None => unreachable!(),
+        };
+        let width = match args.next() {
+            Some(Operand::Number(width)) => Some(width as f32),
+            Some(other) => return Err(argument_mismatch(function, 1, "a number", &other)),
+            None => None,
+        };
+        return Ok(Operand::Paint(Paint::Pattern { texture, width }));
+    }
+
+    let mut args = args.into_iter().zip(offsets).enumerate().peekable();
+    let mut angle = 180.0;
+    if function == "linear_gradient" {
+        if let Some((_, (Operand::Number(n), None))) = args.peek() {
+            angle = *n as f32;
+            args.next();
+        }
+    }
+
+    let mut stops = Vec::new();
+    for (i, (value, offset)) in args {
+        let color = match value {
+            Operand::Color(color) => color,
+            other => return Err(argument_mismatch(function, i, "a color", &other)),
+        };
+        let offset = match offset {
+            Some(Operand::Number(offset)) => Some(offset as f32),
+            Some(other) => {
+                return Err(ExpressionErrorKind::TypeMismatch(format!(
+                    "the offset of argument {} of '{}' must be a number, not {}",
+                    i + 1,
+                    function,
+                    other.type_name()
+                )))
+            }
+            None => None,
+        };
+        stops.push((color, offset));
+    }
+    if stops.len() < 2 {
+        return Err(invalid("gradients need at least two colors"));
+    }
+
+    let paint = if function == "radial_gradient" {
+        if stops.len() != 2 || stops[0].1.is_some() {
+            return Err(invalid(
+                "radial gradients take an inner and an outer color, \
+                 and only the outer color can have an offset",
+            ));
+        }
+        Paint::RadialGradient {
+            inner: stops[0].0,
+            outer: stops[1].0,
+            radius: stops[1].1.unwrap_or(1.0),
+        }
+    } else {
+        if stops.len() > 2 && angle % 90.0 != 0.0 {
+            return Err(invalid(
+                "gradients with more than two colors must run at a multiple of 90 degrees",
+            ));
+        }
+        Paint::LinearGradient {
+            angle,
+            stops: spread_stops(stops),
+        }
+    };
+    Ok(Operand::Paint(paint))
+}
+
+fn argument_mismatch(
+    function: &str,
+    index: usize,
+    expected: &str,
+    actual: &Operand,
+) -> ExpressionErrorKind {
+    ExpressionErrorKind::TypeMismatch(format!(
+        "argument {} of '{}' must be {}, not {}",
+        index + 1,
+        function,
+        expected,
+        actual.type_name()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(yaml: &str) -> Variables {
+        Variables {
+            variables: serde_yaml::from_str(yaml).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn eval(expression: &str, variables: &Variables) -> Result<Value, ExpressionErrorKind> {
+        let mut value = Value::String(expression.to_owned());
+        evaluate(&mut value, variables).map_err(|e| e.kind)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn arithmetic() {
+        let vars = variables("{padding: 5, half: 0.5}");
+        assert_eq!(eval("$padding * 2", &vars), Ok(Value::from(10)));
+        assert_eq!(eval("$padding * -2 + 2", &vars), Ok(Value::from(-8)));
+        assert_eq!(eval("$half * ($padding + 1)", &vars), Ok(Value::from(3)));
+        assert_eq!(eval("$half * 50%", &vars), Ok(Value::from(0.25)));
+        assert_eq!(
+            eval("$padding / (1 - 1)", &vars),
+            Err(ExpressionErrorKind::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn color_functions() {
+        let vars = variables("{bg: 'rgb(128, 128, 128)', fg: 'rgba(0, 0, 0, 255)'}");
+        let color = |s: &str| eval(s, &vars).unwrap().as_str().unwrap().to_owned();
+        assert_eq!(color("darken($bg, 20%)"), "#4d4d4d");
+        assert_eq!(color("lighten($bg, 0.2)"), "#b3b3b3");
+        assert_eq!(color("alpha($fg, 0.5)"), "#00000080");
+        assert_eq!(color("lerp($fg, rgb(255, 255, 255), 25%)"), "#404040");
+        assert_eq!(color("mix(#000, white, 50%)"), "#bcbcbc");
+        assert_eq!(color("darken(hsl(0, 100%, 50%), 10%)"), "#cc0000");
+        assert_eq!(
+            eval("alpha(blurple, 1)", &vars),
+            Err(ExpressionErrorKind::UnknownColor("blurple".to_owned()))
+        );
+    }
+
+    #[test]
+    fn hsl_like_color_strings() {
+        let vars = variables("{s: 1}");
+        let color = |s: &str| eval(s, &vars).unwrap().as_str().unwrap().to_owned();
+        assert_eq!(color("darken(hsl(0, 100, 50), 10%)"), "#cc0000");
+        assert_eq!(color("darken(hsl(120deg, 100%, 50%), 10%)"), "#00cc00");
+        assert_eq!(color("alpha(hsla(240, $s, 50, 1), 1)"), "#0000ff");
+    }
+
+    #[test]
+    fn paints() {
+        let vars = variables("{primary: '#ff0000', stop: 0.25, texture: wood}");
+        let paint = |s: &str| eval(s, &vars).unwrap().as_str().unwrap().to_owned();
+        assert_eq!(
+            paint("linear_gradient(90, $primary, darken($primary, 20%) $stop * 2, white)"),
+            "linear_gradient(90, #ff0000 0, #990000 0.5, #ffffff 1)"
+        );
+        assert_eq!(
+            paint("radial_gradient(alpha($primary, 0), $primary)"),
+            "radial_gradient(#ff000000, #ff0000 1)"
+        );
+        assert_eq!(paint("pattern($texture, 16)"), "pattern('wood', 16)");
+        assert!(matches!(
+            eval("linear_gradient($primary)", &vars),
+            Err(ExpressionErrorKind::InvalidPaint(_))
+        ));
+    }
+
+    #[test]
+    fn plain_values() {
+        let vars = variables("{font: Times New Roman, size: {x: 1}, double: $size}");
+        assert_eq!(eval("$font", &vars), Ok(Value::from("Times New Roman")));
+        assert_eq!(eval("$double", &vars), eval("$size", &vars));
+        // Not expressions, so left for the style type to parse.
+        assert_eq!(eval("rgb(1, 2, 3)", &vars), Ok(Value::from("rgb(1, 2, 3)")));
+        assert_eq!(eval("alpha", &vars), Ok(Value::from("alpha")));
+        for text in ["costs $5", "2 * $size", "mix it up", "darken (", " $font"] {
+            assert_eq!(eval(text, &vars), Ok(Value::from(text)));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let vars = variables("{a: $b, b: $a, map: {x: 1}, n: 1}");
+        assert_eq!(
+            eval("$missing", &vars),
+            Err(ExpressionErrorKind::MissingVariable("missing".to_owned()))
+        );
+        assert_eq!(
+            eval("$map * 2", &vars),
+            Err(ExpressionErrorKind::NotAnOperand("map".to_owned()))
+        );
+        assert!(matches!(
+            eval("darken($n, 1)", &vars),
+            Err(ExpressionErrorKind::TypeMismatch(_))
+        ));
+        assert_eq!(
+            eval("alpha($n)", &vars),
+            Err(ExpressionErrorKind::ArgumentCount {
+                function: "alpha".to_owned(),
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(eval("$n +", &vars), Err(ExpressionErrorKind::UnexpectedEnd));
+        assert_eq!(
+            eval("$n $n", &vars),
+            Err(ExpressionErrorKind::UnexpectedToken("$n".to_owned()))
+        );
+        assert_eq!(
+            eval("$n + frobnicate($n)", &vars),
+            Err(ExpressionErrorKind::UnknownFunction(
+                "frobnicate".to_owned()
+            ))
+        );
+        assert!(matches!(
+            eval("$a", &vars),
+            Err(ExpressionErrorKind::InVariable { variable, error })
+                if variable == "a" && matches!(&error.kind, ExpressionErrorKind::InVariable { .. })
+        ));
+    }
+}
@@ -0,0 +1,713 @@
+//! Class queries, which specify to which elements a style applies.
+//!
+//! # Syntax
+//!
+//! ```text
+//! query      := predicate (combinator predicate)*
+//! combinator := '>' | whitespace
+//! predicate  := and ('|' and)*
+//! and        := unary ('&' unary)*
+//! unary      := '!' unary | '(' predicate ')' | class
+//! ```
+//!
+//! `!` binds tightest, then `&`, then `|`, so `a | b & !c`
+//! means `a | (b & (!c))`. Both `&` and `|` group from the left.
+//!
+//! The last predicate applies to the element itself; the ones before it
+//! apply to its ancestors. `toolbar button` matches a `button` anywhere
+//! inside a `toolbar`, and `toolbar > button` matches a `button`
+//! whose parent is a `toolbar`. Combinators cannot appear
+//! inside parentheses.
+//!
+//! In YAML, a query starting with `!` must be quoted, as in
+//! `'!disabled & button'`; otherwise it is read as a tag.
+
+use std::{fmt, str::FromStr};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum ParseErrorKind {
+    Empty,
+    ExpectedClass,
+    ExpectedOperator,
+    UnclosedParen,
+    UnmatchedParen,
+    CombinatorInGroup,
+    UnexpectedChar(char),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Empty => f.write_str("no query specified (empty / blank string)"),
+            ParseErrorKind::ExpectedClass => f.write_str("expected a class name"),
+            ParseErrorKind::ExpectedOperator => {
+                f.write_str("expected '&', '|', '>' or whitespace between classes")
+            }
+            ParseErrorKind::UnclosedParen => f.write_str("unclosed '('"),
+            ParseErrorKind::UnmatchedParen => f.write_str("unmatched ')'"),
+            ParseErrorKind::CombinatorInGroup => {
+                f.write_str("combinators cannot be used inside parentheses")
+            }
+            ParseErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{kind} at column {column} of query '{query}'")]
+pub(super) struct ClassQueryParseError {
+    pub kind: ParseErrorKind,
+    /// 1-based column of the offending character.
+    pub column: usize,
+    pub query: String,
+}
+
+/// A parsed class query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct ClassQuery {
+    /// Matched against the element itself.
+    pub predicate: QueryPredicate,
+    /// Matched against the element's ancestors, nearest first.
+    pub ancestors: Vec<AncestorSelector>,
+}
+
+/// A predicate on one of the ancestors of an element.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct AncestorSelector {
+    /// Relates this ancestor to the element matched by
+    /// the selector to its right.
+    pub combinator: Combinator,
+    pub predicate: QueryPredicate,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(super) enum Combinator {
+    /// `a > b`: the ancestor is the direct parent.
+    Child,
+    /// `a b`: the ancestor is at any level above.
+    Descendant,
+}
+
+impl FromStr for ClassQuery {
+    type Err = ClassQueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::new(s)?.parse_query()
+    }
+}
+
+/// Formats a query in canonical form, with only
+/// the parentheses that precedence requires.
+impl fmt::Display for ClassQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for ancestor in self.ancestors.iter().rev() {
+            ancestor.predicate.fmt_with_precedence(f, 0)?;
+            match ancestor.combinator {
+                Combinator::Child => f.write_str(" > ")?,
+                Combinator::Descendant => f.write_str(" ")?,
+            }
+        }
+        self.predicate.fmt_with_precedence(f, 0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ClassQuery {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s: String = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|e| serde::de::Error::custom(e.to_string()))
+    }
+}
+
+impl ClassQuery {
+    /// Tests whether an element matches this query. `ancestors` holds
+    /// the classes of each of the element's ancestors, root first.
+    pub fn matches(&self, ancestors: &[Vec<String>], classes: &[String]) -> bool {
+        self.predicate.matches(classes) && matches_ancestors(&self.ancestors, ancestors)
+    }
+
+    /// The number of classes an element and its ancestors need to match this query.
+    pub fn specificity(&self) -> u32 {
+        self.predicate.specificity()
+            + self
+                .ancestors
+                .iter()
+                .map(|ancestor| ancestor.predicate.specificity())
+                .sum::<u32>()
+    }
+}
+
+fn matches_ancestors(selectors: &[AncestorSelector], ancestors: &[Vec<String>]) -> bool {
+    let (selector, rest) = match selectors.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    match selector.combinator {
+        Combinator::Child => match ancestors.split_last() {
+            Some((parent, above)) => {
+                selector.predicate.matches(parent) && matches_ancestors(rest, above)
+            }
+            None => false,
+        },
+        Combinator::Descendant => (0..ancestors.len()).rev().any(|i| {
+            selector.predicate.matches(&ancestors[i]) && matches_ancestors(rest, &ancestors[..i])
+        }),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum QueryPredicate {
+    And(Box<QueryPredicate>, Box<QueryPredicate>),
+    Or(Box<QueryPredicate>, Box<QueryPredicate>),
+    /// The element must not match the inner predicate.
+    Not(Box<QueryPredicate>),
+
+    /// The element must have a certain class.
+    Class(String),
+}
+
+impl QueryPredicate {
+    /// Visits each class mentioned by this predicate.
+    pub fn for_each_class<'a>(&'a self, callback: &mut impl FnMut(&'a str)) {
+        match self {
+            QueryPredicate::And(a, b) | QueryPredicate::Or(a, b) => {
+                a.for_each_class(callback);
+                b.for_each_class(callback);
+            }
+            QueryPredicate::Not(a) => a.for_each_class(callback),
+            QueryPredicate::Class(c) => callback(c),
+        }
+    }
+
+    /// Returns a set of classes such that an element needs
+    /// at least one of them to match this predicate, or `None`
+    /// if the predicate can match elements without any of its classes.
+    ///
+    /// `frequency` counts how many queries mention each class;
+    /// rarer classes are preferred, as they narrow down matches more.
+    pub fn trigger_classes<'a>(
+        &'a self,
+        frequency: &AHashMap<&str, usize>,
+    ) -> Option<Vec<&'a str>> {
+        match self {
+            QueryPredicate::And(a, b) => {
+                // Either side alone is enough, so index by the rarer one.
+                match (a.trigger_classes(frequency), b.trigger_classes(frequency)) {
+                    (Some(a), Some(b)) => {
+                        let cost = |classes: &[&str]| -> usize {
+                            classes.iter().map(|class| frequency[class]).sum()
+                        };
+                        if cost(&a) <= cost(&b) {
+                            Some(a)
+                        } else {
+                            Some(b)
+                        }
+                    }
+                    (Some(classes), None) | (None, Some(classes)) => Some(classes),
+                    (None, None) => None,
+                }
+            }
+            QueryPredicate::Or(a, b) => {
+                let mut classes = a.trigger_classes(frequency)?;
+                classes.extend(b.trigger_classes(frequency)?);
+                classes.sort_unstable();
+                classes.dedup();
+                Some(classes)
+            }
+            QueryPredicate::Not(_) => None,
+            QueryPredicate::Class(c) => Some(vec![c.as_str()]),
+        }
+    }
+
+    /// The number of classes an element needs to match this predicate.
+    ///
+    /// As in CSS, a negation counts the classes it negates.
+    pub fn specificity(&self) -> u32 {
+        match self {
+            QueryPredicate::And(a, b) => a.specificity() + b.specificity(),
+            QueryPredicate::Or(a, b) => a.specificity().max(b.specificity()),
+            QueryPredicate::Not(a) => a.specificity(),
+            QueryPredicate::Class(_) => 1,
+        }
+    }
+
+    /// Formats this predicate, wrapping it in parentheses if it
+    /// binds less tightly than its context requires.
+    fn fmt_with_precedence(&self, f: &mut fmt::Formatter<'_>, context: u8) -> fmt::Result {
+        let precedence = match self {
+            QueryPredicate::Or(..) => 1,
+            QueryPredicate::And(..) => 2,
+            QueryPredicate::Not(_) | QueryPredicate::Class(_) => 3,
+        };
+        if precedence < context {
+            f.write_str("(")?;
+        }
+        match self {
+            // Operators group from the left, so a right
+            // operand of the same precedence needs parentheses.
+            QueryPredicate::Or(a, b) => {
+                a.fmt_with_precedence(f, 1)?;
+                f.write_str(" | ")?;
+                b.fmt_with_precedence(f, 2)?;
+            }
+            QueryPredicate::And(a, b) => {
+                a.fmt_with_precedence(f, 2)?;
+                f.write_str(" & ")?;
+                b.fmt_with_precedence(f, 3)?;
+            }
+            QueryPredicate::Not(a) => {
+                f.write_str("!")?;
+                a.fmt_with_precedence(f, 3)?;
+            }
+            QueryPredicate::Class(c) => f.write_str(c)?,
+        }
+        if precedence < context {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, classes: &[String]) -> bool {
+        match self {
+            QueryPredicate::And(a, b) => a.matches(classes) && b.matches(classes),
+            QueryPredicate::Or(a, b) => a.matches(classes) || b.matches(classes),
+            QueryPredicate::Not(a) => !a.matches(classes),
+            QueryPredicate::Class(c) => classes.iter().any(|class| class == c),
+        }
+    }
+}
+
+fn is_valid_class_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Class(&'a str),
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+    Combinator(Combinator),
+}
+
+impl Token<'_> {
+    fn ends_operand(self) -> bool {
+        matches!(self, Token::Class(_) | Token::CloseParen)
+    }
+
+    fn starts_operand(self) -> bool {
+        matches!(self, Token::Class(_) | Token::Not | Token::OpenParen)
+    }
+}
+
+/// A recursive descent parser for class queries.
+struct Parser<'a> {
+    query: &'a str,
+    /// Tokens along with their byte offsets.
+    tokens: Vec<(usize, Token<'a>)>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(query: &'a str) -> Result<Self, ClassQueryParseError> {
+        let mut parser = Self {
+            query,
+            tokens: Vec::new(),
+            next: 0,
+        };
+        parser.tokenize()?;
+        Ok(parser)
+    }
+
+    fn tokenize(&mut self) -> Result<(), ClassQueryParseError> {
+        let query = self.query;
+        let mut chars = query.char_indices().peekable();
+        let mut after_space = false;
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() {
+                after_space = true;
+                continue;
+            }
+
+            let token = match c {
+                '&' => Token::And,
+                '|' => Token::Or,
+                '!' => Token::Not,
+                '(' => Token::OpenParen,
+                ')' => Token::CloseParen,
+                '>' => Token::Combinator(Combinator::Child),
+                c if is_valid_class_char(c) => {
+                    let mut end = start + c.len_utf8();
+                    while let Some(&(p, c)) = chars.peek() {
+                        if !is_valid_class_char(c) {
+                            break;
+                        }
+                        end = p + c.len_utf8();
+                        chars.next();
+                    }
+                    Token::Class(&query[start..end])
+                }
+                c => return Err(self.error(ParseErrorKind::UnexpectedChar(c), start)),
+            };
+
+            // Operands separated only by whitespace
+            // are joined by the descendant combinator.
+            let follows_operand = matches!(self.tokens.last(), Some((_, t)) if t.ends_operand());
+            if after_space && follows_operand && token.starts_operand() {
+                self.tokens
+                    .push((start, Token::Combinator(Combinator::Descendant)));
+            }
+
+            self.tokens.push((start, token));
+            after_space = false;
+        }
+        Ok(())
+    }
+
+    fn parse_query(&mut self) -> Result<ClassQuery, ClassQueryParseError> {
+        if self.tokens.is_empty() {
+            return Err(self.error(ParseErrorKind::Empty, 0));
+        }
+
+        let mut predicates = vec![self.parse_predicate()?];
+        let mut combinators = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Combinator(combinator) => {
+                    self.next += 1;
+                    combinators.push(combinator);
+                    predicates.push(self.parse_predicate()?);
+                }
+                Token::CloseParen => return Err(self.error_here(ParseErrorKind::UnmatchedParen)),
+                _ => return Err(self.error_here(ParseErrorKind::ExpectedOperator)),
+            }
+        }
+
+        let predicate = predicates.pop().unwrap();
+        let ancestors = predicates
+            .into_iter()
+            .zip(combinators)
+            .rev()
+            .map(|(predicate, combinator)| AncestorSelector {
+                combinator,
+                predicate,
+            })
+            .collect();
+        Ok(ClassQuery {
+            predicate,
+            ancestors,
+        })
+    }
+
+    fn parse_predicate(&mut self) -> Result<QueryPredicate, ClassQueryParseError> {
+        let mut predicate = self.parse_and()?;
+        while self.peek() == Some(Token::Or) {
+            self.next += 1;
+            predicate = QueryPredicate::Or(Box::new(predicate), Box::new(self.parse_and()?));
+        }
+        Ok(predicate)
+    }
+
+    fn parse_and(&mut self) -> Result<QueryPredicate, ClassQueryParseError> {
+        let mut predicate = self.parse_unary()?;
+        while self.peek() == Some(Token::And) {
+            self.next += 1;
+            predicate = QueryPredicate::And(Box::new(predicate), Box::new(self.parse_unary()?));
+        }
+        Ok(predicate)
+    }
+
+    fn parse_unary(&mut self) -> Result<QueryPredicate, ClassQueryParseError> {
+        let (start, token) = match self.tokens.get(self.next) {
+            Some(&token) => token,
+            None => return Err(self.error_here(ParseErrorKind::ExpectedClass)),
+        };
+        match token {
+            Token::Not => {
+                self.next += 1;
+                Ok(QueryPredicate::Not(Box::new(self.parse_unary()?)))
+            }
+            Token::OpenParen => {
+                self.next += 1;
+                let predicate = self.parse_predicate()?;
+                match self.peek() {
+                    Some(Token::CloseParen) => {
+                        self.next += 1;
+                        Ok(predicate)
+                    }
+                    Some(Token::Combinator(_)) => {
+                        Err(self.error_here(ParseErrorKind::CombinatorInGroup))
+                    }
+                    Some(_) => Err(self.error_here(ParseErrorKind::ExpectedOperator)),
+                    None => Err(self.error(ParseErrorKind::UnclosedParen, start)),
+                }
+            }
+            Token::Class(class) => {
+                self.next += 1;
+                Ok(QueryPredicate::Class(class.to_owned()))
+            }
+            _ => Err(self.error_here(ParseErrorKind::ExpectedClass)),
+        }
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.next).map(|&(_, token)| token)
+    }
+
+    /// Creates an error at the next token, or at the end of the query.
+    fn error_here(&self, kind: ParseErrorKind) -> ClassQueryParseError {
+        let offset = self
+            .tokens
+            .get(self.next)
+            .map(|&(offset, _)| offset)
+            .unwrap_or_else(|| self.query.trim_end().len());
+        self.error(kind, offset)
+    }
+
+    fn error(&self, kind: ParseErrorKind, offset: usize) -> ClassQueryParseError {
+        ClassQueryParseError {
+            kind,
+            column: self.query[..offset].chars().count() + 1,
+            query: self.query.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(c: &str) -> QueryPredicate {
+        QueryPredicate::Class(c.to_owned())
+    }
+
+    fn and(a: QueryPredicate, b: QueryPredicate) -> QueryPredicate {
+        QueryPredicate::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: QueryPredicate, b: QueryPredicate) -> QueryPredicate {
+        QueryPredicate::Or(Box::new(a), Box::new(b))
+    }
+
+    fn not(a: QueryPredicate) -> QueryPredicate {
+        QueryPredicate::Not(Box::new(a))
+    }
+
+    fn predicate(query: &str) -> QueryPredicate {
+        let query = ClassQuery::from_str(query).unwrap();
+        assert!(query.ancestors.is_empty());
+        query.predicate
+    }
+
+    #[test]
+    fn simple_class_query() {
+        assert_eq!(predicate("class"), class("class"));
+        assert_eq!(predicate("  text-2_b  "), class("text-2_b"));
+    }
+
+    #[test]
+    fn operators_group_from_the_left() {
+        assert_eq!(predicate("a & b"), and(class("a"), class("b")));
+        assert_eq!(
+            predicate("a&b&c"),
+            and(and(class("a"), class("b")), class("c"))
+        );
+        assert_eq!(
+            predicate("a | b | c"),
+            or(or(class("a"), class("b")), class("c"))
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            predicate("a | b & c"),
+            or(class("a"), and(class("b"), class("c")))
+        );
+        assert_eq!(
+            predicate("a & b | c"),
+            or(and(class("a"), class("b")), class("c"))
+        );
+        assert_eq!(
+            predicate("!a & b | !c"),
+            or(and(not(class("a")), class("b")), not(class("c")))
+        );
+    }
+
+    #[test]
+    fn negation_and_grouping() {
+        assert_eq!(predicate("!!a"), not(not(class("a"))));
+        assert_eq!(predicate("!(a | b)"), not(or(class("a"), class("b"))));
+        assert_eq!(
+            predicate("(a | b) & c"),
+            and(or(class("a"), class("b")), class("c"))
+        );
+        assert_eq!(predicate("((a))"), class("a"));
+        assert_eq!(predicate("( a ) & ! b"), and(class("a"), not(class("b"))));
+    }
+
+    #[test]
+    fn combinators() {
+        let query = ClassQuery::from_str("toolbar > row button & hovered").unwrap();
+        assert_eq!(query.predicate, and(class("button"), class("hovered")));
+        assert_eq!(
+            query.ancestors,
+            vec![
+                AncestorSelector {
+                    combinator: Combinator::Descendant,
+                    predicate: class("row"),
+                },
+                AncestorSelector {
+                    combinator: Combinator::Child,
+                    predicate: class("toolbar"),
+                },
+            ]
+        );
+        assert_eq!(query.specificity(), 4);
+
+        let query = ClassQuery::from_str("(menu | toolbar) !disabled>button").unwrap();
+        assert_eq!(query.predicate, class("button"));
+        assert_eq!(
+            query.ancestors,
+            vec![
+                AncestorSelector {
+                    combinator: Combinator::Child,
+                    predicate: not(class("disabled")),
+                },
+                AncestorSelector {
+                    combinator: Combinator::Descendant,
+                    predicate: or(class("menu"), class("toolbar")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for (query, canonical) in [
+            ("button", "button"),
+            ("a&b&c", "a & b & c"),
+            ("a & (b & c)", "a & (b & c)"),
+            ("(a | b) & !c", "(a | b) & !c"),
+            ("a | b & c", "a | b & c"),
+            ("a | (b | c)", "a | (b | c)"),
+            ("!(a & b)", "!(a & b)"),
+            ("!!a", "!!a"),
+            ("toolbar>row   button & !(hovered)", "toolbar > row button & !hovered"),
+        ] {
+            let parsed = ClassQuery::from_str(query).unwrap();
+            assert_eq!(parsed.to_string(), canonical);
+            assert_eq!(ClassQuery::from_str(canonical).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let error = |query: &str| {
+            let error = ClassQuery::from_str(query).unwrap_err();
+            (error.kind, error.column)
+        };
+
+        assert_eq!(error(""), (ParseErrorKind::Empty, 1));
+        assert_eq!(error("   "), (ParseErrorKind::Empty, 1));
+        assert_eq!(error("a &"), (ParseErrorKind::ExpectedClass, 4));
+        assert_eq!(error("a & | b"), (ParseErrorKind::ExpectedClass, 5));
+        assert_eq!(error("!"), (ParseErrorKind::ExpectedClass, 2));
+        assert_eq!(error("()"), (ParseErrorKind::ExpectedClass, 2));
+        assert_eq!(error("> button"), (ParseErrorKind::ExpectedClass, 1));
+        assert_eq!(error("toolbar >"), (ParseErrorKind::ExpectedClass, 10));
+        assert_eq!(error("a & (b | c"), (ParseErrorKind::UnclosedParen, 5));
+        assert_eq!(error("a & b)"), (ParseErrorKind::UnmatchedParen, 6));
+        assert_eq!(error("(a b)"), (ParseErrorKind::CombinatorInGroup, 4));
+        assert_eq!(error("(a > b)"), (ParseErrorKind::CombinatorInGroup, 4));
+        assert_eq!(error("a(b)"), (ParseErrorKind::ExpectedOperator, 2));
+        assert_eq!(error("(a)b"), (ParseErrorKind::ExpectedOperator, 4));
+        assert_eq!(error("a # b"), (ParseErrorKind::UnexpectedChar('#'), 3));
+        assert_eq!(error("é & #"), (ParseErrorKind::UnexpectedChar('#'), 5));
+
+        assert_eq!(
+            ClassQuery::from_str("a & (b").unwrap_err().to_string(),
+            "unclosed '(' at column 5 of query 'a & (b'"
+        );
+    }
+
+    #[test]
+    fn matching() {
+        let chain = |levels: &[&[&str]]| -> Vec<Vec<String>> {
+            levels
+                .iter()
+                .map(|classes| classes.iter().map(|&c| c.to_owned()).collect())
+                .collect()
+        };
+        let matches = |query: &str, ancestors: &[&[&str]], classes: &[&str]| {
+            let classes: Vec<String> = classes.iter().map(|&c| c.to_owned()).collect();
+            ClassQuery::from_str(query)
+                .unwrap()
+                .matches(&chain(ancestors), &classes)
+        };
+
+        assert!(matches("button & !disabled", &[], &["button"]));
+        assert!(!matches("button & !disabled", &[], &["button", "disabled"]));
+        assert!(matches("!(a | b)", &[], &["c"]));
+        assert!(!matches("!(a | b)", &[], &["b"]));
+
+        assert!(matches(
+            "toolbar button",
+            &[&["toolbar"], &["row"]],
+            &["button"]
+        ));
+        assert!(!matches(
+            "toolbar > button",
+            &[&["toolbar"], &["row"]],
+            &["button"]
+        ));
+        assert!(matches(
+            "toolbar > button",
+            &[&["row"], &["toolbar"]],
+            &["button"]
+        ));
+        assert!(matches(
+            "window toolbar > row button",
+            &[&["window"], &["toolbar"], &["row"]],
+            &["button"]
+        ));
+        assert!(!matches(
+            "toolbar window button",
+            &[&["window"], &["toolbar"]],
+            &["button"]
+        ));
+        assert!(matches(
+            "toolbar | menu > button",
+            &[&["menu"]],
+            &["button"]
+        ));
+        assert!(!matches("toolbar button", &[], &["button"]));
+        assert!(matches("!toolbar > button", &[&["row"]], &["button"]));
+        assert!(!matches("!toolbar > button", &[&["toolbar"]], &["button"]));
+    }
+
+    #[test]
+    fn trigger_classes() {
+        let frequency: AHashMap<&str, usize> =
+            [("a", 1), ("b", 5), ("c", 2)].iter().copied().collect();
+        let triggers = |query: &str| {
+            predicate(query)
+                .trigger_classes(&frequency)
+                .map(|classes| classes.join(" "))
+        };
+
+        assert_eq!(triggers("a & b").as_deref(), Some("a"));
+        assert_eq!(triggers("b & c").as_deref(), Some("c"));
+        assert_eq!(triggers("a | b").as_deref(), Some("a b"));
+        assert_eq!(triggers("!a & b").as_deref(), Some("b"));
+        assert_eq!(triggers("!a | b"), None);
+        assert_eq!(triggers("!a"), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! Loading stylesheets and the sheets they import.
+//!
+//! A sheet lists the sheets it builds on under `import`:
+//!
+//! ```yaml
+//! import:
+//!   - base.yml
+//!   - ../shared/colors.yml
+//! ```
+//!
+//! Imported sheets apply before the importing sheet, in the order listed,
+//! so their variables can be used and their styles overridden. Paths use
+//! `/` as separator and are relative to the directory of the importing
+//! sheet, unless they start with `/`. Sheets added from bytes rather
+//! than loaded from a path import relative to the resolver's root.
+
+use std::{fmt, io, path::PathBuf, rc::Rc};
+
+use super::{SheetSource, StyleError, StyleSheet};
+
+/// Loads the stylesheets that `import` entries and
+/// [`StyleEngine::load_sheet`](super::StyleEngine::load_sheet) refer to.
+///
+/// Implemented for closures, so sheets embedded with
+/// `include_bytes!` can be served from a `match` on the path.
+pub trait StyleResolver: 'static {
+    /// Loads the sheet at `path`, already joined with
+    /// the directory of the importing sheet.
+    fn load(&self, path: &str) -> io::Result<Vec<u8>>;
+}
+
+impl<F> StyleResolver for F
+where
+    F: Fn(&str) -> io::Result<Vec<u8>> + 'static,
+{
+    fn load(&self, path: &str) -> io::Result<Vec<u8>> {
+        self(path)
+    }
+}
+
+/// Loads stylesheets from the file system, relative to a root directory.
+///
+/// The default resolver, rooted at the working directory.
+#[derive(Debug, Clone, Default)]
+pub struct FileResolver {
+    root: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl StyleResolver for FileResolver {
+    fn load(&self, path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(path))
+    }
+}
+
+/// Holds the resolver of a `StyleEngine`.
+pub(super) struct Resolver(Box<dyn StyleResolver>);
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self(Box::new(FileResolver::default()))
+    }
+}
+
+impl Resolver {
+    pub fn set(&mut self, resolver: impl StyleResolver) {
+        self.0 = Box::new(resolver);
+    }
+
+    /// Loads and parses the sheet at `path`, along with its imports.
+    pub fn load(&self, path: &str) -> Result<StyleSheet, StyleError> {
+        let result = self
+            .0
+            .load(path)
+            .map_err(StyleError::from)
+            .and_then(|bytes| self.parse_imports(Some(path), &bytes, &mut vec![path.to_owned()]));
+        result.map_err(|error| StyleError::Sheet {
+            path: path.to_owned(),
+            error: Box::new(error),
+        })
+    }
+
+    /// Parses a sheet that was not loaded from a path, along with its imports.
+    pub fn parse(&self, sheet_bytes: &[u8]) -> Result<StyleSheet, StyleError> {
+        self.parse_imports(None, sheet_bytes, &mut Vec::new())
+    }
+
+    /// `stack` holds the paths of the sheets being
+    /// loaded, to detect import cycles.
+    fn parse_imports(
+        &self,
+        path: Option<&str>,
+        sheet_bytes: &[u8],
+        stack: &mut Vec<String>,
+    ) -> Result<StyleSheet, StyleError> {
+        let source = SheetSource {
+            path: path.map(str::to_owned),
+            text: String::from_utf8_lossy(sheet_bytes).into_owned(),
+        };
+        let mut sheet: StyleSheet = serde_yaml::from_str(&source.text).map_err(|error| {
+            let indexed = source.index();
+            StyleError::Parse {
+                snippet: indexed
+                    .locate_yaml_error(&error)
+                    .map(|location| Box::new(indexed.snippet(location))),
+                error,
+            }
+        })?;
+        sheet.source = Some(Rc::new(source));
+        for import in &sheet.import {
+            let import_path = join_path(path, import);
+            if stack.contains(&import_path) {
+                return Err(StyleError::ImportCycle(import_path));
+            }
+
+            stack.push(import_path.clone());
+            let result = self
+                .0
+                .load(&import_path)
+                .map_err(StyleError::from)
+                .and_then(|bytes| self.parse_imports(Some(&import_path), &bytes, stack));
+            stack.pop();
+
+            sheet
+                .imported
+                .push(result.map_err(|error| StyleError::Sheet {
+                    path: import_path,
+                    error: Box::new(error),
+                })?);
+        }
+        Ok(sheet)
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Resolver")
+    }
+}
+
+/// Joins an import path with the directory of the importing sheet.
+fn join_path(base: Option<&str>, path: &str) -> String {
+    if path.starts_with('/') {
+        return path.to_owned();
+    }
+
+    let mut segments: Vec<&str> = match base.and_then(|base| base.rsplit_once('/')) {
+        Some((dir, _)) => dir.split('/').collect(),
+        None => Vec::new(),
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." if matches!(segments.last(), Some(&last) if last != "..") => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_paths() {
+        assert_eq!(join_path(None, "base.yml"), "base.yml");
+        assert_eq!(join_path(Some("main.yml"), "./base.yml"), "base.yml");
+        assert_eq!(
+            join_path(Some("themes/dark/main.yml"), "../base.yml"),
+            "themes/base.yml"
+        );
+        assert_eq!(
+            join_path(Some("themes/main.yml"), "../../x.yml"),
+            "../x.yml"
+        );
+        assert_eq!(join_path(Some("/usr/main.yml"), "x.yml"), "/usr/x.yml");
+        assert_eq!(join_path(Some("themes/main.yml"), "/x.yml"), "/x.yml");
+    }
+}
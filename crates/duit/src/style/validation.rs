@@ -0,0 +1,271 @@
+//! Checks styles against the style types that widgets deserialize.
+
+use std::{any::TypeId, fmt};
+
+use duit_core::source::Snippet;
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+
+use super::{
+    deserialize_style, expr::is_expression, merge_ordered, precedence_order, DeserializeFn, Style,
+    Styles, OVERRIDE_RULE,
+};
+
+pub(super) type CheckFn = fn(&str) -> Result<(), serde_yaml::Error>;
+
+/// A style type registered for a base class.
+pub(super) struct Registration {
+    pub check: CheckFn,
+    pub type_id: TypeId,
+    /// Creates the style, to fill the cache ahead of time.
+    pub deserialize: DeserializeFn,
+    state_classes: Vec<String>,
+}
+
+impl Registration {
+    pub fn new<S: DeserializeOwned + 'static>(state_classes: &[&str]) -> Self {
+        Self {
+            check: check::<S>,
+            type_id: TypeId::of::<S>(),
+            deserialize: deserialize_style::<S>,
+            state_classes: state_classes.iter().map(|&c| c.to_owned()).collect(),
+        }
+    }
+
+    /// Each set of classes a widget with the base class may have:
+    /// the base class with every combination of the state classes.
+    pub fn class_sets<'a>(&'a self, base_class: &'a str) -> impl Iterator<Item = Vec<String>> + 'a {
+        let states = &self.state_classes;
+        (0..(1usize << states.len())).map(move |mask| {
+            let mut classes = vec![base_class.to_owned()];
+            classes.extend(
+                states
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, class)| class.clone()),
+            );
+            classes
+        })
+    }
+}
+
+impl fmt::Debug for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registration")
+            .field("state_classes", &self.state_classes)
+            .finish()
+    }
+}
+
+pub(super) fn check<S: DeserializeOwned>(yaml: &str) -> Result<(), serde_yaml::Error> {
+    // Deserializing from text, rather than from a `Value`,
+    // makes errors include the path to the offending field.
+    serde_yaml::from_str::<S>(yaml).map(|_| ())
+}
+
+/// A problem found by checking styles against a registered style type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleValidationError {
+    /// The classes of the style that failed to deserialize.
+    pub classes: Vec<String>,
+    /// The path of the offending field, like `font.size`, if known.
+    pub field: Option<String>,
+    /// The rule that set the offending field, if any.
+    pub rule: Option<String>,
+    pub message: String,
+    /// Where the rule sets the field, if known.
+    pub snippet: Option<Snippet>,
+}
+
+impl fmt::Display for StyleValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "style for '{}'", self.classes.join(" & "))?;
+        if let Some(field) = &self.field {
+            write!(f, ", field '{}'", field)?;
+        }
+        if let Some(rule) = &self.rule {
+            write!(f, " (set by rule '{}')", rule)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{}", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for StyleValidationError {}
+
+/// Checks that a style override sets fields to values of the right type.
+///
+/// The rest of the style comes from the rules, so missing fields
+/// are fine. Fields set to expressions are skipped, as they can
+/// only be evaluated when the override is applied.
+pub(super) fn check_override(
+    check: CheckFn,
+    base_class: &str,
+    style_override: &Value,
+) -> Option<StyleValidationError> {
+    let value = without_expressions(style_override.clone());
+    let yaml = serde_yaml::to_string(&value).expect("failed to serialize style override");
+    let error = describe(&check(&yaml).err()?, vec![base_class.to_owned()], &[]);
+    if error.message.starts_with("missing field") {
+        return None;
+    }
+    Some(StyleValidationError {
+        rule: Some(OVERRIDE_RULE.to_owned()),
+        ..error
+    })
+}
+
+fn without_expressions(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .filter(|(_, value)| !matches!(value, Value::String(s) if is_expression(s)))
+                .map(|(key, value)| (key, without_expressions(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Checks the style for a base class with every
+/// combination of the registered state classes.
+///
+/// Class sets that no style applies to are skipped, so that
+/// sheets need not style widgets the application does not use.
+pub(super) fn validate(
+    styles: &Styles,
+    base_class: &str,
+    registration: &Registration,
+) -> Vec<StyleValidationError> {
+    validate_as(styles, base_class, registration, registration)
+}
+
+/// Checks the class sets of a registered base class against
+/// another style type, like one that every widget reads.
+pub(super) fn validate_as(
+    styles: &Styles,
+    base_class: &str,
+    registration: &Registration,
+    style_type: &Registration,
+) -> Vec<StyleValidationError> {
+    let mut errors: Vec<StyleValidationError> = Vec::new();
+    for classes in registration.class_sets(base_class) {
+        let matches = precedence_order(styles.matching_styles(&[], &classes));
+        if matches.is_empty() {
+            continue;
+        }
+        let yaml =
+            serde_yaml::to_string(&merge_ordered(&matches)).expect("failed to serialize style");
+        if let Err(e) = (style_type.check)(&yaml) {
+            let error = describe(&e, classes.clone(), &matches);
+            // A broken rule breaks every class set it applies to;
+            // report it only for the first.
+            let seen = errors.iter().any(|other| {
+                other.field == error.field
+                    && other.rule == error.rule
+                    && other.message == error.message
+            });
+            if !seen {
+                errors.push(error);
+            }
+        }
+    }
+    errors
+}
+
+fn describe(
+    error: &serde_yaml::Error,
+    classes: Vec<String>,
+    matches: &[&Style],
+) -> StyleValidationError {
+    let mut message = error.to_string();
+    if let Some(location) = error.location() {
+        // The location points into the merged style,
+        // which the user never sees.
+        let suffix = format!(" at line {} column {}", location.line(), location.column());
+        if let Some(stripped) = message.strip_suffix(&suffix) {
+            message = stripped.to_owned();
+        }
+    }
+
+    let mut field = None;
+    if let Some((path, rest)) = message.split_once(": ") {
+        if is_path(path) {
+            field = Some(path.to_owned());
+            message = rest.to_owned();
+        }
+    }
+    if let Some(missing) = message
+        .strip_prefix("missing field `")
+        .and_then(|m| m.strip_suffix('`'))
+    {
+        field = Some(match field {
+            Some(path) => format!("{}.{}", path, missing),
+            None => missing.to_owned(),
+        });
+    }
+
+    let path: Vec<&str> = field
+        .as_deref()
+        .map(|field| {
+            field
+                .split('.')
+                .map(|segment| segment.split('[').next().unwrap_or(segment))
+                .collect()
+        })
+        .unwrap_or_default();
+    let (rule, snippet) = match rule_for_field(matches, &path) {
+        Some(style) => (Some(style.query.to_string()), style.snippet(&path)),
+        None => (None, None),
+    };
+    StyleValidationError {
+        classes,
+        field,
+        rule,
+        message,
+        snippet,
+    }
+}
+
+fn is_path(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '[' | ']'))
+}
+
+/// Finds the rule whose value for the field at `path`, or for
+/// the nearest enclosing field, ends up in the merged style.
+fn rule_for_field<'a>(matches: &[&'a Style], path: &[&str]) -> Option<&'a Style> {
+    (1..=path.len()).rev().find_map(|len| {
+        let path = &path[..len];
+        // Important values are merged last, so they win.
+        matches
+            .iter()
+            .rev()
+            .find(|style| matches!(&style.important, Some(important) if has_path(important, path)))
+            .or_else(|| {
+                matches
+                    .iter()
+                    .rev()
+                    .find(|style| has_path(&style.value, path))
+            })
+            .copied()
+    })
+}
+
+fn has_path(value: &Value, path: &[&str]) -> bool {
+    match path.split_first() {
+        None => true,
+        Some((key, rest)) => match value {
+            Value::Mapping(mapping) => {
+                matches!(mapping.get(&Value::from(*key)), Some(value) if has_path(value, rest))
+            }
+            _ => false,
+        },
+    }
+}
@@ -2,19 +2,24 @@ use std::{any::Any, cell::RefCell, collections::VecDeque, rc::Rc};
 
 use ahash::AHashMap;
 use duit_core::{
-    spec::{self, Spec},
+    spec::{self, Spec, ValidationError},
     Axis,
 };
-use dume::Canvas;
+use dume::{Canvas, Rect};
 use glam::Vec2;
+use serde::{de::DeserializeOwned, Serialize};
 use slotmap::SlotMap;
 use winit::event::WindowEvent;
 
 use crate::{
-    event::EventTracker,
+    native_window::{NativeWindow, NativeWindowId, NativeWindowOptions, NativeWindowRequest},
     spec::InstanceHandle,
-    style::{StyleEngine, StyleError},
-    widget::{DynWidget, HitTestResult, WidgetPod, WidgetPodHandle},
+    style::{StyleEngine, StyleError, StyleResolver},
+    widget::{
+        check_style_override, BaseStyle, DynWidget, HitTestResult, WidgetPod, WidgetPodHandle,
+        CLASS_DISABLED, CLASS_EVEN, CLASS_FIRST, CLASS_FOCUSED, CLASS_HOVERED, CLASS_LAST,
+        CLASS_ODD, CLASS_PRESSED, CLASS_SELECTED,
+    },
     widgets,
     window::{Window, WindowPositioner},
     Event, Widget,
@@ -24,47 +29,238 @@ slotmap::new_key_type! {
     pub struct WindowId;
 }
 
+/// The name of the built-in theme.
+const DEFAULT_THEME: &str = "default";
+
 /// Contains the entire UI state, including all windows and their widget trees.
-#[derive(Default)]
+///
+/// A `Ui` starts out with a single native window, the main window.
+/// More can be opened with [`Ui::open_native_window`].
 pub struct Ui {
     windows: SlotMap<WindowId, Window>,
     sorted_windows: Vec<WindowId>,
+    native_windows: SlotMap<NativeWindowId, NativeWindow>,
+    main_native_window: NativeWindowId,
+    native_window_requests: Vec<NativeWindowRequest>,
     specs: AHashMap<String, Spec>,
     style_engine: StyleEngine,
-    event_tracker: EventTracker,
     messages: VecDeque<Box<dyn Any>>,
 
-    custom_widget_builders: AHashMap<String, Box<dyn Fn(&serde_yaml::Value) -> Box<dyn DynWidget>>>,
+    custom_widget_builders: AHashMap<String, CustomWidget>,
+}
+
+/// A widget added with [`Ui::add_custom_widget`].
+struct CustomWidget {
+    build: Box<dyn Fn(&serde_yaml::Value) -> Box<dyn DynWidget>>,
+    /// Checks a style override against the widget's style type.
+    check_style_override: fn(&str, &serde_yaml::Value) -> Result<(), StyleError>,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        let mut native_windows = SlotMap::default();
+        let main_native_window =
+            native_windows.insert(NativeWindow::new(NativeWindowOptions::default()));
+        Self {
+            windows: SlotMap::default(),
+            sorted_windows: Vec::new(),
+            native_windows,
+            main_native_window,
+            native_window_requests: Vec::new(),
+            specs: AHashMap::default(),
+            style_engine: builtin_style_engine(),
+            messages: VecDeque::new(),
+            custom_widget_builders: AHashMap::default(),
+        }
+    }
 }
 
 impl Ui {
+    /// Creates a `Ui` using the built-in theme, named `"default"`.
     pub fn new() -> Self {
-        let mut u = Self::default();
-        u.add_stylesheet(include_bytes!("../../../themes/default.yml"))
+        let mut u = Self::without_default_theme();
+        u.add_theme(DEFAULT_THEME, include_bytes!("../../../themes/default.yml"))
+            .and_then(|u| u.set_theme(DEFAULT_THEME))
             .expect("invalid default theme");
         u
     }
 
-    pub fn add_spec(&mut self, spec: Spec) -> &mut Self {
+    /// Creates a `Ui` without any theme, for applications
+    /// that provide all of their styles.
+    pub fn without_default_theme() -> Self {
+        Self::default()
+    }
+
+    /// Adds a spec, after validating it like [`Spec::deserialize_from_str`]
+    /// does and checking the `style` overrides of its widgets against
+    /// their style types.
+    ///
+    /// Overrides on custom widgets are checked once the
+    /// widget is added with [`Ui::add_custom_widget`].
+    pub fn add_spec(&mut self, spec: Spec) -> Result<&mut Self, AddSpecError> {
+        spec.validate()?;
+        check_style_overrides(self, &spec.child)?;
         self.specs.insert(spec.name.clone(), spec);
-        self
+        Ok(self)
+    }
+
+    /// Registers the style type of a custom widget, so that stylesheets
+    /// are checked against it as they are added. See [`StyleEngine::register_style`].
+    ///
+    /// Built-in widgets are registered automatically.
+    pub fn register_style<W: Widget>(
+        &mut self,
+        base_class: &str,
+        state_classes: &[&str],
+    ) -> Result<&mut Self, StyleError> {
+        self.style_engine
+            .register_style::<W::Style>(base_class, state_classes)?;
+        Ok(self)
     }
 
     pub fn add_stylesheet(&mut self, stylesheet_bytes: &[u8]) -> Result<&mut Self, StyleError> {
         self.style_engine.append_sheet(stylesheet_bytes)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Adds a stylesheet that can later be replaced or removed by name.
+    ///
+    /// If a stylesheet with the same name exists, it is replaced in place.
+    pub fn add_named_stylesheet(
+        &mut self,
+        name: &str,
+        stylesheet_bytes: &[u8],
+    ) -> Result<&mut Self, StyleError> {
+        self.style_engine.add_named_sheet(name, stylesheet_bytes)?;
+        self.restyle();
         Ok(self)
     }
 
+    /// Loads a stylesheet through the style resolver, named by its path.
+    /// Loading the same path again reloads the sheet in place.
+    pub fn load_stylesheet(&mut self, path: &str) -> Result<&mut Self, StyleError> {
+        self.style_engine.load_sheet(path)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Sets how stylesheets are loaded by path, including their imports.
+    /// Defaults to reading files relative to the working directory.
+    pub fn set_style_resolver(&mut self, resolver: impl StyleResolver) -> &mut Self {
+        self.style_engine.set_resolver(resolver);
+        self
+    }
+
+    pub fn remove_stylesheet(&mut self, name: &str) -> Result<&mut Self, StyleError> {
+        self.style_engine.remove_sheet(name)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Registers a theme. Themes lie beneath all stylesheets,
+    /// and only the one selected with [`Ui::set_theme`] applies.
+    ///
+    /// The built-in theme is named `"default"`.
+    pub fn add_theme(&mut self, name: &str, theme_bytes: &[u8]) -> Result<&mut Self, StyleError> {
+        self.style_engine.add_theme(name, theme_bytes)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Loads a theme through the style resolver. See [`Ui::add_theme`].
+    pub fn load_theme(&mut self, name: &str, path: &str) -> Result<&mut Self, StyleError> {
+        self.style_engine.load_theme(name, path)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Switches to a theme registered with [`Ui::add_theme`].
+    ///
+    /// Live widgets pick up the new styles on the next render.
+    pub fn set_theme(&mut self, name: &str) -> Result<&mut Self, StyleError> {
+        self.style_engine.set_theme(name)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Applies several themes in order, like a base theme
+    /// followed by themes that adjust it.
+    ///
+    /// Live widgets pick up the new styles on the next render.
+    pub fn set_theme_layers(&mut self, names: &[&str]) -> Result<&mut Self, StyleError> {
+        self.style_engine.set_theme_layers(names)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Sets a style variable, like `$emphasis`, overriding its value
+    /// in every theme and stylesheet.
+    ///
+    /// Live widgets pick up the new styles on the next render.
+    pub fn set_style_variable(
+        &mut self,
+        name: &str,
+        value: impl Serialize,
+    ) -> Result<&mut Self, StyleError> {
+        let value = serde_yaml::to_value(value)?;
+        self.style_engine.set_variable(name, value)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Removes a variable set with [`Ui::set_style_variable`],
+    /// so that its value from themes and stylesheets applies again.
+    pub fn remove_style_variable(&mut self, name: &str) -> Result<&mut Self, StyleError> {
+        self.style_engine.remove_variable(name)?;
+        self.restyle();
+        Ok(self)
+    }
+
+    /// Returns the name of the current theme, or its topmost layer.
+    pub fn theme(&self) -> Option<&str> {
+        self.style_engine.theme()
+    }
+
+    /// Causes all widgets to be notified of style changes before the next render.
+    fn restyle(&mut self) {
+        for window in self.windows.values_mut() {
+            window.needs_restyle = true;
+        }
+    }
+
+    /// Adds a widget that specs create with `Custom: {type: <name>}`.
+    ///
+    /// Fails, without adding the widget, if a spec that was already
+    /// added overrides its style with properties that do not fit its
+    /// style type.
     pub fn add_custom_widget<W: Widget>(
         &mut self,
         name: &str,
         builder: impl Fn(&serde_yaml::Value) -> W + 'static,
-    ) -> &mut Self {
-        self.custom_widget_builders.insert(
-            name.to_owned(),
-            Box::new(move |params| Box::new(builder(params))),
-        );
-        self
+    ) -> Result<&mut Self, StyleError> {
+        let custom_widget = CustomWidget {
+            build: Box::new(move |params| Box::new(builder(params))),
+            check_style_override: check_style_override::<W::Style>,
+        };
+        let previous = self
+            .custom_widget_builders
+            .insert(name.to_owned(), custom_widget);
+
+        let checked = self
+            .specs
+            .values()
+            .try_for_each(|spec| check_style_overrides(self, &spec.child));
+        if let Err(error) = checked {
+            match previous {
+                Some(previous) => self
+                    .custom_widget_builders
+                    .insert(name.to_owned(), previous),
+                None => self.custom_widget_builders.remove(name),
+            };
+            return Err(error);
+        }
+        Ok(self)
     }
 
     pub fn create_spec_instance<S: InstanceHandle>(&mut self) -> (S, WidgetPodHandle) {
@@ -82,13 +278,33 @@ impl Ui {
         (spec_handle, root)
     }
 
+    /// Creates a window in the main native window.
+    ///
+    /// Panics if the main native window was closed.
     pub fn create_window(
         &mut self,
         root: WidgetPodHandle,
         positioner: impl WindowPositioner,
         z_index: u64,
     ) -> WindowId {
-        let id = self.windows.insert(Window::new(root, positioner, z_index));
+        self.create_window_in(self.main_native_window, root, positioner, z_index)
+    }
+
+    /// Creates a window in the given native window.
+    pub fn create_window_in(
+        &mut self,
+        native_window: NativeWindowId,
+        root: WidgetPodHandle,
+        positioner: impl WindowPositioner,
+        z_index: u64,
+    ) -> WindowId {
+        assert!(
+            self.native_windows.contains_key(native_window),
+            "native window does not exist"
+        );
+        let id = self
+            .windows
+            .insert(Window::new(root, positioner, z_index, native_window));
         self.sorted_windows.push(id);
         self.sort_windows();
         id
@@ -102,47 +318,183 @@ impl Ui {
         self.windows.remove(id);
     }
 
+    /// Returns the native window the `Ui` was created with.
+    pub fn main_native_window(&self) -> NativeWindowId {
+        self.main_native_window
+    }
+
+    /// Requests a new native window from the platform.
+    ///
+    /// Windows can be added to it with [`Ui::create_window_in`] right away.
+    pub fn open_native_window(&mut self, options: NativeWindowOptions) -> NativeWindowId {
+        let id = self
+            .native_windows
+            .insert(NativeWindow::new(options.clone()));
+        self.native_window_requests
+            .push(NativeWindowRequest::Open(id, options));
+        id
+    }
+
+    /// Closes a native window along with all windows inside it.
+    ///
+    /// Closing the main native window usually exits the application.
+    pub fn close_native_window(&mut self, id: NativeWindowId) {
+        if self.native_windows.remove(id).is_some() {
+            self.windows.retain(|_, window| window.native != id);
+            self.sort_windows();
+            self.native_window_requests
+                .push(NativeWindowRequest::Close(id));
+        }
+    }
+
+    pub fn native_window(&self, id: NativeWindowId) -> Option<&NativeWindow> {
+        self.native_windows.get(id)
+    }
+
+    pub fn native_windows(&self) -> impl Iterator<Item = NativeWindowId> + '_ {
+        self.native_windows.keys()
+    }
+
+    /// Drains the native windows opened and closed since the last call.
+    ///
+    /// Called by the platform, which should create or destroy
+    /// the corresponding operating system windows.
+    pub fn take_native_window_requests(&mut self) -> Vec<NativeWindowRequest> {
+        std::mem::take(&mut self.native_window_requests)
+    }
+
+    /// Updates the size and scale factor of a native window.
+    ///
+    /// Called by the platform whenever they change.
+    pub fn set_native_window_size(
+        &mut self,
+        id: NativeWindowId,
+        logical_size: Vec2,
+        scale_factor: f64,
+    ) {
+        if let Some(native_window) = self.native_windows.get_mut(id) {
+            native_window.set_size(logical_size, scale_factor);
+        }
+    }
+
+    /// Renders the main native window.
+    ///
+    /// Does nothing if the main native window was closed.
     pub fn render(&mut self, canvas: &mut Canvas, window_logical_size: Vec2) {
-        for id in &self.sorted_windows {
-            if let Some(window) = self.windows.get_mut(*id) {
+        let main = self.main_native_window;
+        let scale_factor = match self.native_windows.get(main) {
+            Some(native_window) => native_window.scale_factor(),
+            None => return,
+        };
+        self.set_native_window_size(main, window_logical_size, scale_factor);
+        self.render_native_window(main, canvas);
+    }
+
+    /// Renders the windows inside a native window to its canvas.
+    pub fn render_native_window(&mut self, id: NativeWindowId, canvas: &mut Canvas) {
+        let native_window = match self.native_windows.get_mut(id) {
+            Some(w) => w,
+            None => return,
+        };
+        native_window.ime_caret = None;
+        let available_space = native_window.logical_size();
+        for window_id in &self.sorted_windows {
+            if let Some(window) = self.windows.get_mut(*window_id) {
+                if window.native != id {
+                    continue;
+                }
                 window.render(
                     canvas,
                     &mut self.style_engine,
                     &mut self.messages,
-                    window_logical_size,
+                    &mut native_window.ime_caret,
+                    available_space,
                 );
             }
         }
     }
 
+    /// Converts an event received by the main native window.
     pub fn convert_event(
         &mut self,
         event: &WindowEvent,
         window_scale_factor: f64,
     ) -> Option<Event> {
-        self.event_tracker.handle_event(event, window_scale_factor)
+        self.native_windows
+            .get_mut(self.main_native_window)?
+            .event_tracker
+            .handle_event(event, window_scale_factor)
     }
 
+    /// Converts an event received by a native window.
+    pub fn convert_native_window_event(
+        &mut self,
+        id: NativeWindowId,
+        event: &WindowEvent,
+    ) -> Option<Event> {
+        let native_window = self.native_windows.get_mut(id)?;
+        let scale_factor = native_window.scale_factor();
+        native_window
+            .event_tracker
+            .handle_event(event, scale_factor)
+    }
+
+    /// Delivers an event to the windows in the main native window.
     pub fn handle_window_event(
         &mut self,
         canvas: &mut Canvas,
         event: &Event,
         window_logical_size: Vec2,
+    ) -> HitTestResult {
+        let main = self.main_native_window;
+        let scale_factor = match self.native_windows.get(main) {
+            Some(native_window) => native_window.scale_factor(),
+            None => return HitTestResult::Missed,
+        };
+        self.set_native_window_size(main, window_logical_size, scale_factor);
+        self.handle_native_window_event(main, canvas, event)
+    }
+
+    /// Delivers an event to the windows in a native window.
+    pub fn handle_native_window_event(
+        &mut self,
+        id: NativeWindowId,
+        canvas: &mut Canvas,
+        event: &Event,
     ) -> HitTestResult {
         let mut result = HitTestResult::Missed;
+        let native_window = match self.native_windows.get_mut(id) {
+            Some(w) => w,
+            None => return result,
+        };
+        let available_space = native_window.logical_size();
         for (_, window) in &mut self.windows {
+            if window.native != id {
+                continue;
+            }
             result = result
                 | window.handle_event(
                     canvas,
                     &mut self.style_engine,
                     &mut self.messages,
-                    &event,
-                    window_logical_size,
+                    &mut native_window.ime_caret,
+                    event,
+                    available_space,
                 );
         }
         result
     }
 
+    /// Gets the text caret reported by the focused text widget
+    /// during the last call to `render`, in logical window coordinates.
+    ///
+    /// Used to position the IME candidate window.
+    pub fn ime_caret(&self) -> Option<Rect> {
+        self.native_windows
+            .get(self.main_native_window)?
+            .ime_caret()
+    }
+
     /// Invokes `callback` on all messages with a given type.
     /// Drains the messages.
     ///
@@ -180,18 +532,76 @@ impl Ui {
     }
 }
 
-fn instantiate_widget(
-    ui: &Ui,
-    spec_widget: &spec::Widget,
-    widgets_with_ids: &mut Vec<(String, WidgetPodHandle)>,
-) -> WidgetPodHandle {
-    let widget: Box<dyn DynWidget> = match spec_widget {
+/// An error adding a spec with [`Ui::add_spec`].
+#[derive(Debug, thiserror::Error)]
+pub enum AddSpecError {
+    #[error(transparent)]
+    Invalid(#[from] ValidationError),
+    #[error(transparent)]
+    Style(#[from] StyleError),
+}
+
+/// Checks the style overrides of a widget and its descendants.
+fn check_style_overrides(ui: &Ui, spec_widget: &spec::Widget) -> Result<(), StyleError> {
+    if let Some(style_override) = spec_widget.base_spec().and_then(|base| base.style.as_ref()) {
+        let base_class = base_class(spec_widget);
+        match spec_widget {
+            // Custom widgets that are not added yet are checked when they are.
+            spec::Widget::Custom(spec) => {
+                if let Some(custom_widget) = ui.custom_widget_builders.get(&spec.typ) {
+                    (custom_widget.check_style_override)(base_class, style_override)?;
+                }
+            }
+            _ => ui.style_engine.check_override(base_class, style_override)?,
+        }
+    }
+
+    spec_children(spec_widget).try_for_each(|child| check_style_overrides(ui, child))
+}
+
+/// The children of a widget, including those of a `Tooltip`,
+/// which cannot be represented as a slice.
+fn spec_children(spec_widget: &spec::Widget) -> impl Iterator<Item = &spec::Widget> {
+    let (child, tooltip) = match spec_widget {
+        spec::Widget::Tooltip(s) => (s.child.as_deref(), s.tooltip.as_deref()),
+        _ => (None, None),
+    };
+    spec_widget.children().iter().chain(child).chain(tooltip)
+}
+
+/// The base class of the widget a spec creates.
+/// Custom widgets are named by their type instead.
+fn base_class(spec_widget: &spec::Widget) -> &str {
+    match spec_widget {
+        spec::Widget::Column(_) | spec::Widget::Row(_) => "flex",
+        spec::Widget::Text(_) => "text",
+        spec::Widget::TextInput(_) => "text_input",
+        spec::Widget::TextArea(_) => "text_area",
+        spec::Widget::Button(_) => "button",
+        spec::Widget::Image(_) => "image",
+        spec::Widget::Container(_) => "container",
+        spec::Widget::ProgressBar(_) => "progress_bar",
+        spec::Widget::Clickable(_) => "clickable",
+        spec::Widget::Slider(_) => "slider",
+        spec::Widget::Table(_) => "table",
+        spec::Widget::Divider(_) => "divider",
+        spec::Widget::Scrollable(_) => "scrollable",
+        spec::Widget::PickList(_) => "pick_list",
+        spec::Widget::Tooltip(_) => "tooltip",
+        spec::Widget::Custom(spec) => &spec.typ,
+    }
+}
+
+/// Creates the widget for a spec, without its children.
+fn create_widget(ui: &Ui, spec_widget: &spec::Widget) -> Box<dyn DynWidget> {
+    match spec_widget {
         spec::Widget::Column(spec) => {
             Box::new(widgets::Flex::from_spec(&spec.flex, Axis::Vertical))
         }
         spec::Widget::Row(spec) => Box::new(widgets::Flex::from_spec(&spec.flex, Axis::Horizontal)),
         spec::Widget::Text(spec) => Box::new(widgets::Text::from_spec(spec)),
         spec::Widget::TextInput(spec) => Box::new(widgets::TextInput::from_spec(spec)),
+        spec::Widget::TextArea(spec) => Box::new(widgets::TextArea::from_spec(spec)),
         spec::Widget::Button(spec) => Box::new(widgets::Button::from_spec(spec)),
         spec::Widget::Image(spec) => Box::new(widgets::Image::from_spec(spec)),
         spec::Widget::Container(spec) => Box::new(widgets::Container::from_spec(spec)),
@@ -203,13 +613,22 @@ fn instantiate_widget(
         spec::Widget::Scrollable(spec) => Box::new(widgets::Scrollable::from_spec(spec)),
         spec::Widget::PickList(spec) => Box::new(widgets::PickList::from_spec(spec)),
         spec::Widget::Tooltip(_spec) => Box::new(widgets::Tooltip::new()),
-        spec::Widget::Custom(spec) => ui
-            .custom_widget_builders
-            .get(&spec.typ)
-            .unwrap_or_else(|| panic!("missing custom widget '{}'", spec.typ))(
-            &spec.params
-        ),
-    };
+        spec::Widget::Custom(spec) => {
+            let custom_widget = ui
+                .custom_widget_builders
+                .get(&spec.typ)
+                .unwrap_or_else(|| panic!("missing custom widget '{}'", spec.typ));
+            (custom_widget.build)(&spec.params)
+        }
+    }
+}
+
+fn instantiate_widget(
+    ui: &Ui,
+    spec_widget: &spec::Widget,
+    widgets_with_ids: &mut Vec<(String, WidgetPodHandle)>,
+) -> WidgetPodHandle {
+    let widget = create_widget(ui, spec_widget);
 
     let mut pod = WidgetPod::new(widget);
 
@@ -219,30 +638,22 @@ fn instantiate_widget(
         for class in &base.classes {
             pod.data_mut().add_class(class);
         }
+
+        if let Some(style_override) = &base.style {
+            pod.data_mut()
+                .set_style_override(Some(style_override.clone()));
+        }
     }
 
     // Ensure `Widget::style_updated` isn't called for initialization.
     pod.data_mut().mark_classes_clean();
 
     // Inflate children recursively.
-    let children = spec_widget.children();
-
-    for child in children {
+    for child in spec_children(spec_widget) {
         let child = instantiate_widget(ui, child, widgets_with_ids);
         pod.data_mut().add_child(child);
     }
 
-    // Special case for Tooltip, because its children
-    // cannot be represented as a slice
-    if let spec::Widget::Tooltip(s) = spec_widget {
-        for child in [&s.child, &s.tooltip] {
-            if let Some(child) = child {
-                let child = instantiate_widget(ui, child, widgets_with_ids);
-                pod.data_mut().add_child(child);
-            }
-        }
-    }
-
     pod.mount();
 
     let handle = Rc::new(RefCell::new(pod));
@@ -253,3 +664,180 @@ fn instantiate_widget(
 
     handle
 }
+
+/// Creates a style engine with the style types
+/// of the built-in widgets registered.
+fn builtin_style_engine() -> StyleEngine {
+    use widgets::*;
+
+    fn register<S: DeserializeOwned + 'static>(
+        engine: &mut StyleEngine,
+        base_class: &str,
+        states: &[&str],
+    ) {
+        engine
+            .register_style::<S>(base_class, states)
+            .expect("there are no styles to check yet");
+    }
+
+    let mut engine = StyleEngine::default();
+    engine
+        .register_base_style::<Option<BaseStyle>>()
+        .expect("there are no styles to check yet");
+    let e = &mut engine;
+    register::<button::Style>(e, "button", &[CLASS_HOVERED, CLASS_PRESSED, CLASS_DISABLED]);
+    register::<()>(e, "clickable", &[]);
+    register::<container::Style>(e, "container", &[]);
+    register::<divider::Style>(e, "divider", &[]);
+    register::<()>(e, "flex", &[]);
+    register::<()>(e, "image", &[]);
+    register::<pick_list::Style>(e, "pick_list", &[CLASS_HOVERED]);
+    register::<pick_list::OptionStyle>(e, "pick_list_option", &[CLASS_HOVERED, CLASS_SELECTED]);
+    register::<progress_bar::Style>(e, "progress_bar", &[]);
+    register::<scrollable::Style>(e, "scrollable", &[]);
+    register::<slider::Style>(e, "slider", &[CLASS_HOVERED, "grabbed"]);
+    register::<table::Style>(e, "table", &[]);
+    register::<table::RowStyle>(
+        e,
+        "table_row",
+        &[CLASS_FIRST, CLASS_LAST, CLASS_ODD, CLASS_EVEN],
+    );
+    register::<text::Style>(e, "text", &[CLASS_HOVERED]);
+    register::<text_area::Style>(
+        e,
+        "text_area",
+        &[CLASS_FOCUSED, CLASS_HOVERED, CLASS_DISABLED],
+    );
+    register::<text_input::Style>(
+        e,
+        "text_input",
+        &[
+            CLASS_FOCUSED,
+            text_input::CLASS_INVALID,
+            CLASS_HOVERED,
+            CLASS_DISABLED,
+        ],
+    );
+    register::<tooltip::Style>(e, "tooltip", &[]);
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FillPositioner;
+
+    impl WindowPositioner for FillPositioner {
+        fn compute_position(&self, available_space: Vec2) -> Rect {
+            Rect::new(Vec2::ZERO, available_space)
+        }
+    }
+
+    #[test]
+    fn spec_style_overrides() {
+        let spec = |style: &str| {
+            Spec::deserialize_from_str(&format!(
+                "name: Overrides\nchild:\n  Tooltip:\n    tooltip:\n      Container:\n        mode: Shrink\n        style: {}\n",
+                style
+            ))
+            .unwrap()
+        };
+        let mut ui = Ui::new();
+        assert!(ui.add_spec(spec("{border_radius: 3}")).is_ok());
+        assert!(matches!(
+            ui.add_spec(spec("{border_radius: round}")),
+            Err(AddSpecError::Style(StyleError::Invalid(errors)))
+                if errors[0].field.as_deref() == Some("border_radius")
+        ));
+    }
+
+    #[test]
+    fn base_style_validation() {
+        let mut ui = Ui::new();
+        assert!(matches!(
+            ui.add_stylesheet(b"styles: {button: {margin: [1, 2, 3]}}"),
+            Err(StyleError::Invalid(_))
+        ));
+
+        let spec = Spec::deserialize_from_str(
+            "name: Margin\nchild:\n  Container:\n    mode: Shrink\n    style: {margin: [1, 2, 3]}\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            ui.add_spec(spec),
+            Err(AddSpecError::Style(StyleError::Invalid(_)))
+        ));
+    }
+
+    #[test]
+    fn custom_widget_style_overrides() {
+        let spec = Spec::deserialize_from_str(
+            "name: Popups\nchild:\n  Custom:\n    type: Popup\n    params: {}\n    style: {border_radius: round}\n",
+        )
+        .unwrap();
+        let mut ui = Ui::new();
+        // The override is checked once the widget is added.
+        assert!(ui.add_spec(spec).is_ok());
+        assert!(matches!(
+            ui.add_custom_widget("Popup", |_| widgets::Tooltip::new()),
+            Err(StyleError::Invalid(_))
+        ));
+        assert!(!ui.custom_widget_builders.contains_key("Popup"));
+    }
+
+    #[test]
+    fn specs_are_validated() {
+        // Specs built without `Spec::deserialize_from_str` skip its validation.
+        let spec: Spec = serde_yaml::from_str(
+            "name: Filter\nchild:\n  TextInput:\n    filter:\n      Regex: \"(\"\n",
+        )
+        .unwrap();
+        let mut ui = Ui::new();
+        assert!(matches!(
+            ui.add_spec(spec),
+            Err(AddSpecError::Invalid(ValidationError::InvalidRegex(..)))
+        ));
+    }
+
+    #[test]
+    fn native_windows() {
+        let mut ui = Ui::new();
+        let main = ui.main_native_window();
+        assert!(ui.take_native_window_requests().is_empty());
+
+        let palette = ui.open_native_window(NativeWindowOptions {
+            title: "Palette".to_owned(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            ui.take_native_window_requests().as_slice(),
+            [NativeWindowRequest::Open(id, options)] if *id == palette && options.title == "Palette"
+        ));
+        assert_eq!(ui.native_windows().count(), 2);
+
+        ui.create_window(crate::widget(widgets::Tooltip::new()), FillPositioner, 1);
+        let window = ui.create_window_in(
+            palette,
+            crate::widget(widgets::Tooltip::new()),
+            FillPositioner,
+            1,
+        );
+        assert_eq!(ui.windows[window].native, palette);
+
+        ui.close_native_window(palette);
+        assert!(matches!(
+            ui.take_native_window_requests().as_slice(),
+            [NativeWindowRequest::Close(id)] if *id == palette
+        ));
+        assert!(ui.native_window(palette).is_none());
+        assert!(!ui.windows.contains_key(window));
+        assert_eq!(ui.windows.len(), 1);
+        assert!(ui.native_window(main).is_some());
+
+        ui.close_native_window(main);
+        assert!(ui.ime_caret().is_none());
+        let event = WindowEvent::Focused(true);
+        assert!(ui.convert_event(&event, 1.).is_none());
+    }
+}
@@ -9,9 +9,13 @@ use std::{
 
 use dume::{Canvas, Rect};
 use glam::Vec2;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
 
-use crate::{style::StyleEngine, Event};
+use crate::{
+    style::{self, StyleEngine},
+    Event, Insets, InsetsExt, RectExt, StyleError,
+};
 
 pub type WidgetPodHandle = Rc<RefCell<WidgetPod>>;
 
@@ -20,6 +24,35 @@ pub type WidgetPodHandle = Rc<RefCell<WidgetPod>>;
 pub const CLASS_HOVERED: &str = "hovered";
 pub const CLASS_PRESSED: &str = "pressed";
 
+// Standard state classes, kept in sync with the
+// corresponding flags in `WidgetState`.
+pub const CLASS_DISABLED: &str = "disabled";
+pub const CLASS_CHECKED: &str = "checked";
+pub const CLASS_SELECTED: &str = "selected";
+pub const CLASS_FOCUSED: &str = "focused";
+
+// Structural classes given to each child of a container that
+// opts in with `WidgetData::enable_structural_classes`, based on
+// its position among its siblings. Positions count from 1,
+// so the first child is `odd`.
+pub const CLASS_FIRST: &str = "first";
+pub const CLASS_LAST: &str = "last";
+pub const CLASS_ODD: &str = "odd";
+pub const CLASS_EVEN: &str = "even";
+
+/// Each structural class, with whether it applies to
+/// an element at `index` among `count` siblings.
+pub(crate) fn structural_classes(index: usize, count: usize) -> [(&'static str, bool); 4] {
+    // Positions count from 1, so even indices are odd positions.
+    let odd = (index & 1) == 0;
+    [
+        (CLASS_FIRST, index == 0),
+        (CLASS_LAST, index + 1 == count),
+        (CLASS_ODD, odd),
+        (CLASS_EVEN, !odd),
+    ]
+}
+
 pub struct WidgetHandle<T> {
     pod: WidgetPodHandle,
     _marker: PhantomData<T>,
@@ -64,6 +97,41 @@ where
     pub fn remove_class(&self, class: &str) {
         self.pod.borrow_mut().data_mut().remove_class(class);
     }
+
+    pub fn set_disabled(&self, disabled: bool) {
+        self.pod.borrow_mut().data_mut().set_disabled(disabled);
+    }
+
+    pub fn set_checked(&self, checked: bool) {
+        self.pod.borrow_mut().data_mut().set_checked(checked);
+    }
+
+    pub fn set_selected(&self, selected: bool) {
+        self.pod.borrow_mut().data_mut().set_selected(selected);
+    }
+
+    /// See [`WidgetData::enable_structural_classes`].
+    pub fn enable_structural_classes(&self) {
+        self.pod.borrow_mut().data_mut().enable_structural_classes();
+    }
+
+    /// Sets style properties that take precedence over
+    /// every rule, like a spec's `style` mapping.
+    ///
+    /// Fails if the properties do not fit the widget's style type.
+    pub fn set_style_override(&self, style_override: impl Serialize) -> Result<(), StyleError> {
+        let style_override = serde_yaml::to_value(style_override)?;
+        check_style_override::<T::Style>(self.get().base_class(), &style_override)?;
+        self.pod
+            .borrow_mut()
+            .data_mut()
+            .set_style_override(Some(style_override));
+        Ok(())
+    }
+
+    pub fn clear_style_override(&self) {
+        self.pod.borrow_mut().data_mut().set_style_override(None);
+    }
 }
 
 /// Contains a `dyn Widget` and the `WidgetData` associated with the widget.
@@ -92,11 +160,26 @@ impl WidgetPod {
             return;
         }
 
-        let cx = Context {
-            canvas: parent_cx.canvas,
-            style_engine: parent_cx.style_engine,
-            messages: parent_cx.messages,
+        let base_style = parent_cx
+            .style_engine
+            .get_style_with_override::<Option<BaseStyle>>(
+                parent_cx.ancestors,
+                self.data.classes(),
+                self.data.style_override(),
+            );
+        self.data.margin = match base_style.as_deref() {
+            Ok(Some(base_style)) => base_style.margin,
+            Ok(None) => Insets::ZERO,
+            // Rules only for widgets without a registered style
+            // type can still break it; lay them out without margin.
+            Err(e) => {
+                log::error!("failed to compute base style: {}", e);
+                Insets::ZERO
+            }
         };
+
+        let cx = parent_cx.child(Vec2::ZERO);
+        let max_size = (max_size - self.data.margin.size()).max(Vec2::ZERO);
         self.widget.layout(&mut self.data, cx, max_size);
 
         if let Some(first_child) = self.data.children.get(0) {
@@ -120,43 +203,45 @@ impl WidgetPod {
             return;
         }
 
-        parent_cx.canvas.translate(self.data.origin());
+        parent_cx.canvas.translate(self.data.box_origin());
 
-        let cx = Context {
-            canvas: parent_cx.canvas,
-            style_engine: parent_cx.style_engine,
-            messages: parent_cx.messages,
-        };
+        let cx = parent_cx.child(self.data.box_origin());
         paint(self, cx);
 
-        parent_cx.canvas.translate(-self.data().origin());
+        parent_cx.canvas.translate(-self.data().box_origin());
     }
 
     pub fn handle_event(&mut self, parent_cx: &mut Context, event: &Event) {
-        let event = event.translated(-self.data().origin());
+        let event = event.translated(-self.data().box_origin());
 
         self.update_widget_state(&event);
 
-        let cx = Context {
-            canvas: parent_cx.canvas,
-            style_engine: parent_cx.style_engine,
-            messages: parent_cx.messages,
-        };
+        let cx = parent_cx.child(self.data.box_origin());
         self.widget.handle_event(&mut self.data, cx, &event);
 
         if self.data.are_classes_dirty() {
-            let cx = Context {
-                canvas: parent_cx.canvas,
-                style_engine: parent_cx.style_engine,
-                messages: parent_cx.messages,
-            };
+            let cx = parent_cx.child(self.data.box_origin());
             self.widget.style_changed(&mut self.data, cx);
             self.data.mark_classes_clean();
         }
     }
 
+    /// Calls `style_changed` on this widget and all of its
+    /// descendants, after the styles themselves have changed.
+    pub(crate) fn restyle(&mut self, parent_cx: &mut Context) {
+        let cx = parent_cx.child(self.data.box_origin());
+        self.widget.style_changed(&mut self.data, cx);
+
+        let mut cx = parent_cx.child(self.data.box_origin());
+        let classes = cx.style_engine.ancestor_classes(self.data.classes());
+        cx.ancestors.push(classes);
+        self.data.for_each_child(|child| child.restyle(&mut cx));
+        cx.ancestors.pop();
+    }
+
     pub fn hit_test(&self, pos: Vec2) -> HitTestResult {
-        self.widget.hit_test(&self.data, pos - self.data.origin())
+        self.widget
+            .hit_test(&self.data, pos - self.data.box_origin())
     }
 
     fn update_widget_state(&mut self, event: &Event) {
@@ -206,8 +291,10 @@ impl WidgetPod {
 pub struct WidgetData {
     /// Widget children
     children: Vec<WidgetPodHandle>,
-    /// The origin of the widget's coordinate space, relative to the parent's coordinate space
+    /// The origin of the widget's margin box, relative to the parent's coordinate space
     origin: Vec2,
+    /// Space around the widget, taken from its base style.
+    margin: Insets,
     /// The offset of the widget from its top-left position in its parent.
     offset: Vec2,
     /// `offset` field of the widget's child.
@@ -222,9 +309,13 @@ pub struct WidgetData {
     classes: Vec<String>,
     /// Whether classes have changed since the class call to `Widget::style_changed`
     classes_dirty: bool,
+    /// Style properties merged over those from matching rules.
+    style_override: Option<Value>,
 
     /// Whether the widget is hidden from view and layout.
     hidden: bool,
+    /// Whether children get structural classes.
+    structural_children: bool,
 
     state: WidgetState,
 }
@@ -234,14 +325,17 @@ impl Default for WidgetData {
         Self {
             children: Vec::new(),
             origin: Vec2::ZERO,
+            margin: Insets::ZERO,
             offset: Vec2::ZERO,
             child_offset: Vec2::ZERO,
             flex: None,
             size: Vec2::ZERO,
             classes: Vec::new(),
             classes_dirty: false,
+            style_override: None,
             state: WidgetState::default(),
             hidden: false,
+            structural_children: false,
         }
     }
 }
@@ -293,22 +387,17 @@ impl WidgetData {
     pub fn lay_out_child(
         &mut self,
         strategy: LayoutStrategy,
-        padding: f32,
+        padding: impl Into<Insets>,
         cx: &mut Context,
         max_size: Vec2,
     ) -> Vec2 {
+        let padding = padding.into();
         let mut child = self.children[0].borrow_mut();
-        match strategy {
-            LayoutStrategy::Shrink => {
-                child.layout(cx, max_size - (padding * 2.));
-                child.data_mut().set_origin(Vec2::splat(padding));
-                self.size = child.data().size() + (padding * 2.);
-            }
-            LayoutStrategy::Fill => {
-                child.layout(cx, max_size - (padding * 2.));
-                child.data_mut().set_origin(Vec2::splat(padding));
-                self.size = max_size;
-            }
+        child.layout(cx, max_size - padding.size());
+        child.data_mut().set_origin(padding.top_left());
+        self.size = match strategy {
+            LayoutStrategy::Shrink => child.data().outer_size() + padding.size(),
+            LayoutStrategy::Fill => max_size,
         };
         self.size
     }
@@ -333,6 +422,26 @@ impl WidgetData {
         self.size
     }
 
+    /// The size of the widget including its margin,
+    /// which parents should make room for.
+    pub fn outer_size(&self) -> Vec2 {
+        if self.hidden {
+            Vec2::ZERO
+        } else {
+            self.size + self.margin.size()
+        }
+    }
+
+    pub fn margin(&self) -> Insets {
+        self.margin
+    }
+
+    /// The origin of the widget's coordinate space: its
+    /// origin moved inside its margin.
+    pub fn box_origin(&self) -> Vec2 {
+        self.origin + self.margin.top_left()
+    }
+
     pub fn offset(&self) -> Vec2 {
         self.offset
     }
@@ -367,14 +476,44 @@ impl WidgetData {
 
     pub fn add_child(&mut self, child: WidgetPodHandle) {
         self.children.push(child);
+        self.update_structural_classes(self.children.len() - 1);
     }
 
     pub fn insert_child(&mut self, child: WidgetPodHandle, index: usize) {
         self.children.insert(index, child);
+        self.update_structural_classes(index);
     }
 
     pub fn remove_child(&mut self, index: usize) {
         self.children.remove(index);
+        self.update_structural_classes(index);
+    }
+
+    /// Gives each child the structural classes for its position,
+    /// now and whenever children are added, inserted or removed.
+    ///
+    /// Meant for containers whose children form a list that themes
+    /// may want to style by position, like the options of a `PickList`.
+    pub fn enable_structural_classes(&mut self) {
+        self.structural_children = true;
+        self.update_structural_classes(0);
+    }
+
+    /// Gives the structural classes for their new positions to the
+    /// children from `index` on, which moved after a change there,
+    /// and to the one before, which may have become or stopped being last.
+    fn update_structural_classes(&mut self, index: usize) {
+        if !self.structural_children {
+            return;
+        }
+        let count = self.children.len();
+        let children = self.children.iter().enumerate();
+        for (index, child) in children.skip(index.saturating_sub(1)) {
+            child
+                .borrow_mut()
+                .data_mut()
+                .set_structural_classes(index, count);
+        }
     }
 
     pub fn clear_children(&mut self) {
@@ -397,7 +536,70 @@ impl WidgetData {
     }
 
     pub fn add_class(&mut self, class: &str) {
-        self.classes.push(class.to_owned());
+        // Keeping classes sorted gives each set of classes one
+        // cache key, whatever order they were added in.
+        let index = self.classes.partition_point(|c| c.as_str() < class);
+        self.classes.insert(index, class.to_owned());
+        self.classes_dirty = true;
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    /// Adds or removes a class. Unlike `add_class`,
+    /// this never adds the same class twice.
+    pub fn set_class(&mut self, class: &str, enabled: bool) {
+        if enabled != self.has_class(class) {
+            if enabled {
+                self.add_class(class);
+            } else {
+                self.remove_class(class);
+            }
+        }
+    }
+
+    /// Sets the `first`, `last`, `odd` and `even` classes for
+    /// a widget at `index` in a sequence of `count` siblings.
+    ///
+    /// Containers that call `enable_structural_classes` get this
+    /// through `add_child` and friends; widgets that manage their
+    /// children by other means, like `Table`, can call it directly.
+    pub fn set_structural_classes(&mut self, index: usize, count: usize) {
+        for (class, enabled) in structural_classes(index, count) {
+            self.set_class(class, enabled);
+        }
+    }
+
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.state.disabled = disabled;
+        self.set_class(CLASS_DISABLED, disabled);
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.state.checked = checked;
+        self.set_class(CLASS_CHECKED, checked);
+    }
+
+    pub fn set_selected(&mut self, selected: bool) {
+        self.state.selected = selected;
+        self.set_class(CLASS_SELECTED, selected);
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.state.focused = focused;
+        self.set_class(CLASS_FOCUSED, focused);
+    }
+
+    pub fn style_override(&self) -> Option<&Value> {
+        self.style_override.as_ref()
+    }
+
+    /// Sets the style override without checking it; see
+    /// [`WidgetHandle::set_style_override`].
+    pub(crate) fn set_style_override(&mut self, style_override: Option<Value>) {
+        self.style_override = style_override;
+        // Like a class change, this changes the style.
         self.classes_dirty = true;
     }
 
@@ -423,6 +625,13 @@ impl WidgetData {
 pub struct WidgetState {
     pub hovered: bool,
     pub pressed: bool,
+    /// Set by the application. Disabled widgets
+    /// should not respond to input.
+    pub disabled: bool,
+    pub checked: bool,
+    pub selected: bool,
+    /// Whether the widget has keyboard focus.
+    pub focused: bool,
 }
 
 #[non_exhaustive]
@@ -430,13 +639,74 @@ pub struct Context<'a> {
     pub canvas: &'a mut Canvas,
     pub(crate) style_engine: &'a mut StyleEngine,
     pub(crate) messages: &'a mut VecDeque<Box<dyn Any>>,
+    /// The widget's origin relative to the native window.
+    ///
+    /// Not known yet during layout, where it is the parent's origin instead.
+    pub(crate) origin: Vec2,
+    pub(crate) ime_caret: &'a mut Option<Rect>,
+    /// The relevant style classes of each ancestor of the widget, root first.
+    pub(crate) ancestors: &'a mut Vec<Vec<String>>,
+    /// The clip rectangles set by `push_clip`, innermost last,
+    /// relative to the native window.
+    pub(crate) clips: &'a mut Vec<Rect>,
 }
 
 impl<'a> Context<'a> {
+    /// Reborrows the context for a child widget, whose
+    /// origin is `offset` from this widget's origin.
+    pub(crate) fn child(&mut self, offset: Vec2) -> Context<'_> {
+        Context {
+            canvas: self.canvas,
+            style_engine: self.style_engine,
+            messages: self.messages,
+            origin: self.origin + offset,
+            ime_caret: self.ime_caret,
+            ancestors: self.ancestors,
+            clips: self.clips,
+        }
+    }
+
+    /// Restricts painting to `rect`, in the widget's coordinate
+    /// space, until the matching call to `pop_clip`.
+    ///
+    /// The clip is intersected with any clip set by an ancestor.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let mut clip = Rect::new(self.origin + rect.pos, rect.size);
+        if let Some(outer) = self.clips.last() {
+            clip = clip.intersection(*outer);
+        }
+        self.clips.push(clip);
+        self.apply_clip();
+    }
+
+    /// Restores the clip in effect before the last `push_clip`.
+    pub fn pop_clip(&mut self) {
+        self.clips.pop();
+        self.apply_clip();
+    }
+
+    fn apply_clip(&mut self) {
+        match self.clips.last() {
+            Some(clip) => self
+                .canvas
+                .scissor_rect(Rect::new(clip.pos - self.origin, clip.size)),
+            None => self.canvas.clear_scissor(),
+        }
+    }
+
     /// Delivers a message to the UI.
     pub fn send_message(&mut self, message: Box<dyn Any>) {
         self.messages.push_back(message);
     }
+
+    /// Reports the text caret of the focused widget, in the
+    /// widget's coordinate space. The platform layer uses it
+    /// to place the IME candidate window.
+    ///
+    /// Should be called from `Widget::paint`.
+    pub fn set_ime_caret(&mut self, caret: Rect) {
+        *self.ime_caret = Some(Rect::new(self.origin + caret.pos, caret.size));
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -551,39 +821,33 @@ where
     }
 
     fn handle_event(&mut self, data: &mut WidgetData, cx: Context, event: &Event) {
-        <T as Widget>::handle_event(self, data, cx, event);
+        in_scope(data, cx, |data, cx| {
+            <T as Widget>::handle_event(self, data, cx, event)
+        });
     }
 
     fn style_changed(&mut self, data: &mut WidgetData, cx: Context) {
-        let style = cx
-            .style_engine
-            .get_style(data.classes())
-            .expect("failed to compute widget style");
-        <T as Widget>::style_changed(self, &*style, data, cx);
+        with_style::<T, _>(data, cx, |style, data, cx| {
+            <T as Widget>::style_changed(self, style, data, cx)
+        })
     }
 
     fn layout(&mut self, data: &mut WidgetData, cx: Context, max_size: Vec2) {
-        let style = cx
-            .style_engine
-            .get_style(data.classes())
-            .expect("failed to compute widget style");
-        <T as Widget>::layout(self, &*style, data, cx, max_size)
+        with_style::<T, _>(data, cx, |style, data, cx| {
+            <T as Widget>::layout(self, style, data, cx, max_size)
+        })
     }
 
     fn paint(&mut self, data: &mut WidgetData, cx: Context) {
-        let style = cx
-            .style_engine
-            .get_style(data.classes())
-            .expect("failed to compute widget style");
-        <T as Widget>::paint(self, &*style, data, cx)
+        with_style::<T, _>(data, cx, |style, data, cx| {
+            <T as Widget>::paint(self, style, data, cx)
+        })
     }
 
     fn paint_overlay(&mut self, data: &mut WidgetData, cx: Context) {
-        let style = cx
-            .style_engine
-            .get_style(data.classes())
-            .expect("failed to compute widget style");
-        <T as Widget>::paint_overlay(self, &*style, data, cx)
+        with_style::<T, _>(data, cx, |style, data, cx| {
+            <T as Widget>::paint_overlay(self, style, data, cx)
+        })
     }
 
     fn hit_test(&self, data: &WidgetData, pos: Vec2) -> HitTestResult {
@@ -591,6 +855,51 @@ where
     }
 }
 
+/// Style properties that apply to every widget, read
+/// from the same rules as the widget's own style.
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct BaseStyle {
+    #[serde(default)]
+    margin: Insets,
+}
+
+/// Checks a style override against a widget's
+/// style type and against the base style.
+pub(crate) fn check_style_override<S: DeserializeOwned>(
+    base_class: &str,
+    style_override: &Value,
+) -> Result<(), StyleError> {
+    style::check_override::<S>(base_class, style_override)?;
+    style::check_override::<BaseStyle>(base_class, style_override)
+}
+
+/// Computes the style of a widget, then calls `f` in the widget's scope.
+fn with_style<T: Widget, R>(
+    data: &mut WidgetData,
+    cx: Context,
+    f: impl FnOnce(&T::Style, &mut WidgetData, Context) -> R,
+) -> R {
+    let style = cx
+        .style_engine
+        .get_style_with_override::<T::Style>(cx.ancestors, data.classes(), data.style_override())
+        .expect("failed to compute widget style");
+    in_scope(data, cx, |data, cx| f(&*style, data, cx))
+}
+
+/// Calls `f` with a context in which the widget
+/// is the nearest ancestor, as seen by its children.
+fn in_scope<R>(
+    data: &mut WidgetData,
+    mut cx: Context,
+    f: impl FnOnce(&mut WidgetData, Context) -> R,
+) -> R {
+    let classes = cx.style_engine.ancestor_classes(data.classes());
+    cx.ancestors.push(classes);
+    let result = f(data, cx.child(Vec2::ZERO));
+    cx.ancestors.pop();
+    result
+}
+
 pub trait AsAny {
     fn as_any(&self) -> &dyn Any;
 
@@ -609,3 +918,52 @@ where
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::Tooltip;
+
+    fn classes(pod: &WidgetPodHandle) -> Vec<String> {
+        let pod = pod.borrow();
+        let mut classes = pod.data().classes().to_vec();
+        classes.sort();
+        classes
+    }
+
+    #[test]
+    fn structural_classes() {
+        let mut parent = WidgetData::default();
+        let children: Vec<_> = (0..3).map(|_| crate::widget(Tooltip::new())).collect();
+        for child in &children[..2] {
+            parent.add_child(Rc::clone(child));
+        }
+        assert_eq!(classes(&children[0]), ["tooltip"]);
+
+        parent.enable_structural_classes();
+        assert_eq!(classes(&children[0]), ["first", "odd", "tooltip"]);
+        assert_eq!(classes(&children[1]), ["even", "last", "tooltip"]);
+
+        parent.insert_child(Rc::clone(&children[2]), 0);
+        assert_eq!(classes(&children[2]), ["first", "odd", "tooltip"]);
+        assert_eq!(classes(&children[0]), ["even", "tooltip"]);
+        assert_eq!(classes(&children[1]), ["last", "odd", "tooltip"]);
+
+        parent.remove_child(2);
+        assert_eq!(classes(&children[0]), ["even", "last", "tooltip"]);
+    }
+
+    #[test]
+    fn state_classes() {
+        let mut data = WidgetData::default();
+        data.set_disabled(true);
+        data.set_disabled(true);
+        data.set_focused(true);
+        assert!(data.state().disabled);
+        assert_eq!(data.classes(), ["disabled", "focused"]);
+
+        data.set_disabled(false);
+        assert!(!data.state().disabled);
+        assert_eq!(data.classes(), ["focused"]);
+    }
+}
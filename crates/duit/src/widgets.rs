@@ -6,6 +6,7 @@ pub mod progress_bar;
 pub mod text;
 pub mod clickable;
 pub mod text_input;
+pub mod text_area;
 pub mod slider;
 pub mod divider;
 pub mod table;
@@ -21,6 +22,7 @@ pub use progress_bar::ProgressBar;
 pub use text::Text;
 pub use clickable::Clickable;
 pub use text_input::TextInput;
+pub use text_area::TextArea;
 pub use slider::Slider;
 pub use divider::Divider;
 pub use table::Table;
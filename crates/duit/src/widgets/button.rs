@@ -4,7 +4,7 @@ use duit_core::spec::widgets::ButtonSpec;
 use glam::Vec2;
 use winit::event::MouseButton;
 
-use crate::{Color, Event, Widget, WidgetData, widget::{Context, HitTestResult, LayoutStrategy}};
+use crate::{Color, Event, Insets, Paint, Rect, Shadow, Widget, WidgetData, border::stroke_border, widget::{Context, HitTestResult, LayoutStrategy}};
 
 pub struct Button {
     on_click: Option<Box<dyn FnMut() -> Box<dyn Any>>>,
@@ -33,11 +33,12 @@ impl Button {
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Style {
-    padding: f32,
+    padding: Insets,
     border_radius: f32,
-    border_width: f32,
+    border_width: Insets,
     border_color: Color,
-    background_color: Color,
+    background_color: Paint,
+    shadow: Option<Shadow>,
 }
 
 impl Widget for Button {
@@ -58,17 +59,25 @@ impl Widget for Button {
     }
 
     fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
-        let canvas = &mut cx.canvas;
+        let bounds = Rect::new(Vec2::ZERO, data.size());
 
-        canvas
+        if let Some(shadow) = &style.shadow {
+            shadow.paint_outer(cx.canvas, bounds, style.border_radius);
+        }
+        cx.canvas
             .begin_path()
-            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius)
-            .solid_color(style.background_color)
-            .fill();
-        canvas
-            .solid_color(style.border_color)
-            .stroke_width(style.border_width)
-            .stroke();
+            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius);
+        style.background_color.fill(&mut cx, bounds);
+        if let Some(shadow) = &style.shadow {
+            shadow.paint_inner(&mut cx, bounds, style.border_radius);
+        }
+        stroke_border(
+            cx.canvas,
+            bounds,
+            style.border_radius,
+            style.border_width,
+            style.border_color,
+        );
 
         data.paint_children(&mut cx);
     }
@@ -81,7 +90,7 @@ impl Widget for Button {
                 ..
             } = event
             {
-                if data.bounds().contains(*pos) {
+                if data.bounds().contains(*pos) && !data.state().disabled {
                     cx.send_message((*on_click)());
                 }
             }
@@ -72,7 +72,7 @@ impl Widget for Clickable {
                 ..
             } = event
             {
-                if data.bounds().contains(*pos) {
+                if data.bounds().contains(*pos) && !data.state().disabled {
                     cx.send_message((*on_click)(*mods));
                 }
             }
@@ -1,7 +1,7 @@
 use duit_core::spec::widgets::{ContainerMode, ContainerSpec};
 use glam::Vec2;
 
-use crate::{Color, Widget, WidgetData, widget::{Context, HitTestResult, LayoutStrategy}};
+use crate::{Color, Insets, Paint, Rect, Shadow, Widget, WidgetData, border::stroke_border, widget::{Context, HitTestResult, LayoutStrategy}};
 
 pub struct Container {
     mode: ContainerMode,
@@ -22,9 +22,10 @@ impl Container {
 #[derive(Debug, serde::Deserialize)]
 pub struct Style {
     border_radius: f32,
-    border_width: f32,
+    border_width: Insets,
     border_color: Color,
-    background_color: Color,
+    background_color: Paint,
+    shadow: Option<Shadow>,
 }
 
 impl Widget for Container {
@@ -42,8 +43,8 @@ impl Widget for Container {
         max_size: Vec2,
     ) {
         let (strategy, padding) = match self.mode {
-            ContainerMode::Shrink => (LayoutStrategy::Shrink, 0.),
-            ContainerMode::FillParent => (LayoutStrategy::Fill, 0.),
+            ContainerMode::Shrink => (LayoutStrategy::Shrink, Insets::ZERO),
+            ContainerMode::FillParent => (LayoutStrategy::Fill, Insets::ZERO),
             ContainerMode::FillParentAndPad(padding) => (LayoutStrategy::Fill, padding),
             ContainerMode::Pad(padding) => (LayoutStrategy::Shrink, padding),
         };
@@ -58,23 +59,34 @@ impl Widget for Container {
     }
 
     fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
-        let canvas = &mut cx.canvas;
-
         let pos = match self.mode {
             ContainerMode::FillParent | ContainerMode::FillParentAndPad(_) => Vec2::ZERO,
             _ => data.child_offset(),
         };
 
-        canvas
+        let bounds = Rect::new(pos, data.size());
+
+        if let Some(shadow) = &style.shadow {
+            shadow.paint_outer(cx.canvas, bounds, style.border_radius);
+        }
+
+        cx.canvas
             .begin_path()
             .rounded_rect(pos, data.size(), style.border_radius);
 
-        canvas.solid_color(style.background_color).fill();
+        style.background_color.fill(&mut cx, bounds);
+
+        if let Some(shadow) = &style.shadow {
+            shadow.paint_inner(&mut cx, bounds, style.border_radius);
+        }
 
-        canvas
-            .solid_color(style.border_color)
-            .stroke_width(style.border_width)
-            .stroke();
+        stroke_border(
+            cx.canvas,
+            bounds,
+            style.border_radius,
+            style.border_width,
+            style.border_color,
+        );
 
         data.paint_children(&mut cx);
     }
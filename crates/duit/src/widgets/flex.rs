@@ -113,8 +113,8 @@ impl Widget for Flex {
                 let mut child_constraints = max_size;
                 child_constraints[main_axis] -= consumed_space;
                 child.layout(&mut cx, child_constraints);
-                non_flex_size += child.data().size()[main_axis];
-                consumed_space += child.data().size()[main_axis];
+                non_flex_size += child.data().outer_size()[main_axis];
+                consumed_space += child.data().outer_size()[main_axis];
             }
         });
 
@@ -144,9 +144,9 @@ impl Widget for Flex {
                     cursor += widget_main_size;
                 }
                 None => {
-                    cursor += child.data().size()[main_axis];
+                    cursor += child.data().outer_size()[main_axis];
 
-                    let cross_size = child.data().size()[cross_axis];
+                    let cross_size = child.data().outer_size()[cross_axis];
                     if cross_size > largest_cross_size {
                         largest_cross_size = cross_size;
                     }
@@ -180,10 +180,11 @@ impl Widget for Flex {
                 Align::Start => {}
                 Align::Center => {
                     origin[cross_axis] +=
-                        max_size[cross_axis] / 2. - child.data().size()[cross_axis] / 2.;
+                        max_size[cross_axis] / 2. - child.data().outer_size()[cross_axis] / 2.;
                 }
                 Align::End => {
-                    origin[cross_axis] += max_size[cross_axis] - child.data().size()[cross_axis];
+                    origin[cross_axis] +=
+                        max_size[cross_axis] - child.data().outer_size()[cross_axis];
                 }
             }
 
@@ -9,7 +9,8 @@ use glam::{vec2, Vec2};
 use winit::event::MouseButton;
 
 use crate::{
-    widget, widget::Context, widget::HitTestResult, Color, Event, Widget, WidgetData, WidgetHandle,
+    border::stroke_border,
+    widget, widget::Context, widget::HitTestResult, Color, Event, Insets, InsetsExt, Paint, Shadow, Widget, WidgetData, WidgetHandle,
     WidgetPodHandle,
 };
 
@@ -51,6 +52,7 @@ impl PickList {
         );
 
         let column = widget(column);
+        column.borrow_mut().data_mut().enable_structural_classes();
 
         let child = widget(Scrollable::new(Axis::Vertical));
         child.borrow_mut().data_mut().add_child(Rc::clone(&column));
@@ -94,8 +96,12 @@ const ARROW_DOWN: &str = "▼";
 pub struct Style {
     border_color: Color,
     border_radius: f32,
-    border_width: f32,
-    background_color: Color,
+    border_width: Insets,
+    background_color: Paint,
+    /// Space around the selected option.
+    padding: Insets,
+    /// Cast by the list of options while it is open.
+    overlay_shadow: Option<Shadow>,
 
     arrow_font_family: String,
     arrow_size: f32,
@@ -109,9 +115,13 @@ impl Widget for PickList {
         "pick_list"
     }
 
+    fn style_changed(&mut self, _style: &Self::Style, _data: &mut WidgetData, _cx: Context) {
+        self.arrow_down = None;
+    }
+
     fn layout(
         &mut self,
-        _style: &Self::Style,
+        style: &Self::Style,
         data: &mut WidgetData,
         mut cx: Context,
         max_size: Vec2,
@@ -121,15 +131,14 @@ impl Widget for PickList {
             None => max_size.x,
         };
 
-        let padding = 10.;
         let height = {
             let mut placeholder = data.child(CHILD_INDEX_PLACEHOLDER);
-            placeholder.layout(&mut cx, max_size - Vec2::splat(padding * 2.));
-            placeholder.data_mut().set_origin(Vec2::splat(padding));
-            placeholder.data().size().y
+            placeholder.layout(&mut cx, max_size - style.padding.size());
+            placeholder.data_mut().set_origin(style.padding.top_left());
+            placeholder.data().outer_size().y
         };
 
-        data.set_size(vec2(width, height) + Vec2::splat(padding * 2.));
+        data.set_size(vec2(width, height) + style.padding.size());
 
         if let Some(child) = self.queued_child.take() {
             data.add_child(child);
@@ -146,15 +155,18 @@ impl Widget for PickList {
     }
 
     fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
+        let bounds = Rect::new(Vec2::ZERO, data.size());
         cx.canvas
             .begin_path()
-            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius)
-            .solid_color(style.background_color)
-            .fill();
-        cx.canvas
-            .stroke_width(style.border_width)
-            .solid_color(style.border_color)
-            .stroke();
+            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius);
+        style.background_color.fill(&mut cx, bounds);
+        stroke_border(
+            cx.canvas,
+            bounds,
+            style.border_radius,
+            style.border_width,
+            style.border_color,
+        );
 
         let arrow_down = self.arrow_down.get_or_insert_with(|| {
             let text = Text::from_sections(vec![TextSection::Text {
@@ -188,9 +200,14 @@ impl Widget for PickList {
         data.child(CHILD_INDEX_PLACEHOLDER).paint(&mut cx);
     }
 
-    fn paint_overlay(&mut self, _style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
+    fn paint_overlay(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
         if self.opened {
-            data.child(CHILD_INDEX_OVERLAY).paint(&mut cx);
+            let mut overlay = data.child(CHILD_INDEX_OVERLAY);
+            if let Some(shadow) = &style.overlay_shadow {
+                let bounds = Rect::new(overlay.data().box_origin(), overlay.data().size());
+                shadow.paint_outer(cx.canvas, bounds, style.border_radius);
+            }
+            overlay.paint(&mut cx);
         }
     }
 
@@ -214,7 +231,7 @@ impl Widget for PickList {
 
             if data.bounds().contains(*pos) {
                 self.opened = !self.opened;
-            } else if !Rect::new(overlay.data().origin(), overlay.data().size()).contains(*pos) {
+            } else if !Rect::new(overlay.data().box_origin(), overlay.data().size()).contains(*pos) {
                 self.opened = false;
             }
         }
@@ -227,11 +244,11 @@ struct PickListOption {
 }
 
 #[derive(Debug, serde::Deserialize)]
-struct OptionStyle {
+pub(crate) struct OptionStyle {
     border_color: Color,
-    border_width: f32,
-    background_color: Color,
-    padding: f32,
+    border_width: Insets,
+    background_color: Paint,
+    padding: Insets,
 }
 
 impl Widget for PickListOption {
@@ -250,23 +267,24 @@ impl Widget for PickListOption {
     ) {
         let mut child_size = Vec2::default();
         data.for_each_child(|child| {
-            child.layout(&mut cx, max_size - Vec2::splat(style.padding * 2.));
-            child_size = child.data().size();
-            child.data_mut().set_origin(Vec2::splat(style.padding));
+            child.layout(&mut cx, max_size - style.padding.size());
+            child_size = child.data().outer_size();
+            child.data_mut().set_origin(style.padding.top_left());
         });
-        data.set_size(vec2(max_size.x, child_size.y + style.padding * 2.));
+        data.set_size(vec2(max_size.x, child_size.y + style.padding.vertical()));
     }
 
     fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
-        cx.canvas
-            .begin_path()
-            .rect(Vec2::ZERO, data.size())
-            .solid_color(style.background_color)
-            .fill();
-        cx.canvas
-            .solid_color(style.border_color)
-            .stroke_width(style.border_width)
-            .stroke();
+        let bounds = Rect::new(Vec2::ZERO, data.size());
+        cx.canvas.begin_path().rect(Vec2::ZERO, data.size());
+        style.background_color.fill(&mut cx, bounds);
+        stroke_border(
+            cx.canvas,
+            bounds,
+            0.,
+            style.border_width,
+            style.border_color,
+        );
 
         data.paint_children(&mut cx);
     }
@@ -1,7 +1,9 @@
 use duit_core::spec::widgets::ProgressBarSpec;
 use glam::{vec2, Vec2};
 
-use crate::{widget::Context, Color, Widget, WidgetData};
+use crate::{
+    border::stroke_border, widget::Context, Color, Insets, Paint, Rect, Widget, WidgetData,
+};
 
 pub struct ProgressBar {
     width: Option<f32>,
@@ -34,9 +36,9 @@ impl ProgressBar {
 #[derive(Debug, serde::Deserialize)]
 pub struct Style {
     border_radius: f32,
-    border_width: f32,
+    border_width: Insets,
     border_color: Color,
-    background_color: Color,
+    background_color: Paint,
     progress_color: Color,
     projected_progress_color: Color,
 }
@@ -70,18 +72,17 @@ impl Widget for ProgressBar {
     }
 
     fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
-        let canvas = &mut cx.canvas;
+        let bounds = Rect::new(Vec2::ZERO, data.size());
 
         // Background
-        canvas
+        cx.canvas
             .begin_path()
-            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius)
-            .solid_color(style.background_color)
-            .fill();
+            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius);
+        style.background_color.fill(&mut cx, bounds);
 
         // Progress
         let progress_size = data.size() * vec2(self.progress, 1.0);
-        canvas
+        cx.canvas
             .begin_path()
             .rounded_rect(Vec2::ZERO, progress_size, style.border_radius)
             .solid_color(style.progress_color)
@@ -89,7 +90,7 @@ impl Widget for ProgressBar {
 
         // Projected progress
         if let Some(projected_progress) = self.projected_progress {
-            canvas
+            cx.canvas
                 .begin_path()
                 .rounded_rect(
                     vec2(progress_size.x, 0.0),
@@ -101,12 +102,13 @@ impl Widget for ProgressBar {
         }
 
         // Border
-        canvas
-            .begin_path()
-            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius)
-            .solid_color(style.border_color)
-            .stroke_width(style.border_width)
-            .stroke();
+        stroke_border(
+            cx.canvas,
+            bounds,
+            style.border_radius,
+            style.border_width,
+            style.border_color,
+        );
 
         data.paint_children(&mut cx);
     }
@@ -41,6 +41,19 @@ impl Scrollable {
         }
     }
 
+    /// Returns how far the child is scrolled along the scroll axis.
+    pub fn scroll_pos(&self) -> f32 {
+        self.scroll_pos
+    }
+
+    /// Scrolls the child to the given position along the scroll axis.
+    ///
+    /// Takes effect on the next layout.
+    pub fn set_scroll_pos(&mut self, pos: f32) -> &mut Self {
+        self.scroll_pos = pos.max(0.);
+        self
+    }
+
     fn bar_rect(&self, self_size: Vec2) -> Rect {
         if self.child_size[self.scroll_axis as usize] == 0. {
             return Rect::new(Vec2::ZERO, Vec2::ZERO);
@@ -99,8 +112,8 @@ impl Widget for Scrollable {
             origin[self.scroll_axis as usize] = -self.scroll_pos;
             child.data_mut().set_origin(origin);
 
-            cross_size = child.data().size()[self.cross_axis as usize];
-            self.child_size = child.data().size();
+            cross_size = child.data().outer_size()[self.cross_axis as usize];
+            self.child_size = child.data().outer_size();
         });
 
         let mut size = max_size;
@@ -112,9 +125,9 @@ impl Widget for Scrollable {
     }
 
     fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
-        // cx.canvas.scissor_rect(Rect::new(Vec2::ZERO, data.size()));
+        cx.push_clip(Rect::new(Vec2::ZERO, data.size()));
         data.paint_children(&mut cx);
-        //   cx.canvas.clear_scissor();
+        cx.pop_clip();
 
         if self.child_size[self.scroll_axis as usize] > data.size()[self.scroll_axis as usize] {
             let bar = self.bar_rect(data.size());
@@ -128,11 +128,7 @@ impl Widget for Slider {
 
         // Update style classes
         if was_grabbed != self.grabbed {
-            if self.grabbed {
-                data.add_class("grabbed");
-            } else {
-                data.remove_class("grabbed");
-            }
+            data.set_class("grabbed", self.grabbed);
         }
     }
 }
@@ -4,7 +4,11 @@ use ahash::AHashMap;
 use duit_core::spec::widgets::TableSpec;
 use glam::{vec2, Vec2};
 
-use crate::{widget::Context, Color, Widget, WidgetData, WidgetPodHandle};
+use crate::{
+    border::stroke_border,
+    widget::{structural_classes, Context},
+    Color, Insets, Paint, Rect, Widget, WidgetData, WidgetPodHandle,
+};
 
 type ColumnName = Rc<str>;
 
@@ -13,6 +17,10 @@ type ColumnName = Rc<str>;
 /// Has a fixed list of columns, each of which has a name.
 /// Programmaticaly, you should add data by calling [`add_row`].
 /// Each row consists of a widget for each column.
+///
+/// Row backgrounds are styled with the `table_row` class, along
+/// with the structural classes (`first`, `last`, `odd`, `even`)
+/// for the row's position. Cell widgets get the same structural classes.
 pub struct Table {
     columns: Vec<ColumnName>,
     column_widths: AHashMap<ColumnName, f32>,
@@ -20,6 +28,10 @@ pub struct Table {
     rows: Vec<Row>,
     empty_rows: u32,
     the_empty_row: Row,
+    /// The style classes of each row, including empty rows.
+    row_classes: Vec<Vec<String>>,
+    /// The style of each row, resolved during layout.
+    row_styles: Vec<Rc<RowStyle>>,
 }
 
 #[derive(Default)]
@@ -30,14 +42,18 @@ struct Row {
 
 impl Table {
     pub fn from_spec(spec: &TableSpec) -> Self {
-        Self {
+        let mut table = Self {
             columns: spec.columns.iter().map(|c| c.as_str().into()).collect(),
             column_widths: AHashMap::new(),
             column_offsets: AHashMap::new(),
             rows: Vec::new(),
             empty_rows: spec.empty_rows,
             the_empty_row: Row::default(),
-        }
+            row_classes: Vec::new(),
+            row_styles: Vec::new(),
+        };
+        table.update_structural_classes();
+        table
     }
 
     fn find_column_name(&self, name: &str) -> ColumnName {
@@ -55,10 +71,31 @@ impl Table {
             row.widgets.insert(name, widget);
         }
         self.rows.push(row);
+        self.update_structural_classes();
     }
 
     pub fn clear_rows(&mut self) {
         self.rows.clear();
+        self.update_structural_classes();
+    }
+
+    fn num_rows(&self) -> usize {
+        self.rows.len() + self.empty_rows as usize
+    }
+
+    /// Gives rows and their cells the structural
+    /// classes for their positions after a change.
+    fn update_structural_classes(&mut self) {
+        let count = self.num_rows();
+        for (index, row) in self.rows.iter().enumerate() {
+            for widget in row.widgets.values() {
+                widget
+                    .borrow_mut()
+                    .data_mut()
+                    .set_structural_classes(index, count);
+            }
+        }
+        self.row_classes = (0..count).map(|index| row_classes(index, count)).collect();
     }
 
     pub fn add_column(&mut self, name: &str) {
@@ -79,15 +116,36 @@ impl Table {
     }
 }
 
+const CLASS_TABLE_ROW: &str = "table_row";
+
+/// Gets the style classes of the row at `index`,
+/// sorted like the classes of a widget.
+fn row_classes(index: usize, count: usize) -> Vec<String> {
+    let mut classes: Vec<String> = iter::once(CLASS_TABLE_ROW)
+        .chain(
+            structural_classes(index, count)
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(class, _)| *class),
+        )
+        .map(str::to_owned)
+        .collect();
+    classes.sort();
+    classes
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct Style {
-    background_color_a: Color,
-    background_color_b: Color,
     border_color: Color,
-    border_width: f32,
+    border_width: Insets,
     min_row_height: f32,
     min_column_width: f32,
-    cell_padding: f32,
+    cell_padding: Insets,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct RowStyle {
+    background_color: Paint,
 }
 
 impl Widget for Table {
@@ -122,9 +180,9 @@ impl Widget for Table {
 
                 widget
                     .data_mut()
-                    .set_origin(vec2(0., size.y + style.cell_padding));
+                    .set_origin(vec2(0., size.y + style.cell_padding.top));
 
-                let size = widget.data().size();
+                let size = widget.data().outer_size();
                 row_height = row_height.max(size.y);
 
                 let column_width = column_widths.entry(column_name.clone()).or_default();
@@ -132,7 +190,7 @@ impl Widget for Table {
             }
 
             row_height = row_height.max(style.min_row_height);
-            row_height += 2. * style.cell_padding;
+            row_height += style.cell_padding.vertical();
 
             row.height.set(row_height);
             size.y += row_height;
@@ -141,7 +199,7 @@ impl Widget for Table {
         for column in &self.columns {
             let mut column_width = column_widths.get(column).copied().unwrap_or_default();
             column_width = column_width.max(style.min_column_width);
-            column_width += 2. * style.cell_padding;
+            column_width += style.cell_padding.horizontal();
 
             column_widths.insert(column.clone(), column_width);
             size.x += column_width;
@@ -158,7 +216,7 @@ impl Widget for Table {
                 if let Some(widget) = row.widgets.get(column) {
                     let mut widget = widget.borrow_mut();
                     let mut origin = widget.data().origin();
-                    origin.x = cursor + style.cell_padding;
+                    origin.x = cursor + style.cell_padding.left;
                     widget.data_mut().set_origin(origin);
                 }
             }
@@ -168,31 +226,36 @@ impl Widget for Table {
         }
 
         self.column_widths = column_widths;
+
+        self.row_styles = self
+            .row_classes
+            .iter()
+            .map(|classes| {
+                cx.style_engine
+                    .get_style::<RowStyle>(cx.ancestors, classes)
+                    .expect("failed to compute table row style")
+            })
+            .collect();
     }
 
     fn paint(&mut self, style: &Self::Style, _data: &mut WidgetData, mut cx: Context) {
         let mut cursor_y = 0.0f32;
-        for (i, row) in self.rows().enumerate() {
+        for (row, row_style) in self.rows().zip(&self.row_styles) {
             for column in &self.columns {
                 // Background / border
                 let pos = vec2(self.column_offsets[column], cursor_y);
                 let size = vec2(self.column_widths[column], row.height.get());
 
-                let color = if i % 2 == 0 {
-                    style.background_color_a
-                } else {
-                    style.background_color_b
-                };
-
-                cx.canvas
-                    .begin_path()
-                    .rect(pos, size)
-                    .solid_color(color)
-                    .fill();
-                cx.canvas
-                    .stroke_width(style.border_width)
-                    .solid_color(style.border_color)
-                    .stroke();
+                let bounds = Rect::new(pos, size);
+                cx.canvas.begin_path().rect(pos, size);
+                row_style.background_color.fill(&mut cx, bounds);
+                stroke_border(
+                    cx.canvas,
+                    bounds,
+                    0.,
+                    style.border_width,
+                    style.border_color,
+                );
             }
 
             for widget in row.widgets.values() {
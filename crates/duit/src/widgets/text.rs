@@ -1,92 +1,624 @@
-use duit_core::spec::widgets::TextSpec;
-use dume::{Align, Baseline, TextBlob, TextOptions, TextSection};
-use glam::Vec2;
+use std::{any::Any, ops::Range};
+
+use duit_core::spec::widgets::{TextOverflow, TextSpec};
+use dume::{Baseline, Rect, TextBlob, TextOptions, TextSection};
+use glam::{vec2, Vec2};
+use winit::event::MouseButton;
 
 use crate::{
     color::Color,
-    widget::{Context, Widget, WidgetData},
-    AlignExt,
+    widget::{Context, HitTestResult, Widget, WidgetData},
+    Align, AlignExt, Event, FontStyle, FontWeight,
 };
 
+use self::spans::{LineLayout, Measurer, SpanLayout};
+
+mod spans;
+
+type SpanCallback = Box<dyn FnMut(&str) -> Box<dyn Any>>;
+
+/// What spans and lines are laid out for: the width lines break
+/// at, and the length the text was shortened to, if it was.
+type MeasureKey = (f32, Option<usize>);
+
+/// Ends text shortened by `TextOverflow::Ellipsis`.
+const ELLIPSIS: &str = "…";
+
 pub struct Text {
     text: dume::Text,
-    align_h: Align,
-    align_v: Align,
+    /// Alignment set by the spec, which overrides the style's.
+    align_h: Option<Align>,
+    align_v: Option<Align>,
+    /// Truncation settings set by the spec, which override the style's.
+    max_lines: Option<u32>,
+    overflow: Option<TextOverflow>,
+    wrap: Option<bool>,
     paragraph: Option<TextBlob>,
+    /// The text in the shadow color, if the style has a shadow.
+    shadow_paragraph: Option<TextBlob>,
+    /// The maximum size the paragraph was shortened to fit,
+    /// and how many characters were kept, if it was.
+    shortened: Option<(Vec2, usize)>,
+    /// The height of `max_lines` lines, or infinity.
+    max_height: f32,
+    /// Sections that are interactive spans, with their action ids.
+    span_actions: Vec<(usize, String)>,
+    /// The spans as laid out, and what they were laid out for.
+    span_layouts: Option<(MeasureKey, Vec<SpanLayout>)>,
+    /// The lines as laid out, if the style sets a line height
+    /// or decorates the text, and what they were laid out for.
+    line_layouts: Option<(MeasureKey, Vec<LineLayout>)>,
+    /// Each line in its own blob, if the style sets a line height.
+    lines: Vec<Line>,
+    /// The size of the text as drawn, before it is cut off.
+    content_size: Vec2,
+    /// Index into `span_actions` of the span under the mouse.
+    hovered_span: Option<usize>,
+    /// Whether span colors changed since the paragraph was created.
+    recolor: bool,
+    on_span_click: Option<SpanCallback>,
 }
 
 impl Text {
     pub fn from_spec(spec: &TextSpec) -> Self {
-        let (initial_text, align_h, align_v) = match spec {
-            TextSpec::Simple(text) => (text.as_str(), Align::default(), Align::default()),
-            TextSpec::Complex {
-                text,
-                align_h,
-                align_v,
-                ..
-            } => (
-                text.as_ref().map(String::as_str).unwrap_or_default(),
-                align_h.into_dume(),
-                align_v.into_dume(),
-            ),
+        let (initial_text, markup) = match spec {
+            TextSpec::Simple(text) => (text.as_str(), false),
+            TextSpec::Complex { text, markup, .. } => {
+                (text.as_deref().unwrap_or_default(), *markup)
+            }
         };
-        Self {
-            text: dume::Text::from_sections([TextSection::Text {
-                text: initial_text.into(),
-                style: Default::default(),
-            }]),
-            paragraph: None,
+        let mut text = Self::new(dume::Text::from_sections([TextSection::Text {
+            text: initial_text.into(),
+            style: Default::default(),
+        }]));
+        if markup {
+            text.set_markup(initial_text);
+        }
+        if let TextSpec::Complex {
             align_h,
             align_v,
+            max_lines,
+            overflow,
+            wrap,
+            ..
+        } = spec
+        {
+            text.align_h = *align_h;
+            text.align_v = *align_v;
+            text.max_lines = *max_lines;
+            text.overflow = *overflow;
+            text.wrap = *wrap;
         }
+        text
     }
 
     pub fn new(text: dume::Text) -> Self {
         Self {
             text,
+            align_h: None,
+            align_v: None,
+            max_lines: None,
+            overflow: None,
+            wrap: None,
             paragraph: None,
-            align_h: Align::default(),
-            align_v: Align::default(),
+            shadow_paragraph: None,
+            shortened: None,
+            max_height: f32::INFINITY,
+            span_actions: Vec::new(),
+            span_layouts: None,
+            line_layouts: None,
+            lines: Vec::new(),
+            content_size: Vec2::ZERO,
+            hovered_span: None,
+            recolor: false,
+            on_span_click: None,
         }
     }
 
+    /// Sets the text, removing any interactive spans.
     pub fn set_text(&mut self, text: dume::Text) -> &mut Self {
         self.text = text;
         self.paragraph = None;
+        self.span_actions.clear();
+        self.span_layouts = None;
+        self.line_layouts = None;
+        self.hovered_span = None;
+        self
+    }
+
+    /// Sets the text from markup, in which `[label](action)`
+    /// makes `label` an interactive span with the action id `action`.
+    ///
+    /// Texts created from a spec are only parsed as
+    /// markup if the spec sets `markup: true`.
+    pub fn set_markup(&mut self, markup: &str) -> &mut Self {
+        let mut actions = Vec::new();
+        let sections: Vec<_> = spans::parse_markup(markup)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (text, action))| {
+                if let Some(action) = action {
+                    actions.push((index, action));
+                }
+                TextSection::Text {
+                    text: text.into(),
+                    style: Default::default(),
+                }
+            })
+            .collect();
+        self.set_text(dume::Text::from_sections(sections));
+        self.span_actions = actions;
+        self
+    }
+
+    /// Makes the section at index `section` of the text an
+    /// interactive span with the action id `id`.
+    ///
+    /// Spans are removed by `set_text`.
+    pub fn set_span_action(&mut self, section: usize, id: impl Into<String>) -> &mut Self {
+        let id = id.into();
+        match self.span_actions.iter_mut().find(|(s, _)| *s == section) {
+            Some((_, action)) => *action = id,
+            None => self.span_actions.push((section, id)),
+        }
+        self.paragraph = None;
+        self.span_layouts = None;
         self
     }
 
-    fn create_paragraph(
+    /// Causes a message to be sent when the user clicks an
+    /// interactive span. The message is created from the span's action id.
+    ///
+    /// If an `on_span_click` message is already set, it is overriden.
+    pub fn on_span_click<Message: 'static>(
         &mut self,
+        mut message: impl FnMut(&str) -> Message + 'static,
+    ) -> &mut Self {
+        self.on_span_click = Some(Box::new(move |id| Box::new(message(id))));
+        self
+    }
+
+    fn truncation(&self, style: &Style) -> Truncation {
+        Truncation {
+            max_lines: self.max_lines.or(style.max_lines),
+            overflow: self.overflow.unwrap_or(style.overflow),
+            wrap: self.wrap.or(style.wrap).unwrap_or(true),
+        }
+    }
+
+    fn options(&self, style: &Style) -> TextOptions {
+        TextOptions {
+            wrap_lines: self.truncation(style).wrap,
+            baseline: Baseline::Top,
+            align_h: self.align_h.unwrap_or(style.default_align_h).into_dume(),
+            align_v: self.align_v.unwrap_or(style.default_align_v).into_dume(),
+        }
+    }
+
+    /// Copies the text with the style's span colors applied.
+    fn colored_text(&self, style: &Style) -> dume::Text {
+        let sections =
+            self.text
+                .sections()
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(index, mut section)| {
+                    let span = self.span_actions.iter().position(|(s, _)| *s == index);
+                    if let (
+                        Some(span),
+                        TextSection::Text {
+                            style: text_style, ..
+                        },
+                    ) = (span, &mut section)
+                    {
+                        if let Some(color) = style.span_color(self.hovered_span == Some(span)) {
+                            text_style.color = Some(color.into());
+                        }
+                    }
+                    section
+                });
+        dume::Text::from_sections(sections)
+    }
+
+    fn create_paragraph(&mut self, style: &Style, cx: &mut Context, max_size: Vec2) {
+        let truncation = self.truncation(style);
+        let options = self.options(style);
+        let lay_out = |cx: &mut Context, text: dume::Text| {
+            let mut blob = cx.canvas.context().create_text_blob(text, options);
+            cx.canvas.context().resize_text_blob(&mut blob, max_size);
+            blob
+        };
+
+        // Whether the text fits is checked against its natural
+        // layout, but it is sized with the style's line height.
+        let (limit_height, max_height) = match truncation.max_lines {
+            Some(max_lines) => {
+                let natural = line_height(style, cx, options);
+                let max_lines = max_lines as f32;
+                (
+                    max_lines * natural,
+                    max_lines * style.line_height.unwrap_or(natural),
+                )
+            }
+            None => (f32::INFINITY, f32::INFINITY),
+        };
+        self.max_height = max_height;
+        let limit = vec2(max_size.x, limit_height);
+
+        let source = self.colored_text(style);
+        let mut blob = lay_out(cx, style.apply_defaults(&source, None));
+        let mut shortened_text = None;
+        self.shortened = None;
+        if truncation.overflow == TextOverflow::Ellipsis && !fits(&blob, limit) {
+            let len = longest_fitting(char_count(&source), |len| {
+                let text = style.apply_defaults(&shortened(&source, len), None);
+                fits(&lay_out(cx, text), limit)
+            });
+            let text = shortened(&source, len);
+            blob = lay_out(cx, style.apply_defaults(&text, None));
+            shortened_text = Some(text);
+            self.shortened = Some((max_size, len));
+        }
+        self.paragraph = Some(blob);
+
+        let text = shortened_text.as_ref().unwrap_or(&source);
+        self.shadow_paragraph = style
+            .text_shadow
+            .as_ref()
+            .map(|shadow| lay_out(cx, style.apply_defaults(text, Some(shadow.color))));
+    }
+
+    /// Creates the paragraph again with the current span colors,
+    /// keeping the length the text was shortened to. Colors
+    /// do not change the layout, so neither spans nor the
+    /// shadow need to be laid out again.
+    fn recolor_paragraph(&mut self, style: &Style, cx: &mut Context, max_size: Vec2) {
+        let text = self.shown(self.colored_text(style));
+        let mut blob = cx
+            .canvas
+            .context()
+            .create_text_blob(style.apply_defaults(&text, None), self.options(style));
+        cx.canvas.context().resize_text_blob(&mut blob, max_size);
+        self.paragraph = Some(blob);
+    }
+
+    /// Shortens `text` to the length the paragraph was shortened to, if it was.
+    fn shown(&self, text: dume::Text) -> dume::Text {
+        match self.shortened {
+            Some((_, len)) => shortened(&text, len),
+            None => text,
+        }
+    }
+
+    /// Lays out the spans and, if the style needs them, the lines
+    /// of the text as shown.
+    fn measure(
+        &self,
         style: &Style,
         cx: &mut Context,
         max_size: Vec2,
-    ) -> &mut TextBlob {
-        let dume_text = &mut self.text;
-        dume_text.set_default_size(style.default_size);
-        dume_text.set_default_color(style.default_color.into());
-        dume_text.set_default_font_family(style.default_font_family.clone().into());
-
-        let mut blob = cx.canvas.context().create_text_blob(
-            dume_text,
-            TextOptions {
-                wrap_lines: true,
-                baseline: Baseline::Top,
-                align_h: self.align_h,
-                align_v: self.align_v,
-            },
+    ) -> (Vec<SpanLayout>, Vec<LineLayout>) {
+        let options = self.options(style);
+        let line_height = line_height(style, cx, options);
+        let width = self.paragraph.as_ref().map_or(0., |p| p.size().x);
+        let text = self.shown(self.text.clone());
+        let mut measurer = Measurer::new(
+            cx.canvas.context(),
+            style,
+            &text,
+            options,
+            max_size,
+            line_height,
+            width,
         );
-        cx.canvas.context().resize_text_blob(&mut blob, max_size);
-        self.paragraph = Some(blob);
-        self.paragraph.as_mut().unwrap()
+        measurer.set_line_spacing(style.line_height.unwrap_or(line_height));
+
+        // Spans cut off by an ellipsis are only hit where they are still shown.
+        let shown = self.shortened.map_or(usize::MAX, |(_, len)| len);
+        let ranges = section_ranges(&self.text);
+        let spans = self
+            .span_actions
+            .iter()
+            .enumerate()
+            .map(|(action, (section, _))| {
+                let range = ranges.get(*section).cloned().unwrap_or(0..0);
+                let end = range.end.min(shown);
+                SpanLayout {
+                    action,
+                    rects: measurer.rects(range.start.min(end)..end),
+                }
+            })
+            .collect();
+        let lines = if style.lays_out_lines() {
+            measurer.lines()
+        } else {
+            Vec::new()
+        };
+        (spans, lines)
+    }
+
+    /// Lays out each line of the text as shown on its own,
+    /// so that lines can be drawn the style's line height apart.
+    fn create_lines(&self, style: &Style, cx: &mut Context) -> Vec<Line> {
+        let layouts = match &self.line_layouts {
+            Some((_, layouts)) if style.line_height.is_some() => layouts,
+            _ => return Vec::new(),
+        };
+        // Lines are already broken and aligned by the measurer.
+        let options = TextOptions {
+            wrap_lines: false,
+            align_h: dume::Align::Start,
+            ..self.options(style)
+        };
+        let text = self.shown(self.colored_text(style));
+        let lay_out = |cx: &mut Context, text: &dume::Text, color| {
+            let mut blob = cx
+                .canvas
+                .context()
+                .create_text_blob(style.apply_defaults(text, color), options);
+            cx.canvas
+                .context()
+                .resize_text_blob(&mut blob, Vec2::splat(f32::INFINITY));
+            blob
+        };
+        layouts
+            .iter()
+            .map(|layout| {
+                let line = dume::Text::from_sections(slice(&text, layout.range.clone()));
+                Line {
+                    pos: layout.rect.pos,
+                    paragraph: lay_out(cx, &line, None),
+                    shadow_paragraph: style
+                        .text_shadow
+                        .as_ref()
+                        .map(|shadow| lay_out(cx, &line, Some(shadow.color))),
+                }
+            })
+            .collect()
     }
+
+    /// Finds the span at a position, as an index into `span_actions`.
+    fn span_at(&self, pos: Vec2) -> Option<usize> {
+        let (_, layouts) = self.span_layouts.as_ref()?;
+        layouts
+            .iter()
+            .find(|layout| layout.contains(pos))
+            .map(|layout| layout.action)
+    }
+}
+
+/// A line of text laid out on its own.
+struct Line {
+    /// Where the line is drawn, from its alignment and the line height.
+    pos: Vec2,
+    paragraph: TextBlob,
+    shadow_paragraph: Option<TextBlob>,
+}
+
+/// How text that does not fit is cut short, combining the spec and the style.
+#[derive(Copy, Clone, Debug)]
+struct Truncation {
+    max_lines: Option<u32>,
+    overflow: TextOverflow,
+    wrap: bool,
 }
 
+impl Truncation {
+    /// Whether any setting cuts the text short. Texts without
+    /// one keep their full size, even if it overflows.
+    fn limits_size(&self) -> bool {
+        self.max_lines.is_some() || self.overflow != TextOverflow::Wrap || !self.wrap
+    }
+}
+
+/// The style of a `Text`.
+///
+/// Letter spacing is not supported: dume places glyphs itself
+/// and has no option for the space between them.
 #[derive(Debug, serde::Deserialize)]
 pub struct Style {
     default_color: Color,
     default_size: f32,
     default_font_family: String,
+    /// Applies to sections of normal weight.
+    #[serde(default)]
+    default_weight: FontWeight,
+    /// Applies to upright sections.
+    #[serde(default)]
+    default_font_style: FontStyle,
+    /// Alignment for texts whose spec does not set one.
+    #[serde(default)]
+    default_align_h: Align,
+    #[serde(default)]
+    default_align_v: Align,
+    text_shadow: Option<TextShadow>,
+    /// The most lines shown, unless the spec sets it.
+    max_lines: Option<u32>,
+    #[serde(default)]
+    overflow: TextOverflow,
+    /// Whether lines break at the widget's width. Defaults to `true`.
+    wrap: Option<bool>,
+    /// The distance between the tops of consecutive lines.
+    /// Defaults to the height of a line in the default font.
+    line_height: Option<f32>,
+    /// Draws a line under the text, in the default color.
+    #[serde(default)]
+    underline: bool,
+    /// Draws a line through the text, in the default color.
+    #[serde(default)]
+    strikethrough: bool,
+    /// The color of interactive spans. Defaults to the section's color.
+    span_color: Option<Color>,
+    /// The color of the interactive span under the mouse.
+    /// Defaults to `span_color`.
+    hovered_span_color: Option<Color>,
+    #[serde(default)]
+    span_underline: bool,
+}
+
+impl Style {
+    fn span_color(&self, hovered: bool) -> Option<Color> {
+        if hovered {
+            self.hovered_span_color.or(self.span_color)
+        } else {
+            self.span_color
+        }
+    }
+
+    /// Whether texts need their lines laid out to be drawn.
+    fn lays_out_lines(&self) -> bool {
+        self.line_height.is_some() || self.underline || self.strikethrough
+    }
+
+    /// Copies `text` with the style's size, font and, for sections
+    /// that leave them at normal, weight and font style. If `color`
+    /// is set, every section is recolored with it.
+    fn apply_defaults(&self, text: &dume::Text, color: Option<Color>) -> dume::Text {
+        let sections = text.sections().iter().cloned().map(|mut section| {
+            if let TextSection::Text { style, .. } = &mut section {
+                if style.font.weight == Default::default() {
+                    style.font.weight = self.default_weight.into();
+                }
+                if style.font.style == Default::default() {
+                    style.font.style = self.default_font_style.into();
+                }
+                if let Some(color) = color {
+                    style.color = Some(color.into());
+                }
+            }
+            section
+        });
+        let mut text = dume::Text::from_sections(sections);
+        text.set_default_size(self.default_size);
+        text.set_default_color(color.unwrap_or(self.default_color).into());
+        text.set_default_font_family(self.default_font_family.clone().into());
+        text
+    }
+}
+
+/// Measures the height of a line of text in the style's default font.
+fn line_height(style: &Style, cx: &mut Context, options: TextOptions) -> f32 {
+    let line = dume::Text::from_sections([TextSection::Text {
+        text: "M".into(),
+        style: Default::default(),
+    }]);
+    let mut blob = cx
+        .canvas
+        .context()
+        .create_text_blob(style.apply_defaults(&line, None), options);
+    cx.canvas
+        .context()
+        .resize_text_blob(&mut blob, Vec2::splat(f32::INFINITY));
+    blob.size().y
+}
+
+fn fits(blob: &TextBlob, limit: Vec2) -> bool {
+    // Leave some room for rounding in text layout.
+    let size = blob.size() - Vec2::splat(0.5);
+    size.x <= limit.x && size.y <= limit.y
+}
+
+fn section_len(section: &TextSection) -> usize {
+    match section {
+        TextSection::Text { text, .. } => text.chars().count(),
+        TextSection::Icon { .. } => 1,
+    }
+}
+
+/// Counts the characters of a text, with each icon as one.
+fn char_count(text: &dume::Text) -> usize {
+    text.sections().iter().map(section_len).sum()
+}
+
+/// The range of characters each section of a text covers.
+fn section_ranges(text: &dume::Text) -> Vec<Range<usize>> {
+    let mut start = 0;
+    text.sections()
+        .iter()
+        .map(|section| {
+            let range = start..start + section_len(section);
+            start = range.end;
+            range
+        })
+        .collect()
+}
+
+/// Copies the sections covering a range of characters.
+fn slice(text: &dume::Text, range: Range<usize>) -> Vec<TextSection> {
+    text.sections()
+        .iter()
+        .zip(section_ranges(text))
+        .filter_map(|(section, covers)| {
+            let start = range.start.max(covers.start);
+            let end = range.end.min(covers.end);
+            if start >= end {
+                return None;
+            }
+            Some(match section {
+                TextSection::Text { text, style } => TextSection::Text {
+                    text: text
+                        .chars()
+                        .skip(start - covers.start)
+                        .take(end - start)
+                        .collect::<String>()
+                        .into(),
+                    style: style.clone(),
+                },
+                TextSection::Icon { .. } => section.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Keeps the first `len` characters of `text` and ends it with an ellipsis
+/// in the style of the last character kept.
+fn shortened(text: &dume::Text, len: usize) -> dume::Text {
+    let mut sections = slice(text, 0..len);
+    let ellipsis_style = sections
+        .iter()
+        .rev()
+        .find_map(|section| match section {
+            TextSection::Text { style, .. } => Some(style.clone()),
+            TextSection::Icon { .. } => None,
+        })
+        .unwrap_or_default();
+
+    // Keep the ellipsis next to the last word.
+    if let Some(TextSection::Text { text, .. }) = sections.last_mut() {
+        *text = text.trim_end().into();
+    }
+    sections.push(TextSection::Text {
+        text: ELLIPSIS.into(),
+        style: ellipsis_style,
+    });
+    dume::Text::from_sections(sections)
+}
+
+/// Finds the largest `len` up to `max` for which `fits(len)` holds,
+/// assuming that it holds for every smaller length too.
+pub(super) fn longest_fitting(max: usize, mut fits: impl FnMut(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, max);
+    while low < high {
+        // Round up, so that `low` always moves.
+        let mid = low + (high - low + 1) / 2;
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// A copy of the text drawn in one color behind it.
+///
+/// Unlike box shadows, text shadows cannot be blurred.
+#[derive(Debug, serde::Deserialize)]
+pub struct TextShadow {
+    color: Color,
+    #[serde(default)]
+    offset: [f32; 2],
 }
 
 impl Widget for Text {
@@ -99,6 +631,8 @@ impl Widget for Text {
     fn style_changed(&mut self, _style: &Self::Style, _data: &mut WidgetData, _cx: Context) {
         // Re-create the paragraph so text style is updated.
         self.paragraph = None;
+        self.span_layouts = None;
+        self.line_layouts = None;
     }
 
     fn layout(
@@ -108,24 +642,254 @@ impl Widget for Text {
         mut cx: Context,
         max_size: Vec2,
     ) {
-        let paragraph = match &mut self.paragraph {
-            Some(p) => {
+        // Shortened text has to be shortened again to fit a new size.
+        let stale = matches!(self.shortened, Some((size, _)) if size != max_size);
+        let mut recreated = true;
+        if self.paragraph.is_none() || stale {
+            self.create_paragraph(style, &mut cx, max_size);
+        } else {
+            if self.recolor {
+                self.recolor_paragraph(style, &mut cx, max_size);
+            } else if let Some(p) = &mut self.paragraph {
                 cx.canvas.context().resize_text_blob(p, max_size);
-                p
+                recreated = false;
+            }
+            if let Some(shadow) = &mut self.shadow_paragraph {
+                cx.canvas.context().resize_text_blob(shadow, max_size);
+            }
+        }
+        self.recolor = false;
+        // Finding line breaks is costly, so spans and lines are only
+        // laid out again if the width or the shortened text changes.
+        let key = (max_size.x, self.shortened.map(|(_, len)| len));
+        let spans_stale = !self.span_actions.is_empty()
+            && !matches!(&self.span_layouts, Some((k, _)) if *k == key);
+        let lines_stale =
+            style.lays_out_lines() && !matches!(&self.line_layouts, Some((k, _)) if *k == key);
+        if spans_stale || lines_stale {
+            let (spans, lines) = self.measure(style, &mut cx, max_size);
+            self.span_layouts = Some((key, spans));
+            self.line_layouts = Some((key, lines));
+        }
+        if recreated || lines_stale {
+            self.lines = self.create_lines(style, &mut cx);
+        }
+        let paragraph = self.paragraph.as_ref().unwrap();
+
+        self.content_size = match style.line_height {
+            Some(line_height) if !self.lines.is_empty() => {
+                vec2(paragraph.size().x, self.lines.len() as f32 * line_height)
             }
-            None => self.create_paragraph(style, &mut cx, max_size),
+            _ => paragraph.size(),
         };
+        let mut size = self.content_size;
+        if self.truncation(style).limits_size() {
+            size = size.min(vec2(max_size.x, max_size.y.min(self.max_height)));
+        }
+        data.set_size(size);
+    }
+
+    fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
+        let paragraph = self
+            .paragraph
+            .as_ref()
+            .expect("paragraph not created in layout()");
+        // Cut off text that did not fit, like lines past `max_lines`.
+        // Leave some room for rounding in text layout.
+        let overflow = self.content_size - data.size() - Vec2::splat(0.5);
+        let clipped = overflow.x > 0. || overflow.y > 0.;
+        if clipped {
+            cx.push_clip(Rect::new(Vec2::ZERO, data.size()));
+        }
+
+        if self.lines.is_empty() {
+            if let (Some(shadow), Some(shadow_paragraph)) =
+                (&style.text_shadow, &self.shadow_paragraph)
+            {
+                cx.canvas
+                    .draw_text(shadow_paragraph, Vec2::from(shadow.offset), 1.);
+            }
+            cx.canvas.draw_text(paragraph, Vec2::ZERO, 1.);
+        } else {
+            if let Some(shadow) = &style.text_shadow {
+                for line in &self.lines {
+                    if let Some(shadow_paragraph) = &line.shadow_paragraph {
+                        cx.canvas.draw_text(
+                            shadow_paragraph,
+                            line.pos + Vec2::from(shadow.offset),
+                            1.,
+                        );
+                    }
+                }
+            }
+            for line in &self.lines {
+                cx.canvas.draw_text(&line.paragraph, line.pos, 1.);
+            }
+        }
 
-        data.set_size(paragraph.size());
+        if let Some((_, layouts)) = &self.line_layouts {
+            for LineLayout { rect, .. } in layouts {
+                let mut decorations = Vec::new();
+                if style.underline {
+                    decorations.push(rect.pos.y + rect.size.y - 1.);
+                }
+                if style.strikethrough {
+                    decorations.push(rect.pos.y + rect.size.y / 2.);
+                }
+                for y in decorations {
+                    cx.canvas
+                        .begin_path()
+                        .move_to(vec2(rect.pos.x, y))
+                        .line_to(vec2(rect.pos.x + rect.size.x, y))
+                        .stroke_width(1.)
+                        .solid_color(style.default_color)
+                        .stroke();
+                }
+            }
+        }
+
+        if let (true, Some((_, layouts))) = (style.span_underline, &self.span_layouts) {
+            for layout in layouts {
+                let color = style
+                    .span_color(self.hovered_span == Some(layout.action))
+                    .unwrap_or(style.default_color);
+                for rect in &layout.rects {
+                    cx.canvas
+                        .begin_path()
+                        .rect(
+                            vec2(rect.pos.x, rect.pos.y + rect.size.y - 1.),
+                            vec2(rect.size.x, 1.),
+                        )
+                        .solid_color(color)
+                        .fill();
+                }
+            }
+        }
+
+        if clipped {
+            cx.pop_clip();
+        }
+    }
+
+    fn handle_event(&mut self, _data: &mut WidgetData, mut cx: Context, event: &Event) {
+        match event {
+            Event::MouseMove { pos } => {
+                let hovered = self.span_at(*pos);
+                if hovered != self.hovered_span {
+                    self.hovered_span = hovered;
+                    self.recolor = true;
+                }
+            }
+            Event::MousePress {
+                pos,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if let (Some(span), Some(on_span_click)) =
+                    (self.span_at(*pos), self.on_span_click.as_mut())
+                {
+                    cx.send_message(on_span_click(&self.span_actions[span].1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn hit_test(&self, _data: &WidgetData, pos: Vec2) -> HitTestResult {
+        if self.span_at(pos).is_some() {
+            HitTestResult::Hit
+        } else {
+            HitTestResult::Missed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &dume::Text) -> String {
+        text.sections()
+            .iter()
+            .map(|section| match section {
+                TextSection::Text { text, .. } => text.to_string(),
+                TextSection::Icon { name, .. } => format!("[{}]", name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shorten() {
+        let text = dume::Text::from_sections([
+            TextSection::Text {
+                text: "Iron ".into(),
+                style: Default::default(),
+            },
+            TextSection::Icon {
+                name: "ingot".into(),
+                size: 16.,
+            },
+            TextSection::Text {
+                text: " ingots".into(),
+                style: Default::default(),
+            },
+        ]);
+        assert_eq!(char_count(&text), 13);
+        assert_eq!(plain(&shortened(&text, 5)), "Iron…");
+        assert_eq!(plain(&shortened(&text, 9)), "Iron [ingot] in…");
+        assert_eq!(plain(&shortened(&text, 0)), "…");
     }
 
-    fn paint(&mut self, _style: &Self::Style, _data: &mut WidgetData, cx: Context) {
-        cx.canvas.draw_text(
-            self.paragraph
-                .as_ref()
-                .expect("paragraph not created in layout()"),
-            Vec2::ZERO,
-            1.,
+    #[test]
+    fn markup_is_opt_in() {
+        let text = |yaml: &str| {
+            let spec: TextSpec = serde_yaml::from_str(yaml).unwrap();
+            let text = Text::from_spec(&spec);
+            (plain(&text.text), text.span_actions)
+        };
+        assert_eq!(
+            text(r"'[manual](open) \\'"),
+            (r"[manual](open) \\".to_owned(), Vec::new())
+        );
+        assert_eq!(
+            text(r"{text: '[manual](open) \\'}"),
+            (r"[manual](open) \\".to_owned(), Vec::new())
         );
+        assert_eq!(
+            text(r"{text: '[manual](open) \\', markup: true}"),
+            (r"manual \".to_owned(), vec![(0, "open".to_owned())])
+        );
+    }
+
+    #[test]
+    fn only_truncated_text_is_limited() {
+        let truncation = |max_lines, overflow, wrap| Truncation {
+            max_lines,
+            overflow,
+            wrap,
+        };
+        assert!(!truncation(None, TextOverflow::Wrap, true).limits_size());
+        assert!(truncation(Some(2), TextOverflow::Wrap, true).limits_size());
+        assert!(truncation(None, TextOverflow::Ellipsis, true).limits_size());
+        assert!(truncation(None, TextOverflow::Wrap, false).limits_size());
+    }
+
+    #[test]
+    fn lines_are_laid_out_when_needed() {
+        let style = |yaml: &str| {
+            let base = "{default_color: black, default_size: 16, default_font_family: Sans";
+            serde_yaml::from_str::<Style>(&format!("{}{}}}", base, yaml)).unwrap()
+        };
+        assert!(!style("").lays_out_lines());
+        assert!(style(", line_height: 24").lays_out_lines());
+        assert!(style(", underline: true").lays_out_lines());
+        assert!(style(", strikethrough: true").lays_out_lines());
+    }
+
+    #[test]
+    fn longest_fitting_length() {
+        assert_eq!(longest_fitting(10, |len| len <= 4), 4);
+        assert_eq!(longest_fitting(10, |_| true), 10);
+        assert_eq!(longest_fitting(10, |_| false), 0);
     }
 }
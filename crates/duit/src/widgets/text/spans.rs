@@ -0,0 +1,338 @@
+//! Interactive spans: sections of a `Text` tagged with an action id.
+
+use std::ops::Range;
+
+use dume::{Align, Rect, TextOptions, TextSection};
+use glam::{vec2, Vec2};
+
+use super::{longest_fitting, slice, Style};
+
+/// Stands in for an icon when looking for word boundaries.
+const OBJECT_REPLACEMENT: char = '\u{fffc}';
+
+/// A span as laid out: the rectangle it covers on each of its lines.
+#[derive(Debug)]
+pub(super) struct SpanLayout {
+    /// Index into the text's span actions.
+    pub action: usize,
+    pub rects: Vec<Rect>,
+}
+
+impl SpanLayout {
+    pub fn contains(&self, pos: Vec2) -> bool {
+        self.rects.iter().any(|rect| rect.contains(pos))
+    }
+}
+
+/// A line as laid out: the characters on it, and the rectangle
+/// they cover without the whitespace at the end of the line.
+#[derive(Debug)]
+pub(super) struct LineLayout {
+    pub range: Range<usize>,
+    pub rect: Rect,
+}
+
+/// Splits markup into sections, each with the action of the
+/// `[label](action)` link it comes from, if any.
+///
+/// A backslash escapes the character after it. Brackets that
+/// do not form a link are kept as they are.
+pub(super) fn parse_markup(markup: &str) -> Vec<(String, Option<String>)> {
+    let mut sections = Vec::new();
+    let mut plain = String::new();
+    let mut rest = markup;
+    while let Some(c) = rest.chars().next() {
+        if c == '\\' {
+            let mut chars = rest[1..].chars();
+            plain.push(chars.next().unwrap_or('\\'));
+            rest = chars.as_str();
+            continue;
+        }
+        if c == '[' {
+            if let Some((label, action, after)) = parse_link(rest) {
+                if !plain.is_empty() {
+                    sections.push((std::mem::take(&mut plain), None));
+                }
+                sections.push((label.to_owned(), Some(action.to_owned())));
+                rest = after;
+                continue;
+            }
+        }
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !plain.is_empty() || sections.is_empty() {
+        sections.push((plain, None));
+    }
+    sections
+}
+
+/// Parses a link at the start of `s`, returning its label,
+/// its action and the text after it.
+fn parse_link(s: &str) -> Option<(&str, &str, &str)> {
+    let (label, rest) = s.strip_prefix('[')?.split_once("](")?;
+    let (action, rest) = rest.split_once(')')?;
+    let action = action.trim();
+    if label.is_empty() || label.contains(['[', ']'].as_ref()) || action.is_empty() {
+        return None;
+    }
+    Some((label, action, rest))
+}
+
+/// Finds where ranges of characters end up in a laid out text.
+///
+/// `TextBlob` does not expose its glyph positions, so this lays out
+/// parts of the text and measures them instead, like `TextArea` does.
+/// Every line is assumed to be `line_height` tall, and lines
+/// are `line_spacing` apart, centered in that space.
+///
+/// Line breaks are found once, when the measurer is created,
+/// so that measuring each span only lays out its own lines.
+/// Each line is found by laying out text from its start, no
+/// more than about twice the line's length at a time.
+pub(super) struct Measurer<'a> {
+    context: &'a dume::Context,
+    style: &'a Style,
+    text: &'a dume::Text,
+    options: TextOptions,
+    max_size: Vec2,
+    line_height: f32,
+    /// The distance between the tops of consecutive lines.
+    line_spacing: f32,
+    /// The width lines are aligned within.
+    width: f32,
+    chars: Vec<char>,
+    /// The index of the first character on each line.
+    line_starts: Vec<usize>,
+    /// The offset of each line from the left edge, from its alignment.
+    line_offsets: Vec<f32>,
+}
+
+impl<'a> Measurer<'a> {
+    pub fn new(
+        context: &'a dume::Context,
+        style: &'a Style,
+        text: &'a dume::Text,
+        options: TextOptions,
+        max_size: Vec2,
+        line_height: f32,
+        width: f32,
+    ) -> Self {
+        let chars = text
+            .sections()
+            .iter()
+            .flat_map(|section| match section {
+                TextSection::Text { text, .. } => text.chars().collect(),
+                TextSection::Icon { .. } => vec![OBJECT_REPLACEMENT],
+            })
+            .collect();
+        let mut measurer = Self {
+            context,
+            style,
+            text,
+            options,
+            max_size,
+            line_height,
+            line_spacing: line_height,
+            width,
+            chars,
+            line_starts: vec![0],
+            line_offsets: Vec::new(),
+        };
+        if line_height > 0. {
+            measurer.line_starts = measurer.find_line_starts();
+        }
+        measurer.line_offsets = (0..measurer.line_starts.len())
+            .map(|line| measurer.line_offset(line))
+            .collect();
+        measurer
+    }
+
+    /// Moves lines `spacing` apart instead of `line_height`.
+    pub fn set_line_spacing(&mut self, spacing: f32) {
+        self.line_spacing = spacing;
+    }
+
+    /// Every line of the text, with the rectangle it covers.
+    pub fn lines(&self) -> Vec<LineLayout> {
+        (0..self.line_starts.len())
+            .map(|line| {
+                let range = self.line_starts[line]..self.line_end(line);
+                let mut end = range.end;
+                while end > range.start && self.chars[end - 1].is_whitespace() {
+                    end -= 1;
+                }
+                let rect = self.rects(range.start..end).pop().unwrap_or_else(|| {
+                    Rect::new(
+                        vec2(self.line_offsets[line], self.line_top(line)),
+                        vec2(0., self.line_height),
+                    )
+                });
+                LineLayout { range, rect }
+            })
+            .collect()
+    }
+
+    /// The rectangles covered by a range of characters, one per line.
+    pub fn rects(&self, range: Range<usize>) -> Vec<Rect> {
+        if range.is_empty() || self.line_height <= 0. {
+            return Vec::new();
+        }
+        let (first, last) = (self.line_at(range.start), self.line_at(range.end - 1));
+        (first..=last)
+            .map(|line| {
+                let line_start = self.line_starts[line];
+                let start = range.start.max(line_start);
+                let end = range.end.min(self.line_end(line));
+
+                let x = self.line_offsets[line] + self.size(line_start..start, false).x;
+                Rect::new(
+                    vec2(x, self.line_top(line)),
+                    vec2(self.size(start..end, false).x, self.line_height),
+                )
+            })
+            .collect()
+    }
+
+    /// The index of the first character on each line.
+    fn find_line_starts(&self) -> Vec<usize> {
+        let mut starts = vec![0];
+        if !self.options.wrap_lines {
+            let breaks = self.chars.iter().enumerate().filter(|(_, &c)| c == '\n');
+            starts.extend(breaks.map(|(i, _)| i + 1));
+            return starts;
+        }
+        let mut start = self.line_len(0);
+        while start < self.chars.len() {
+            starts.push(start);
+            start += self.line_len(start);
+        }
+        starts
+    }
+
+    /// The number of characters on the line starting at `start`.
+    fn line_len(&self, start: usize) -> usize {
+        let rest = self.chars.len() - start;
+        let fits = |len: usize| len == 0 || self.is_on_first_line(start, start + len - 1);
+        // Double the length until it no longer fits, then search
+        // between the last two lengths, so that no probe lays out
+        // much more than the line.
+        let (mut low, mut step) = (0, 1);
+        while low < rest {
+            let high = (low + step).min(rest);
+            if !fits(high) {
+                // Keep at least one character, in case a word is
+                // broken because it is too long for a line.
+                return (low + longest_fitting(high - low - 1, |len| fits(low + len))).max(1);
+            }
+            low = high;
+            step *= 2;
+        }
+        rest
+    }
+
+    /// Where `line` is drawn from the top of the text.
+    fn line_top(&self, line: usize) -> f32 {
+        line as f32 * self.line_spacing + (self.line_spacing - self.line_height) / 2.
+    }
+
+    /// The line the character at `index` is on, from the line starts.
+    fn line_at(&self, index: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= index) - 1
+    }
+
+    /// The index after the last character on `line`.
+    fn line_end(&self, line: usize) -> usize {
+        self.line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.chars.len())
+    }
+
+    /// How far `line` is moved right by its alignment.
+    fn line_offset(&self, line: usize) -> f32 {
+        let line_width = || {
+            self.size(self.line_starts[line]..self.line_end(line), false)
+                .x
+        };
+        match self.options.align_h {
+            Align::Start => 0.,
+            Align::Center => (self.width - line_width()) / 2.,
+            Align::End => self.width - line_width(),
+        }
+    }
+
+    /// Whether the character at `index` is on the first line
+    /// of the text laid out from `start`.
+    fn is_on_first_line(&self, start: usize, index: usize) -> bool {
+        // Lay out through the end of the word, so that it
+        // wraps the same way it does in the full text.
+        let end = self.chars[index..]
+            .iter()
+            .position(|c| c.is_whitespace())
+            .map_or(self.chars.len(), |len| index + len)
+            .max(index + 1);
+        let lines = (self.size(start..end, true).y / self.line_height).round() as usize;
+        lines <= 1
+    }
+
+    fn size(&self, range: Range<usize>, wrap: bool) -> Vec2 {
+        if range.is_empty() {
+            return Vec2::ZERO;
+        }
+        let text = dume::Text::from_sections(slice(self.text, range));
+        let options = TextOptions {
+            wrap_lines: wrap,
+            align_h: Align::Start,
+            ..self.options
+        };
+        let mut blob = self
+            .context
+            .create_text_blob(self.style.apply_defaults(&text, None), options);
+        let max_size = if wrap {
+            vec2(self.max_size.x, f32::INFINITY)
+        } else {
+            Vec2::splat(f32::INFINITY)
+        };
+        self.context.resize_text_blob(&mut blob, max_size);
+        blob.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(text: &str, action: Option<&str>) -> (String, Option<String>) {
+        (text.to_owned(), action.map(str::to_owned))
+    }
+
+    #[test]
+    fn markup() {
+        assert_eq!(
+            parse_markup("Read the [manual](open_manual) first."),
+            vec![
+                section("Read the ", None),
+                section("manual", Some("open_manual")),
+                section(" first.", None),
+            ]
+        );
+        assert_eq!(
+            parse_markup("[a](x)[b]( y )"),
+            vec![section("a", Some("x")), section("b", Some("y"))]
+        );
+        assert_eq!(parse_markup(""), vec![section("", None)]);
+    }
+
+    #[test]
+    fn markup_without_links() {
+        assert_eq!(
+            parse_markup("[not a link] [](empty) [empty]()"),
+            vec![section("[not a link] [](empty) [empty]()", None)]
+        );
+        assert_eq!(
+            parse_markup(r"\[escaped](link) \\"),
+            vec![section(r"[escaped](link) \", None)]
+        );
+    }
+}
@@ -0,0 +1,836 @@
+use std::{ops::Range, rc::Rc};
+
+use duit_core::{spec::widgets::TextAreaSpec, Axis};
+use dume::{Align, Baseline, Canvas, Rect, Text, TextBlob, TextOptions, TextSection, TextStyle};
+use glam::{vec2, Vec2};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::{
+    border::stroke_border,
+    editor::{impl_editing_methods, CaretBlink, Editor, TextCallback},
+    font, widget,
+    widget::Context,
+    Color, Event, FontStyle, FontWeight, Insets, InsetsExt, Widget, WidgetData, WidgetHandle,
+    WidgetPodHandle,
+};
+
+use super::{text::longest_fitting, Scrollable};
+
+/// Line height as a multiple of the font size.
+const LINE_SPACING: f32 = 1.25;
+
+/// A multi-line text input.
+///
+/// Long lines wrap, and text taller than the widget
+/// scrolls vertically inside a `Scrollable`.
+pub struct TextArea {
+    width: Option<f32>,
+    height: Option<f32>,
+
+    scrollable: WidgetHandle<Scrollable>,
+    body: WidgetHandle<TextAreaBody>,
+    queued_child: Option<WidgetPodHandle>,
+
+    focused: bool,
+}
+
+impl TextArea {
+    pub fn from_spec(spec: &TextAreaSpec) -> Self {
+        let mut editor = Editor::new();
+        if let Some(depth) = spec.history_depth {
+            editor.set_history_depth(depth);
+        }
+
+        let body = widget(TextAreaBody {
+            editor,
+            placeholder: spec.placeholder.clone().unwrap_or_default(),
+            limits: Limits {
+                max_len: spec.max_len,
+                max_lines: spec.max_lines,
+            },
+            wrap: spec.wrap,
+            style: None,
+            min_height: 0.,
+
+            composed: String::new(),
+            lines: Vec::new(),
+            lines_dirty: true,
+            wrap_width: 0.,
+            placeholder_paragraph: None,
+
+            caret_pos: Vec2::ZERO,
+            selection_rects: Vec::new(),
+            preedit_rects: Vec::new(),
+            caret_dirty: true,
+            caret_moved: false,
+            goal_x: None,
+
+            focused: false,
+            dragging: false,
+            caret_blink: CaretBlink::new(),
+
+            on_change: None,
+        });
+
+        let child = widget(Scrollable::new(Axis::Vertical));
+        child.borrow_mut().data_mut().add_child(Rc::clone(&body));
+
+        Self {
+            width: spec.width,
+            height: spec.height,
+
+            scrollable: WidgetHandle::new(Rc::clone(&child)),
+            body: WidgetHandle::new(body),
+            queued_child: Some(child),
+
+            focused: false,
+        }
+    }
+
+    pub fn current_input(&self) -> String {
+        self.body.get().editor.text().to_owned()
+    }
+
+    /// Returns the selected text, if any.
+    pub fn selected_text(&self) -> Option<String> {
+        self.body.get().editor.selected_text().map(str::to_owned)
+    }
+
+    /// Replaces the current input and moves the caret to the end.
+    ///
+    /// The replacement can be undone like any other edit.
+    /// Does not trigger the `on_change` message.
+    pub fn set_text(&mut self, text: impl Into<String>) -> &mut Self {
+        self.edit(|editor| editor.set_text(text.into()));
+        self
+    }
+
+    /// Clears the current input.
+    ///
+    /// Does not trigger the `on_change` message.
+    pub fn clear(&mut self) -> &mut Self {
+        self.set_text(String::new())
+    }
+
+    fn edit<R>(&mut self, f: impl FnOnce(&mut Editor) -> R) -> R {
+        let mut body = self.body.get_mut();
+        let result = f(&mut body.editor);
+        body.mark_text_dirty();
+        result
+    }
+
+    fn read_editor<R>(&self, f: impl FnOnce(&Editor) -> R) -> R {
+        f(&self.body.get().editor)
+    }
+
+    fn set_change_callback(&mut self, callback: TextCallback) {
+        self.body.get_mut().on_change = Some(callback);
+    }
+
+    fn lay_out_scrollable(
+        &self,
+        data: &mut WidgetData,
+        cx: &mut Context,
+        size: Vec2,
+        padding: Insets,
+    ) {
+        let mut scrollable = data.child(0);
+        scrollable.layout(cx, size);
+        scrollable.data_mut().set_origin(padding.top_left());
+    }
+
+    /// Scrolls so that the caret is visible, if it moved
+    /// since the last layout. Returns whether the scroll position changed.
+    fn scroll_to_caret(&self, view_height: f32) -> bool {
+        let mut body = self.body.get_mut();
+        if !body.caret_moved {
+            return false;
+        }
+        body.caret_moved = false;
+        let (top, bottom) = body.caret_span();
+
+        let mut scrollable = self.scrollable.get_mut();
+        let pos = scrollable.scroll_pos();
+        let new_pos = if top < pos {
+            top
+        } else if bottom > pos + view_height {
+            bottom - view_height
+        } else {
+            return false;
+        };
+        scrollable.set_scroll_pos(new_pos);
+        true
+    }
+}
+
+impl_editing_methods!(TextArea);
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Style {
+    background_color: Color,
+    border_color: Color,
+    border_width: Insets,
+    border_radius: f32,
+    cursor_color: Color,
+    cursor_width: f32,
+    font: String,
+    font_size: f32,
+    #[serde(default)]
+    font_weight: FontWeight,
+    #[serde(default)]
+    font_style: FontStyle,
+    font_color: Color,
+    placeholder_font_color: Color,
+    selection_color: Color,
+    padding: Insets,
+}
+
+impl Style {
+    fn line_height(&self) -> f32 {
+        self.font_size * LINE_SPACING
+    }
+}
+
+impl Widget for TextArea {
+    type Style = Style;
+
+    fn base_class(&self) -> &str {
+        "text_area"
+    }
+
+    fn layout(
+        &mut self,
+        style: &Self::Style,
+        data: &mut WidgetData,
+        mut cx: Context,
+        max_size: Vec2,
+    ) {
+        if let Some(child) = self.queued_child.take() {
+            data.add_child(child);
+        }
+
+        let width = self.width.unwrap_or(max_size.x);
+        let height = self.height.unwrap_or(max_size.y);
+        let inner_size = vec2(width, height) - style.padding.size();
+
+        {
+            let mut body = self.body.get_mut();
+            body.set_style(style);
+            // Fill the visible area, so clicks anywhere inside focus the text.
+            body.min_height = if inner_size.y.is_finite() {
+                inner_size.y
+            } else {
+                0.
+            };
+        }
+
+        self.lay_out_scrollable(data, &mut cx, inner_size, style.padding);
+        if self.scroll_to_caret(inner_size.y) {
+            self.lay_out_scrollable(data, &mut cx, inner_size, style.padding);
+        }
+
+        let height = if height.is_finite() {
+            height
+        } else {
+            data.child(0).data().outer_size().y + style.padding.vertical()
+        };
+        data.set_size(vec2(width, height));
+    }
+
+    fn paint(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
+        let cv = &mut cx.canvas;
+
+        cv.begin_path()
+            .rounded_rect(Vec2::ZERO, data.size(), style.border_radius)
+            .solid_color(style.background_color)
+            .fill();
+
+        stroke_border(
+            cv,
+            Rect::new(Vec2::ZERO, data.size()),
+            style.border_radius,
+            style.border_width,
+            style.border_color,
+        );
+
+        data.paint_children(&mut cx);
+    }
+
+    fn handle_event(&mut self, data: &mut WidgetData, mut cx: Context, event: &Event) {
+        data.pass_event_to_children(&mut cx, event);
+
+        // Clicks on the padding also focus the text, while clicks
+        // on lines scrolled out of view do not.
+        if let Event::MousePress {
+            pos,
+            button: MouseButton::Left,
+            ..
+        } = event
+        {
+            let mut body = self.body.get_mut();
+            body.focused = data.bounds().contains(*pos);
+            body.dragging &= body.focused;
+        }
+
+        let focused = self.body.get().focused;
+        if focused != self.focused {
+            self.focused = focused;
+            // Update style classes
+            data.set_focused(focused);
+        }
+    }
+}
+
+/// Limits on the text in a `TextArea`.
+struct Limits {
+    max_len: Option<usize>,
+    max_lines: Option<usize>,
+}
+
+impl Limits {
+    /// Returns whether the user may edit the text so that it becomes `text`.
+    fn allows(&self, text: &str) -> bool {
+        !matches!(self.max_len, Some(max_len) if text.chars().count() > max_len)
+            && !matches!(self.max_lines, Some(max_lines) if text.split('\n').count() > max_lines)
+    }
+}
+
+/// A line of text as displayed, after wrapping.
+struct Line {
+    /// Byte range within the composed text, excluding any line break.
+    range: Range<usize>,
+    paragraph: TextBlob,
+}
+
+/// The scrolled content of a `TextArea`. Handles
+/// editing and draws the text, caret and selection.
+struct TextAreaBody {
+    editor: Editor,
+    placeholder: String,
+    limits: Limits,
+    wrap: bool,
+    /// The style of the parent `TextArea`, as of its last layout.
+    style: Option<Style>,
+    min_height: f32,
+
+    /// The text being displayed, including text being composed.
+    composed: String,
+    lines: Vec<Line>,
+    lines_dirty: bool,
+    wrap_width: f32,
+    placeholder_paragraph: Option<TextBlob>,
+
+    caret_pos: Vec2,
+    selection_rects: Vec<Rect>,
+    /// Extent of the text being composed by an input method, to be underlined.
+    preedit_rects: Vec<Rect>,
+    caret_dirty: bool,
+    /// Set when the caret moves, so the `TextArea` can scroll to it.
+    caret_moved: bool,
+    /// Horizontal position kept while moving the caret up and down.
+    goal_x: Option<f32>,
+
+    focused: bool,
+    dragging: bool,
+    caret_blink: CaretBlink,
+
+    on_change: Option<TextCallback>,
+}
+
+impl TextAreaBody {
+    fn style(&self) -> &Style {
+        self.style
+            .as_ref()
+            .expect("text area body laid out before its parent")
+    }
+
+    fn set_style(&mut self, style: &Style) {
+        let text_changed = match &self.style {
+            Some(old) => {
+                old.font != style.font
+                    || old.font_size != style.font_size
+                    || old.font_color != style.font_color
+                    || old.placeholder_font_color != style.placeholder_font_color
+            }
+            None => true,
+        };
+        if self.style.as_ref() != Some(style) {
+            self.style = Some(style.clone());
+        }
+        if text_changed {
+            self.placeholder_paragraph = None;
+            self.mark_text_dirty();
+        }
+    }
+
+    fn mark_text_dirty(&mut self) {
+        // causes the lines to be recreated in layout()
+        self.lines_dirty = true;
+        self.mark_caret_dirty();
+    }
+
+    fn mark_caret_dirty(&mut self) {
+        self.caret_dirty = true;
+        self.caret_moved = true;
+        self.caret_blink.reset();
+    }
+
+    /// Returns the vertical extent of the caret.
+    fn caret_span(&self) -> (f32, f32) {
+        let line_height = self.style().line_height();
+        (self.caret_pos.y, self.caret_pos.y + line_height)
+    }
+
+    fn paragraph(&self, cv: &mut Canvas, color: Color, text: &str) -> TextBlob {
+        create_paragraph(cv, self.style(), color, text)
+    }
+
+    fn measure(&self, cv: &mut Canvas, text: &str) -> f32 {
+        self.paragraph(cv, self.style().font_color, text).size().x
+    }
+
+    /// Recreates the lines if the text or width changed.
+    fn update_lines(&mut self, cv: &mut Canvas) {
+        if !self.lines_dirty {
+            return;
+        }
+        self.lines_dirty = false;
+
+        self.composed = self.editor.composed_text();
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for hard_line in self.composed.split('\n') {
+            let range = start..start + hard_line.len();
+            if self.wrap {
+                self.wrap_line(cv, range.clone(), &mut ranges);
+            } else {
+                ranges.push(range.clone());
+            }
+            start = range.end + 1;
+        }
+
+        self.lines = ranges
+            .into_iter()
+            .map(|range| Line {
+                paragraph: self.paragraph(
+                    cv,
+                    self.style().font_color,
+                    &self.composed[range.clone()],
+                ),
+                range,
+            })
+            .collect();
+    }
+
+    /// Splits a line of text into lines that fit within `wrap_width`,
+    /// breaking between words.
+    fn wrap_line(&self, cv: &mut Canvas, range: Range<usize>, lines: &mut Vec<Range<usize>>) {
+        let mut line_start = range.start;
+        let mut line_width = 0.;
+        let mut word_start = range.start;
+        for word_end in word_ends(&self.composed[range.clone()]) {
+            let word_end = range.start + word_end;
+            let width = self.measure(cv, &self.composed[word_start..word_end]);
+            if line_width + width > self.wrap_width && word_start > line_start {
+                lines.push(line_start..word_start);
+                line_start = word_start;
+                line_width = 0.;
+            }
+            line_width += width;
+            word_start = word_end;
+        }
+        lines.push(line_start..range.end);
+    }
+
+    /// Returns the index of the line containing the given position in the composed text.
+    fn line_of(&self, index: usize) -> usize {
+        self.lines
+            .iter()
+            .rposition(|line| line.range.start <= index)
+            .unwrap_or(0)
+    }
+
+    /// Returns the horizontal offset of a position in the composed text.
+    fn x_of(&self, cv: &mut Canvas, index: usize) -> f32 {
+        let line = &self.lines[self.line_of(index)];
+        self.measure(cv, &self.composed[line.range.start..index])
+    }
+
+    /// Returns the position in the composed text nearest to `x` on the given line.
+    fn index_at_x(&self, cv: &mut Canvas, line: usize, x: f32) -> usize {
+        let range = self.lines[line].range.clone();
+        let boundaries: Vec<usize> = self.composed[range.clone()]
+            .char_indices()
+            .map(|(i, _)| range.start + i)
+            .chain(std::iter::once(range.end))
+            .collect();
+        let width = |cv: &mut Canvas, i: usize| {
+            self.measure(cv, &self.composed[range.start..boundaries[i]])
+        };
+
+        // Find the last boundary left of `x`, then
+        // check whether the next one is closer.
+        let mut low = longest_fitting(boundaries.len() - 1, |i| width(cv, i) <= x);
+        if low + 1 < boundaries.len() && width(cv, low + 1) - x < x - width(cv, low) {
+            low += 1;
+        }
+        boundaries[low]
+    }
+
+    /// Returns the position in the text nearest to a point.
+    fn index_at(&mut self, cv: &mut Canvas, pos: Vec2) -> usize {
+        self.update_lines(cv);
+        let line =
+            ((pos.y / self.style().line_height()).max(0.) as usize).min(self.lines.len() - 1);
+        let index = self.index_at_x(cv, line, pos.x);
+        self.editor.text_index(index)
+    }
+
+    /// Moves the caret up or down by `delta` lines.
+    fn move_vertically(&mut self, cv: &mut Canvas, delta: isize, select: bool) {
+        self.update_lines(cv);
+        let caret = self.editor.composed_index(self.editor.caret());
+        let x = match self.goal_x {
+            Some(x) => x,
+            None => self.x_of(cv, caret),
+        };
+
+        let line = self.line_of(caret) as isize + delta;
+        let target = if line < 0 {
+            0
+        } else if line as usize >= self.lines.len() {
+            self.composed.len()
+        } else {
+            self.index_at_x(cv, line as usize, x)
+        };
+        self.editor
+            .set_caret(self.editor.text_index(target), select);
+        self.goal_x = Some(x);
+    }
+
+    /// Moves the caret to the start or end of its line.
+    fn move_to_line_edge(&mut self, cv: &mut Canvas, end: bool, select: bool) {
+        self.update_lines(cv);
+        let caret = self.editor.composed_index(self.editor.caret());
+        let line = self.line_of(caret);
+        let range = &self.lines[line].range;
+        let target = if !end {
+            range.start
+        } else {
+            match self.lines.get(line + 1) {
+                // Stay before the break in a wrapped line; the
+                // end of the line is also the start of the next.
+                Some(next) if next.range.start == range.end && range.end > range.start => self
+                    .composed[..range.end]
+                    .char_indices()
+                    .next_back()
+                    .map_or(range.end, |(i, _)| i),
+                _ => range.end,
+            }
+        };
+        self.editor
+            .set_caret(self.editor.text_index(target), select);
+    }
+
+    /// Inserts text at the caret if the limits allow it.
+    /// Returns whether anything was inserted.
+    fn insert(&mut self, s: &str) -> bool {
+        if self.limits.allows(&self.editor.preview_insert(s)) {
+            self.editor.insert(s);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns rectangles covering the given range of the composed text.
+    fn span_rects(&self, cv: &mut Canvas, range: Range<usize>) -> Vec<Rect> {
+        let line_height = self.style().line_height();
+        let mut rects = Vec::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if line.range.end < range.start || line.range.start > range.end {
+                continue;
+            }
+            let start = range.start.max(line.range.start);
+            let end = range.end.min(line.range.end);
+            let x0 = self.measure(cv, &self.composed[line.range.start..start]);
+            let x1 = self.measure(cv, &self.composed[line.range.start..end]);
+            rects.push(Rect::new(
+                vec2(x0, i as f32 * line_height),
+                vec2(x1 - x0, line_height),
+            ));
+        }
+        rects
+    }
+
+    fn update_caret(&mut self, cv: &mut Canvas) {
+        if !self.caret_dirty {
+            return;
+        }
+        self.caret_dirty = false;
+
+        let caret = self.editor.composed_caret();
+        self.caret_pos = vec2(
+            self.x_of(cv, caret),
+            self.line_of(caret) as f32 * self.style().line_height(),
+        );
+
+        // Preedit text replaces the selection while composing.
+        self.selection_rects = match self.editor.selection() {
+            Some(selection) if self.editor.preedit().is_none() => {
+                let start = self.editor.composed_index(selection.start);
+                let end = self.editor.composed_index(selection.end);
+                self.span_rects(cv, start..end)
+            }
+            _ => Vec::new(),
+        };
+        self.preedit_rects = match self.editor.preedit_range() {
+            Some(range) => self.span_rects(cv, range),
+            None => Vec::new(),
+        };
+    }
+}
+
+/// Returns the end of each word in `text`, where a word
+/// includes the whitespace following it.
+fn word_ends(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut prev_whitespace = false;
+    text.char_indices()
+        .filter_map(move |(i, c)| {
+            let is_end = prev_whitespace && !c.is_whitespace();
+            prev_whitespace = c.is_whitespace();
+            if is_end {
+                Some(i)
+            } else {
+                None
+            }
+        })
+        .chain(std::iter::once(text.len()))
+}
+
+fn create_paragraph(cv: &mut Canvas, style: &Style, color: Color, text: &str) -> TextBlob {
+    let text = Text::from_sections([TextSection::Text {
+        text: text.into(),
+        style: TextStyle {
+            color: Some(color.into()),
+            size: Some(style.font_size),
+            font: font::query(&style.font, style.font_weight, style.font_style),
+        },
+    }]);
+
+    cv.context().create_text_blob(
+        text,
+        TextOptions {
+            wrap_lines: false,
+            baseline: Baseline::Top,
+            align_h: Align::Start,
+            align_v: Align::Start,
+        },
+    )
+}
+
+impl Widget for TextAreaBody {
+    type Style = ();
+
+    fn base_class(&self) -> &str {
+        "text_area_body"
+    }
+
+    fn layout(&mut self, _style: &Self::Style, data: &mut WidgetData, cx: Context, max_size: Vec2) {
+        if max_size.x != self.wrap_width {
+            self.wrap_width = max_size.x;
+            self.mark_text_dirty();
+        }
+
+        if self.placeholder_paragraph.is_none() {
+            self.placeholder_paragraph = Some(self.paragraph(
+                cx.canvas,
+                self.style().placeholder_font_color,
+                &self.placeholder,
+            ));
+        }
+
+        self.update_lines(cx.canvas);
+        self.update_caret(cx.canvas);
+
+        let height = self.lines.len() as f32 * self.style().line_height();
+        data.set_size(vec2(max_size.x, height.max(self.min_height)));
+    }
+
+    fn paint(&mut self, _style: &Self::Style, _data: &mut WidgetData, mut cx: Context) {
+        let style = self.style();
+        let line_height = style.line_height();
+        let cv = &mut cx.canvas;
+
+        for rect in &self.selection_rects {
+            cv.begin_path()
+                .rect(rect.pos, rect.size)
+                .solid_color(style.selection_color)
+                .fill();
+        }
+
+        if self.composed.is_empty() {
+            if let Some(placeholder) = &self.placeholder_paragraph {
+                cv.draw_text(placeholder, Vec2::ZERO, 1.);
+            }
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            cv.draw_text(&line.paragraph, vec2(0., i as f32 * line_height), 1.);
+        }
+
+        // Underline text being composed
+        for rect in &self.preedit_rects {
+            let y = rect.pos.y + style.font_size;
+            cv.begin_path()
+                .move_to(vec2(rect.pos.x, y))
+                .line_to(vec2(rect.pos.x + rect.size.x, y))
+                .stroke_width(1.)
+                .solid_color(style.font_color)
+                .stroke();
+        }
+
+        // Cursor
+        if self.focused {
+            cx.set_ime_caret(Rect::new(
+                self.caret_pos,
+                vec2(style.cursor_width, style.font_size),
+            ));
+        }
+
+        let cv = &mut cx.canvas;
+        if self.focused && self.caret_blink.is_visible() {
+            cv.begin_path()
+                .move_to(self.caret_pos)
+                .line_to(self.caret_pos + vec2(0., style.font_size))
+                .stroke_width(style.cursor_width)
+                .solid_color(style.cursor_color)
+                .stroke();
+        }
+    }
+
+    fn handle_event(&mut self, data: &mut WidgetData, mut cx: Context, event: &Event) {
+        if self.style.is_none() {
+            // Not laid out yet
+            return;
+        }
+
+        let focused = self.focused;
+        let caret = (self.editor.caret(), self.editor.selection());
+        let mut changed = false;
+        let mut keep_goal_x = false;
+
+        match event {
+            Event::MousePress {
+                pos,
+                button: MouseButton::Left,
+                mods,
+                ..
+            } => {
+                self.focused = data.bounds().contains(*pos);
+                if self.focused {
+                    let index = self.index_at(cx.canvas, *pos);
+                    self.editor.set_caret(index, mods.shift());
+                    self.dragging = true;
+                }
+            }
+            Event::MouseMove { pos } if self.dragging => {
+                let index = self.index_at(cx.canvas, *pos);
+                self.editor.set_caret(index, true);
+            }
+            Event::MouseRelease {
+                button: MouseButton::Left,
+                ..
+            } => self.dragging = false,
+            Event::KeyPress { key, mods } if self.focused => {
+                let shortcut = mods.ctrl() || mods.logo();
+                let select = mods.shift();
+                match key {
+                    VirtualKeyCode::Back => changed = self.editor.delete_backward(),
+                    VirtualKeyCode::Delete => changed = self.editor.delete_forward(),
+                    VirtualKeyCode::Left => self.editor.move_left(select),
+                    VirtualKeyCode::Right => self.editor.move_right(select),
+                    VirtualKeyCode::Up => {
+                        self.move_vertically(cx.canvas, -1, select);
+                        keep_goal_x = true;
+                    }
+                    VirtualKeyCode::Down => {
+                        self.move_vertically(cx.canvas, 1, select);
+                        keep_goal_x = true;
+                    }
+                    VirtualKeyCode::Home if shortcut => self.editor.move_home(select),
+                    VirtualKeyCode::End if shortcut => self.editor.move_end(select),
+                    VirtualKeyCode::Home => self.move_to_line_edge(cx.canvas, false, select),
+                    VirtualKeyCode::End => self.move_to_line_edge(cx.canvas, true, select),
+                    VirtualKeyCode::A if shortcut => self.editor.select_all(),
+                    VirtualKeyCode::Z if shortcut && mods.shift() => changed = self.editor.redo(),
+                    VirtualKeyCode::Z if shortcut => changed = self.editor.undo(),
+                    VirtualKeyCode::Y if shortcut => changed = self.editor.redo(),
+                    VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                        changed = self.insert("\n")
+                    }
+                    _ => keep_goal_x = true,
+                }
+            }
+            Event::CompositionUpdate { text, cursor } if self.focused => {
+                self.editor.set_preedit(text.clone(), *cursor);
+                self.mark_text_dirty();
+            }
+            Event::CompositionCommit { text } if self.focused => {
+                self.editor.clear_preedit();
+                changed = self.insert(text);
+                self.mark_text_dirty();
+            }
+            Event::Character(c) if self.focused && !c.is_control() => {
+                let mut buf = [0; 4];
+                changed = self.insert(c.encode_utf8(&mut buf));
+            }
+            _ => keep_goal_x = true,
+        }
+
+        if !keep_goal_x {
+            self.goal_x = None;
+        }
+
+        if changed {
+            self.mark_text_dirty();
+            if let Some(on_change) = self.on_change.as_mut() {
+                cx.send_message(on_change(self.editor.text()));
+            }
+        } else if caret != (self.editor.caret(), self.editor.selection()) {
+            self.mark_caret_dirty();
+        }
+
+        if focused && !self.focused {
+            self.editor.clear_preedit();
+            self.dragging = false;
+            self.mark_text_dirty();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_len: Some(5),
+            max_lines: Some(2),
+        };
+        assert!(limits.allows("ab\ncd"));
+        assert!(!limits.allows("ab\ncde"));
+        assert!(!limits.allows("a\nb\nc"));
+        assert!(limits.allows(""));
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            word_ends("one two  three").collect::<Vec<_>>(),
+            vec![4, 9, 14]
+        );
+        assert_eq!(word_ends(" a").collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(word_ends("").collect::<Vec<_>>(), vec![0]);
+    }
+}
@@ -1,34 +1,62 @@
-use std::time::Instant;
+use std::any::Any;
 
-use duit_core::spec::widgets::TextInputSpec;
+use duit_core::spec::widgets::{TextInputFilter, TextInputSpec};
 use dume::{
-    font::Query, Align, Baseline, Canvas, SmartString, Text, TextBlob, TextOptions, TextSection,
-    TextStyle,
+    Align, Baseline, Canvas, Rect, SmartString, Text, TextBlob, TextOptions, TextSection, TextStyle,
 };
 use glam::{vec2, Vec2};
+use regex::Regex;
 use winit::event::{MouseButton, VirtualKeyCode};
 
-use crate::{widget::Context, Color, Event, Widget, WidgetData};
+use crate::{
+    border::stroke_border,
+    editor::{impl_editing_methods, text_callback, CaretBlink, Editor, TextCallback},
+    font,
+    widget::Context,
+    Color, Event, FontStyle, FontWeight, Insets, Widget, WidgetData,
+};
+
+type ValidateFn = Box<dyn FnMut(&str) -> bool>;
+
+/// Style class added while the input fails validation.
+pub const CLASS_INVALID: &str = "invalid";
 
 pub struct TextInput {
     width: Option<f32>,
     placeholder: String,
-    max_len: Option<usize>,
     is_password: bool,
 
+    validator: Validator,
+    invalid: bool,
+
     placeholder_paragraph: Option<TextBlob>,
 
-    text: String,
+    editor: Editor,
     text_paragraph: Option<TextBlob>,
+    /// The text before the caret, used to find the caret position.
+    caret_paragraph: Option<TextBlob>,
+    /// Horizontal extent of the selected text.
+    selection_span: Option<(f32, f32)>,
+    /// Horizontal extent of the text being composed by an input method.
+    preedit_span: Option<(f32, f32)>,
 
     focused: bool,
+    caret_blink: CaretBlink,
 
-    last_change: Instant,
-    create_time: Instant,
+    on_change: Option<TextCallback>,
+    on_submit: Option<TextCallback>,
+    on_focus_lost: Option<TextCallback>,
 }
 
 impl TextInput {
+    /// Panics if the spec's filter regex is invalid.
+    /// [`Ui::add_spec`](crate::Ui::add_spec) rejects such specs.
     pub fn from_spec(spec: &TextInputSpec) -> Self {
+        let mut editor = Editor::new();
+        if let Some(depth) = spec.history_depth {
+            editor.set_history_depth(depth);
+        }
+
         Self {
             width: spec.width,
 
@@ -36,24 +64,116 @@ impl TextInput {
             placeholder_paragraph: None,
 
             is_password: spec.is_password,
-            max_len: spec.max_len,
 
-            text: String::new(),
+            validator: Validator::from_spec(spec),
+            invalid: false,
+
+            editor,
             text_paragraph: None,
+            caret_paragraph: None,
+            selection_span: None,
+            preedit_span: None,
 
             focused: false,
+            caret_blink: CaretBlink::new(),
 
-            last_change: Instant::now(),
-            create_time: Instant::now(),
+            on_change: None,
+            on_submit: None,
+            on_focus_lost: None,
         }
     }
 
     pub fn current_input(&self) -> &str {
-        &self.text
+        self.editor.text()
+    }
+
+    /// Replaces the current input and moves the caret to the end.
+    ///
+    /// The text is filtered as if the user typed it: characters
+    /// that `max_len` or the spec's filter would reject are dropped.
+    /// The replacement can be undone like any other edit.
+    /// Does not trigger the `on_change` message.
+    pub fn set_text(&mut self, text: impl Into<String>) -> &mut Self {
+        let text = self.validator.filtered(&text.into());
+        self.editor.set_text(text);
+        self.mark_text_dirty();
+        self
+    }
+
+    /// Clears the current input.
+    ///
+    /// Does not trigger the `on_change` message.
+    pub fn clear(&mut self) -> &mut Self {
+        self.set_text(String::new())
+    }
+
+    /// Causes a message to be sent when the user presses Enter
+    /// while the input is focused. Nothing is sent while the
+    /// text is invalid; see [`TextInput::is_valid`].
+    ///
+    /// If an `on_submit` message is already set, it is overriden.
+    pub fn on_submit<Message: 'static>(
+        &mut self,
+        message: impl FnMut(&str) -> Message + 'static,
+    ) -> &mut Self {
+        self.on_submit = Some(text_callback(message));
+        self
+    }
+
+    /// Causes a message to be sent when the input loses focus.
+    ///
+    /// If an `on_focus_lost` message is already set, it is overriden.
+    pub fn on_focus_lost<Message: 'static>(
+        &mut self,
+        message: impl FnMut(&str) -> Message + 'static,
+    ) -> &mut Self {
+        self.on_focus_lost = Some(text_callback(message));
+        self
+    }
+
+    /// Sets a function that decides whether the text is valid.
+    ///
+    /// Called after every change, in addition to the
+    /// filter set in the spec. While either one fails,
+    /// the input has the `invalid` style class.
+    ///
+    /// If a validation function is already set, it is overriden.
+    pub fn on_validate(&mut self, validate: impl FnMut(&str) -> bool + 'static) -> &mut Self {
+        self.validator.on_validate = Some(Box::new(validate));
+        self.mark_text_dirty();
+        self
+    }
+
+    /// Returns whether the current text passed validation
+    /// the last time it was checked.
+    pub fn is_valid(&self) -> bool {
+        !self.invalid
+    }
+
+    fn edit<R>(&mut self, f: impl FnOnce(&mut Editor) -> R) -> R {
+        let result = f(&mut self.editor);
+        self.mark_text_dirty();
+        result
+    }
+
+    fn read_editor<R>(&self, f: impl FnOnce(&Editor) -> R) -> R {
+        f(&self.editor)
+    }
+
+    fn set_change_callback(&mut self, callback: TextCallback) {
+        self.on_change = Some(callback);
+    }
+
+    fn display_text(&self, text: &str) -> String {
+        if self.is_password {
+            make_password_text(text)
+        } else {
+            text.to_owned()
+        }
     }
 
     fn paragraph_to_draw(&self) -> &TextBlob {
-        if self.text.is_empty() {
+        if self.editor.text().is_empty() && self.editor.preedit().is_none() {
             self.placeholder_paragraph
                 .as_ref()
                 .expect("placeholder paragraph not created")
@@ -64,10 +184,146 @@ impl TextInput {
         }
     }
 
+    /// The displayed text up to the caret, including text being composed.
+    fn text_before_caret(&self) -> String {
+        let composed = self.editor.composed_text();
+        self.display_text(&composed[..self.editor.composed_caret()])
+    }
+
     fn mark_text_dirty(&mut self) {
-        // causes the paragraph to be recreated in layout()
+        // causes the paragraphs to be recreated in layout()
         self.text_paragraph = None;
+        self.caret_paragraph = None;
+        self.caret_blink.reset();
+    }
+
+    /// Causes the caret and selection to be laid out again,
+    /// keeping the text's paragraph.
+    fn mark_caret_dirty(&mut self) {
+        self.caret_paragraph = None;
+        self.caret_blink.reset();
+    }
+
+    fn update_validity(&mut self, data: &mut WidgetData) {
+        let invalid = !self.validator.is_valid(self.editor.text());
+        if invalid != self.invalid {
+            self.invalid = invalid;
+            if invalid {
+                data.add_class(CLASS_INVALID);
+            } else {
+                data.remove_class(CLASS_INVALID);
+            }
+        }
+    }
+}
+
+impl_editing_methods!(TextInput);
+
+/// Restricts and validates the contents of a `TextInput`.
+struct Validator {
+    filter: Option<Filter>,
+    min: Option<f64>,
+    max: Option<f64>,
+    max_len: Option<usize>,
+    on_validate: Option<ValidateFn>,
+}
+
+enum Filter {
+    Integer,
+    Float,
+    Alphanumeric,
+    Regex(Regex),
+}
+
+impl Validator {
+    fn from_spec(spec: &TextInputSpec) -> Self {
+        let filter = spec.filter.as_ref().map(|filter| match filter {
+            TextInputFilter::Integer => Filter::Integer,
+            TextInputFilter::Float => Filter::Float,
+            TextInputFilter::Alphanumeric => Filter::Alphanumeric,
+            TextInputFilter::Regex(pattern) => Filter::Regex(
+                // Anchor the pattern so it must match the whole text.
+                Regex::new(&format!("^(?:{})$", pattern)).expect("invalid TextInput filter regex"),
+            ),
+        });
+        Self {
+            filter,
+            min: spec.min,
+            max: spec.max,
+            max_len: spec.max_len,
+            on_validate: None,
+        }
+    }
+
+    /// Returns whether the user may edit the text so that it becomes `text`.
+    ///
+    /// Rejects anything that cannot be completed into valid input,
+    /// like letters in a numeric field.
+    fn allows(&self, text: &str) -> bool {
+        if let Some(max_len) = self.max_len {
+            if text.chars().count() > max_len {
+                return false;
+            }
+        }
+
+        match &self.filter {
+            Some(Filter::Integer) => is_number_prefix(text, false),
+            Some(Filter::Float) => is_number_prefix(text, true),
+            Some(Filter::Alphanumeric) => text.chars().all(char::is_alphanumeric),
+            Some(Filter::Regex(_)) | None => true,
+        }
+    }
+
+    /// Keeps the characters of `text` that could be typed one by one.
+    fn filtered(&self, text: &str) -> String {
+        let mut kept = String::with_capacity(text.len());
+        for c in text.chars() {
+            kept.push(c);
+            if !self.allows(&kept) {
+                kept.pop();
+            }
+        }
+        kept
+    }
+
+    /// Returns whether `text` is valid.
+    ///
+    /// Empty text always passes the filter, so that
+    /// blank fields are not shown as invalid.
+    fn is_valid(&mut self, text: &str) -> bool {
+        let passes_filter = text.is_empty()
+            || match &self.filter {
+                Some(Filter::Integer) => {
+                    matches!(text.parse::<i64>(), Ok(x) if self.in_range(x as f64))
+                }
+                Some(Filter::Float) => matches!(text.parse::<f64>(), Ok(x) if self.in_range(x)),
+                Some(Filter::Alphanumeric) | None => true,
+                Some(Filter::Regex(regex)) => regex.is_match(text),
+            };
+        let passes_callback = match &mut self.on_validate {
+            Some(validate) => validate(text),
+            None => true,
+        };
+        passes_filter && passes_callback
     }
+
+    fn in_range(&self, x: f64) -> bool {
+        !matches!(self.min, Some(min) if x < min) && !matches!(self.max, Some(max) if x > max)
+    }
+}
+
+/// Returns whether `text` could be the beginning of a number.
+fn is_number_prefix(text: &str, allow_decimal: bool) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let mut seen_point = false;
+    digits.chars().all(|c| match c {
+        '0'..='9' => true,
+        '.' if allow_decimal && !seen_point => {
+            seen_point = true;
+            true
+        }
+        _ => false,
+    })
 }
 
 fn make_password_text(text: &str) -> String {
@@ -78,15 +334,20 @@ fn make_password_text(text: &str) -> String {
 pub struct Style {
     background_color: Color,
     border_color: Color,
-    border_width: f32,
+    border_width: Insets,
     border_radius: f32,
     cursor_color: Color,
     cursor_width: f32,
     font: String,
     font_size: f32,
+    #[serde(default)]
+    font_weight: FontWeight,
+    #[serde(default)]
+    font_style: FontStyle,
     font_color: Color,
     placeholder_font_color: Color,
-    padding: f32,
+    selection_color: Color,
+    padding: Insets,
 }
 
 fn create_paragraph(cv: &mut Canvas, style: &Style, color: Color, text: SmartString) -> TextBlob {
@@ -95,10 +356,7 @@ fn create_paragraph(cv: &mut Canvas, style: &Style, color: Color, text: SmartStr
         style: TextStyle {
             color: Some(color.into()),
             size: Some(style.font_size),
-            font: Query {
-                family: Some(style.font.clone().into()),
-                ..Default::default()
-            },
+            font: font::query(&style.font, style.font_weight, style.font_style),
         },
     }]);
 
@@ -120,6 +378,12 @@ impl Widget for TextInput {
         "text_input"
     }
 
+    fn style_changed(&mut self, _style: &Self::Style, _data: &mut WidgetData, _cx: Context) {
+        // Re-create the paragraphs so text style is updated.
+        self.placeholder_paragraph = None;
+        self.mark_text_dirty();
+    }
+
     fn layout(&mut self, style: &Self::Style, data: &mut WidgetData, cx: Context, max_size: Vec2) {
         if self.placeholder_paragraph.is_none() {
             self.placeholder_paragraph = Some(create_paragraph(
@@ -131,12 +395,7 @@ impl Widget for TextInput {
         }
 
         if self.text_paragraph.is_none() {
-            let text = if !self.is_password {
-                self.text.clone()
-            } else {
-                make_password_text(&self.text)
-            };
-
+            let text = self.display_text(&self.editor.composed_text());
             self.text_paragraph = Some(create_paragraph(
                 cx.canvas,
                 style,
@@ -145,12 +404,46 @@ impl Widget for TextInput {
             ));
         }
 
+        if self.caret_paragraph.is_none() {
+            // Text may have been replaced programmatically.
+            self.update_validity(data);
+
+            let composed = self.editor.composed_text();
+            let text = self.text_before_caret();
+            self.caret_paragraph = Some(create_paragraph(
+                cx.canvas,
+                style,
+                style.font_color,
+                text.into(),
+            ));
+
+            let mut measure = |end: usize| {
+                let text = self.display_text(&composed[..end]);
+                create_paragraph(cx.canvas, style, style.font_color, text.into())
+                    .size()
+                    .x
+            };
+            let selection_span = match self.editor.selection() {
+                Some(selection) if self.editor.preedit().is_none() => Some((
+                    measure(self.editor.composed_index(selection.start)),
+                    measure(self.editor.composed_index(selection.end)),
+                )),
+                _ => None,
+            };
+            let preedit_span = self
+                .editor
+                .preedit_range()
+                .map(|range| (measure(range.start), measure(range.end)));
+            self.selection_span = selection_span;
+            self.preedit_span = preedit_span;
+        }
+
         let width = match self.width {
             Some(x) => x,
             None => max_size.x,
         };
 
-        let height = style.font_size + 2. * style.padding;
+        let height = style.font_size + style.padding.vertical();
 
         data.set_size(vec2(width, height));
     }
@@ -163,21 +456,50 @@ impl Widget for TextInput {
             .solid_color(style.background_color)
             .fill();
 
-        cv.solid_color(style.border_color)
-            .stroke_width(style.border_width)
-            .stroke();
+        stroke_border(
+            cv,
+            Rect::new(Vec2::ZERO, data.size()),
+            style.border_radius,
+            style.border_width,
+            style.border_color,
+        );
 
-        let text_pos = Vec2::new(style.padding, style.padding / 2.);
+        let text_pos = Vec2::new(style.padding.left, style.padding.top / 2.);
+
+        if let Some((start, end)) = self.selection_span {
+            cv.begin_path()
+                .rect(
+                    vec2(text_pos.x + start, text_pos.y),
+                    vec2(end - start, style.font_size),
+                )
+                .solid_color(style.selection_color)
+                .fill();
+        }
 
         cv.draw_text(self.paragraph_to_draw(), text_pos, 1.);
 
+        // Underline text being composed
+        if let Some((start, end)) = self.preedit_span {
+            let y = text_pos.y + style.font_size;
+            cv.begin_path()
+                .move_to(vec2(text_pos.x + start, y))
+                .line_to(vec2(text_pos.x + end, y))
+                .stroke_width(1.)
+                .solid_color(style.font_color)
+                .stroke();
+        }
+
         // Cursor
-        let time = self.create_time.elapsed().as_secs_f32();
-        if self.focused
-            && (self.last_change.elapsed().as_secs_f32() <= 0.75 || (time * 2.0) as u32 % 2 == 0)
-        {
-            let cursor_pos = text_pos + vec2(self.text_paragraph.as_ref().unwrap().size().x, 0.);
+        let cursor_pos = text_pos + vec2(self.caret_paragraph.as_ref().unwrap().size().x, 0.);
+        if self.focused {
+            cx.set_ime_caret(Rect::new(
+                cursor_pos,
+                vec2(style.cursor_width, style.font_size),
+            ));
+        }
 
+        let cv = &mut cx.canvas;
+        if self.focused && self.caret_blink.is_visible() {
             cv.begin_path()
                 .move_to(cursor_pos)
                 .line_to(cursor_pos + vec2(0., style.font_size))
@@ -187,8 +509,25 @@ impl Widget for TextInput {
         }
     }
 
-    fn handle_event(&mut self, data: &mut WidgetData, _cx: Context, event: &Event) {
+    fn handle_event(&mut self, data: &mut WidgetData, mut cx: Context, event: &Event) {
+        self.handle_input(data, event, |message| cx.send_message(message));
+    }
+}
+
+impl TextInput {
+    /// Handles an event, passing messages to `send_message`.
+    ///
+    /// Separate from `handle_event` so that tests
+    /// can feed events without a canvas.
+    fn handle_input(
+        &mut self,
+        data: &mut WidgetData,
+        event: &Event,
+        mut send_message: impl FnMut(Box<dyn Any>),
+    ) {
         let focused = self.focused;
+        let caret = (self.editor.caret(), self.editor.selection());
+        let mut changed = false;
 
         match event {
             Event::MousePress {
@@ -198,28 +537,237 @@ impl Widget for TextInput {
             } => {
                 self.focused = data.bounds().contains(*pos);
             }
-            Event::KeyPress { key, .. } => {
-                if self.focused && matches!(key, VirtualKeyCode::Back | VirtualKeyCode::Delete) {
-                    self.text.pop();
-                    self.mark_text_dirty();
+            Event::KeyPress { key, mods } if self.focused => {
+                let shortcut = mods.ctrl() || mods.logo();
+                let select = mods.shift();
+                match key {
+                    VirtualKeyCode::Back => changed = self.editor.delete_backward(),
+                    VirtualKeyCode::Delete => changed = self.editor.delete_forward(),
+                    VirtualKeyCode::Left => self.editor.move_left(select),
+                    VirtualKeyCode::Right => self.editor.move_right(select),
+                    VirtualKeyCode::Home => self.editor.move_home(select),
+                    VirtualKeyCode::End => self.editor.move_end(select),
+                    VirtualKeyCode::A if shortcut => self.editor.select_all(),
+                    VirtualKeyCode::Z if shortcut && mods.shift() => changed = self.editor.redo(),
+                    VirtualKeyCode::Z if shortcut => changed = self.editor.undo(),
+                    VirtualKeyCode::Y if shortcut => changed = self.editor.redo(),
+                    VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter if self.is_valid() => {
+                        if let Some(on_submit) = self.on_submit.as_mut() {
+                            send_message(on_submit(self.editor.text()));
+                        }
+                    }
+                    _ => {}
                 }
             }
+            Event::CompositionUpdate { text, cursor } if self.focused => {
+                self.editor.set_preedit(text.clone(), *cursor);
+                self.mark_text_dirty();
+            }
+            Event::CompositionCommit { text } if self.focused => {
+                self.editor.clear_preedit();
+                if self.validator.allows(&self.editor.preview_insert(text)) {
+                    self.editor.insert(text);
+                    changed = true;
+                }
+                self.mark_text_dirty();
+            }
             Event::Character(c) if self.focused && !c.is_control() => {
-                if Some(self.text.len()) != self.max_len {
-                    self.text.push(*c);
-                    self.mark_text_dirty();
+                let mut buf = [0; 4];
+                let c = c.encode_utf8(&mut buf);
+                if self.validator.allows(&self.editor.preview_insert(c)) {
+                    self.editor.insert(c);
+                    changed = true;
                 }
             }
             _ => {}
         }
 
+        if changed {
+            self.mark_text_dirty();
+            self.update_validity(data);
+            if let Some(on_change) = self.on_change.as_mut() {
+                send_message(on_change(self.editor.text()));
+            }
+        } else if caret != (self.editor.caret(), self.editor.selection()) {
+            self.mark_caret_dirty();
+        }
+
+        if focused && !self.focused {
+            self.editor.clear_preedit();
+            self.mark_text_dirty();
+            if let Some(on_focus_lost) = self.on_focus_lost.as_mut() {
+                send_message(on_focus_lost(self.editor.text()));
+            }
+        }
+
         if focused != self.focused {
             // Update style classes
-            if self.focused {
-                data.add_class("focused");
-            } else {
-                data.remove_class("focused");
-            }
+            data.set_focused(self.focused);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use winit::event::ModifiersState;
+
+    use super::*;
+
+    fn validator(filter: Option<Filter>) -> Validator {
+        Validator {
+            filter,
+            min: None,
+            max: None,
+            max_len: None,
+            on_validate: None,
+        }
+    }
+
+    #[test]
+    fn integer_filter() {
+        let mut v = validator(Some(Filter::Integer));
+        v.min = Some(-10.);
+        v.max = Some(10.);
+
+        assert!(v.allows("-"));
+        assert!(v.allows("-12"));
+        assert!(!v.allows("1.5"));
+        assert!(!v.allows("1a"));
+
+        assert!(v.is_valid(""));
+        assert!(v.is_valid("-10"));
+        assert!(!v.is_valid("-"));
+        assert!(!v.is_valid("11"));
+    }
+
+    #[test]
+    fn float_filter() {
+        let mut v = validator(Some(Filter::Float));
+        assert!(v.allows("-0."));
+        assert!(!v.allows("0.1.2"));
+        assert!(v.is_valid("3.25"));
+        assert!(!v.is_valid("."));
+    }
+
+    #[test]
+    fn regex_filter() {
+        let mut v = validator(Some(Filter::Regex(Regex::new("^(?:[a-z]+)$").unwrap())));
+        assert!(v.allows("ABC"));
+        assert!(v.is_valid("abc"));
+        assert!(!v.is_valid("abc1"));
+    }
+
+    #[test]
+    fn max_len_counts_chars() {
+        let mut v = validator(Some(Filter::Alphanumeric));
+        v.max_len = Some(2);
+        assert!(v.allows("éé"));
+        assert!(!v.allows("ééé"));
+        assert!(!v.allows("a b"));
+    }
+
+    fn text_input(yaml: &str) -> TextInput {
+        TextInput::from_spec(&serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn composition_over_selection() {
+        let mut input = text_input("max_len: 10");
+        let mut data = WidgetData::default();
+        let mut messages = Vec::new();
+        input.set_text("abcdef");
+        input.focused = true;
+        input.editor.select_all();
+
+        input.handle_input(
+            &mut data,
+            &Event::CompositionUpdate {
+                text: "x".to_owned(),
+                cursor: None,
+            },
+            |message| messages.push(message),
+        );
+        assert_eq!(input.editor.composed_text(), "x");
+        assert_eq!(input.text_before_caret(), "x");
+        assert_eq!(input.current_input(), "abcdef");
+
+        input.handle_input(
+            &mut data,
+            &Event::CompositionCommit {
+                text: "日本".to_owned(),
+            },
+            |message| messages.push(message),
+        );
+        assert_eq!(input.current_input(), "日本");
+        assert_eq!(input.text_before_caret(), "日本");
+        assert!(input.editor.preedit().is_none());
+    }
+
+    #[test]
+    fn keyboard_selection() {
+        let mut input = text_input("max_len: 10");
+        let mut data = WidgetData::default();
+        input.set_text("abcdef");
+        input.focused = true;
+        let mut press = |input: &mut TextInput, key, mods| {
+            input.handle_input(&mut data, &Event::KeyPress { key, mods }, |_| {})
+        };
+
+        press(&mut input, VirtualKeyCode::End, ModifiersState::empty());
+        press(&mut input, VirtualKeyCode::Left, ModifiersState::SHIFT);
+        press(&mut input, VirtualKeyCode::Left, ModifiersState::SHIFT);
+        assert_eq!(input.editor.selected_text(), Some("ef"));
+
+        press(&mut input, VirtualKeyCode::A, ModifiersState::CTRL);
+        assert_eq!(input.editor.selected_text(), Some("abcdef"));
+
+        press(&mut input, VirtualKeyCode::Home, ModifiersState::empty());
+        assert_eq!(input.editor.selection(), None);
+    }
+
+    #[test]
+    fn set_text_is_filtered() {
+        let mut input = text_input("{ filter: Integer, max_len: 4 }");
+        input.set_text("-1a2.345");
+        assert_eq!(input.current_input(), "-123");
+
+        let mut input = text_input("filter: Alphanumeric");
+        input.set_text("a b-c");
+        assert_eq!(input.current_input(), "abc");
+    }
+
+    #[test]
+    fn invalid_text_is_not_submitted() {
+        let mut input = text_input("filter: Integer");
+        input.on_submit(|text| text.to_owned());
+        input.focused = true;
+        let mut data = WidgetData::default();
+        let mut submitted = Vec::new();
+        let mut handle = |input: &mut TextInput, event: Event| {
+            input.handle_input(&mut data, &event, |message| {
+                if let Ok(text) = message.downcast::<String>() {
+                    submitted.push(*text);
+                }
+            })
+        };
+        let enter = || Event::KeyPress {
+            key: VirtualKeyCode::Return,
+            mods: ModifiersState::empty(),
+        };
+
+        handle(&mut input, Event::Character('-'));
+        assert!(!input.is_valid());
+        handle(&mut input, enter());
+        handle(&mut input, Event::Character('1'));
+        handle(&mut input, enter());
+        assert_eq!(submitted, ["-1"]);
+    }
+
+    #[test]
+    fn on_validate() {
+        let mut v = validator(None);
+        v.on_validate = Some(Box::new(|text| text != "admin"));
+        assert!(v.is_valid("user"));
+        assert!(!v.is_valid("admin"));
+    }
+}
@@ -1,6 +1,6 @@
 use glam::{vec2, Vec2};
 
-use crate::{widget::Context, Event, Widget, WidgetData};
+use crate::{widget::Context, Event, Rect, Shadow, Widget, WidgetData};
 
 const CHILD_INDEX: usize = 0;
 const TOOLTIP_INDEX: usize = 1;
@@ -17,8 +17,16 @@ impl Tooltip {
     }
 }
 
+#[derive(Debug, serde::Deserialize)]
+pub struct Style {
+    /// Cast by the tooltip while it is shown.
+    shadow: Option<Shadow>,
+    #[serde(default)]
+    border_radius: f32,
+}
+
 impl Widget for Tooltip {
-    type Style = ();
+    type Style = Style;
 
     fn base_class(&self) -> &str {
         "tooltip"
@@ -38,12 +46,12 @@ impl Widget for Tooltip {
         let mut tooltip = data.child(TOOLTIP_INDEX);
         tooltip.layout(&mut cx, Vec2::splat(f32::INFINITY));
         let pos = vec2(
-            -tooltip.data().size().x - 10.,
-            -tooltip.data().size().y / 2. + child.data().size().y / 2.,
+            -tooltip.data().outer_size().x - 10.,
+            -tooltip.data().outer_size().y / 2. + child.data().size().y / 2.,
         );
         tooltip.data_mut().set_origin(pos);
 
-        let size = child.data().size();
+        let size = child.data().outer_size();
         drop(child);
         drop(tooltip);
         data.set_size(size);
@@ -53,9 +61,14 @@ impl Widget for Tooltip {
         data.child(CHILD_INDEX).paint(&mut cx);
     }
 
-    fn paint_overlay(&mut self, _style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
+    fn paint_overlay(&mut self, style: &Self::Style, data: &mut WidgetData, mut cx: Context) {
         if self.showing_tooltip {
-            data.child(TOOLTIP_INDEX).paint(&mut cx);
+            let mut tooltip = data.child(TOOLTIP_INDEX);
+            if let Some(shadow) = &style.shadow {
+                let bounds = Rect::new(tooltip.data().box_origin(), tooltip.data().size());
+                shadow.paint_outer(cx.canvas, bounds, style.border_radius);
+            }
+            tooltip.paint(&mut cx);
         }
     }
 
@@ -4,6 +4,7 @@ use dume::{Canvas, Rect};
 use glam::Vec2;
 
 use crate::{
+    native_window::NativeWindowId,
     style::StyleEngine,
     widget::{Context, HitTestResult, WidgetPodHandle},
     Event,
@@ -23,15 +24,26 @@ pub(crate) struct Window {
     root: WidgetPodHandle,
     positioner: Box<dyn WindowPositioner>,
     pub(crate) z_index: u64,
+    /// The native window this window is displayed in.
+    pub(crate) native: NativeWindowId,
+    /// Set when styles changed since the last render.
+    pub(crate) needs_restyle: bool,
     hidden: bool,
 }
 
 impl Window {
-    pub fn new(root: WidgetPodHandle, positioner: impl WindowPositioner, z_index: u64) -> Self {
+    pub fn new(
+        root: WidgetPodHandle,
+        positioner: impl WindowPositioner,
+        z_index: u64,
+        native: NativeWindowId,
+    ) -> Self {
         Self {
             root,
             positioner: Box::new(positioner),
             z_index,
+            native,
+            needs_restyle: false,
             hidden: false,
         }
     }
@@ -41,6 +53,7 @@ impl Window {
         canvas: &mut Canvas,
         style_engine: &mut StyleEngine,
         messages: &mut VecDeque<Box<dyn Any>>,
+        ime_caret: &mut Option<Rect>,
         available_space: Vec2,
     ) {
         if self.hidden {
@@ -54,8 +67,17 @@ impl Window {
             canvas,
             style_engine,
             messages,
+            origin: layout.pos,
+            ime_caret,
+            ancestors: &mut Vec::new(),
+            clips: &mut Vec::new(),
         };
 
+        if self.needs_restyle {
+            root.restyle(&mut cx);
+            self.needs_restyle = false;
+        }
+
         cx.canvas.translate(layout.pos);
 
         root.layout(&mut cx, layout.size);
@@ -70,17 +92,24 @@ impl Window {
         canvas: &mut Canvas,
         style_engine: &mut StyleEngine,
         messages: &mut VecDeque<Box<dyn Any>>,
+        ime_caret: &mut Option<Rect>,
         event: &Event,
         available_space: Vec2,
     ) -> HitTestResult {
+        let origin = self.positioner.compute_position(available_space).pos;
+
         let mut root = self.root.borrow_mut();
         let mut cx = Context {
             canvas,
             style_engine,
             messages,
+            origin,
+            ime_caret,
+            ancestors: &mut Vec::new(),
+            clips: &mut Vec::new(),
         };
 
-        let event = event.translated(-self.positioner.compute_position(available_space).pos);
+        let event = event.translated(-origin);
 
         root.handle_event(&mut cx, &event);
 